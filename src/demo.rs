@@ -4,7 +4,7 @@
 //! as well as database initialization.
 
 use crate::entities::character::Character;
-use crate::entities::database::Database;
+use crate::entities::database::{Database, DEFAULT_DB_PATH};
 
 /// Runs a demonstration of the TTRPG system.
 ///
@@ -25,7 +25,7 @@ pub fn demo() {
     println!("=== TTRPG System Demo ===\n");
 
     // Check for database and create new databse if no database found on start.
-    let _db = Database::new("src/database/game_data.db").expect("Failed to initialize database");
+    let _db = Database::new(DEFAULT_DB_PATH).expect("Failed to initialize database");
 
     // Create a default character
     let default_char = Character::new("Default Character".to_string());
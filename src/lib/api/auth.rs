@@ -0,0 +1,362 @@
+//! Token authentication and role checks.
+//!
+//! Every request except `GET /api/health` and `GET /api/openapi.json` needs a token issued for a game
+//! (see `Database::issue_token`), sent as `Authorization: Bearer <token>` or,
+//! for WebSocket clients that can't set headers, as `?access_token=<token>`.
+//!
+//! The `authorize` middleware runs on every route. It rejects requests for
+//! another game's paths and applies the rule for the matched route:
+//!
+//! - GMs can do everything in their game, and edit object definitions no
+//!   other game uses
+//! - players can read the game and change only the character their token
+//!   controls; inventories and Foundry exports are private to the owner, who
+//!   can discard or give away items but not add them or re-import the sheet
+//! - observers can read but change nothing
+//!
+//! Routes whose rule depends on the request body (rolls, trades) finish the
+//! check in the handler through the `Access` extractor. Routes without a rule
+//! are GM-only, so a new route is never accidentally public.
+
+use std::collections::HashMap;
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, MatchedPath, Path, Query, Request, State},
+    http::{header, request::Parts, Method},
+    middleware::Next,
+    response::Response,
+};
+use serde::Deserialize;
+
+use super::error::ApiError;
+use super::state::AppState;
+use crate::entities::pool::DbPool;
+use crate::entities::tokens::{ApiToken, Role};
+
+/// Who is making a request, as established by `authorize`.
+#[derive(Debug, Clone)]
+pub enum Access {
+    /// Authentication is turned off in the server config
+    Unrestricted,
+    /// The request carried this valid token
+    Token(ApiToken),
+}
+
+impl Access {
+    /// True for the GM of `game` (or when authentication is off).
+    pub fn is_gm(&self, game: &str) -> bool {
+        match self {
+            Access::Unrestricted => true,
+            Access::Token(token) => token.role == Role::Gm && token.game == game,
+        }
+    }
+
+    /// True if the caller may change, and see private data of, a character:
+    /// the GM of its game or the player controlling it.
+    pub fn controls(&self, game: &str, character_uuid: &str) -> bool {
+        match self {
+            Access::Unrestricted => true,
+            Access::Token(token) => {
+                token.game == game
+                    && (token.role == Role::Gm
+                        || token.character_uuid.as_deref() == Some(character_uuid))
+            }
+        }
+    }
+
+    /// Fails unless the caller has access to `game` at all.
+    pub fn require_game(&self, game: &str) -> Result<(), ApiError> {
+        match self {
+            Access::Token(token) if token.game != game => Err(ApiError::Forbidden(format!(
+                "Token is not valid for game {}",
+                game
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails unless the caller is the GM of `game`.
+    pub fn require_gm(&self, game: &str) -> Result<(), ApiError> {
+        self.require_game(game)?;
+        if self.is_gm(game) {
+            Ok(())
+        } else {
+            Err(ApiError::Forbidden("Only the GM can do this".to_string()))
+        }
+    }
+
+    /// Fails unless the caller controls the character (see `controls`).
+    pub fn require_character(&self, game: &str, character_uuid: &str) -> Result<(), ApiError> {
+        self.require_game(game)?;
+        if self.controls(game, character_uuid) {
+            Ok(())
+        } else {
+            Err(ApiError::Forbidden(format!(
+                "Only the GM or the character's player can do this to {}",
+                character_uuid
+            )))
+        }
+    }
+
+    /// Fails unless the caller is the GM of every game in `games`, such as
+    /// the games using a shared object definition.
+    pub fn require_gm_of_all(&self, games: &[String]) -> Result<(), ApiError> {
+        match self {
+            Access::Token(token) if games.iter().any(|game| !self.is_gm(game)) => {
+                Err(ApiError::Forbidden(format!(
+                    "Only a GM of every game using this can do this; used in {}",
+                    games
+                        .iter()
+                        .filter(|game| **game != token.game)
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// True while the caller's token is unrevoked, for connections that
+    /// outlive the request that authenticated them.
+    pub async fn is_still_valid(&self, db: &DbPool) -> bool {
+        match self {
+            Access::Unrestricted => true,
+            Access::Token(token) => {
+                let id = token.id;
+                db.run(move |db| db.get_token(id))
+                    .await
+                    .is_ok_and(|token| token.is_some_and(|token| token.revoked_at.is_none()))
+            }
+        }
+    }
+
+    fn is_observer(&self) -> bool {
+        matches!(self, Access::Token(token) if token.role == Role::Observer)
+    }
+
+    fn is_gm_anywhere(&self) -> bool {
+        match self {
+            Access::Unrestricted => true,
+            Access::Token(token) => token.role == Role::Gm,
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Access {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts.extensions.get::<Access>().cloned().ok_or_else(|| {
+            ApiError::Internal("route is not behind the authorize middleware".to_string())
+        })
+    }
+}
+
+/// What a route demands of the caller, on top of belonging to the path's game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Rule {
+    /// Anyone, with or without a token
+    Public,
+    /// Any token holder; observers only for GET
+    Member,
+    /// The GM of the path's game
+    Gm,
+    /// The GM of any game (for data shared between games)
+    AnyGm,
+    /// The GM, or the player controlling the path's `:id` character
+    Character,
+}
+
+/// The rule for a matched route; unlisted routes are GM-only.
+fn rule(method: &Method, route: &str) -> Rule {
+    let read = *method == Method::GET;
+    match route {
+        "/api/health" | "/api/openapi.json" if read => Rule::Public,
+        "/api/rolls/:id" | "/api/games/:game/characters" if read => Rule::Member,
+        "/api/games/:game/characters/:id" if read => Rule::Member,
+        "/api/games/:game/characters/:id" if *method == Method::DELETE => Rule::Gm,
+        "/api/games/:game/characters/:id" => Rule::Character,
+        // Re-importing can create object definitions and adding or resizing
+        // stacks conjures items, so players only read, discard and give away
+        "/api/games/:game/characters/:id/foundry" | "/api/games/:game/characters/:id/inventory"
+            if read =>
+        {
+            Rule::Character
+        }
+        "/api/games/:game/characters/:id/inventory/:object_id" if *method == Method::DELETE => {
+            Rule::Character
+        }
+        "/api/games/:game/characters/:id/inventory/:object_id/transfer" => Rule::Character,
+        "/api/objects" | "/api/objects/:id" if read => Rule::Member,
+        "/api/objects" | "/api/objects/:id" => Rule::AnyGm,
+        "/api/games/:game/events" if read => Rule::Member,
+        "/api/games/:game/sessions"
+        | "/api/games/:game/sessions/:id"
+        | "/api/games/:game/sessions/:id/recap"
+            if read =>
+        {
+            Rule::Member
+        }
+        // Handlers check which character is acting
+        "/api/roll"
+        | "/api/games/:game/trades"
+        | "/api/games/:game/trades/:id"
+        | "/api/games/:game/trades/:id/accept"
+        | "/api/games/:game/trades/:id/reject" => Rule::Member,
+        _ => Rule::Gm,
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TokenQuery {
+    access_token: Option<String>,
+}
+
+/// Authenticates the request and enforces the matched route's rule.
+///
+/// Added to every route by `server::api_router`. On success the caller's
+/// `Access` is stored in the request for handlers to extract.
+pub async fn authorize(
+    State(state): State<AppState>,
+    route: Option<MatchedPath>,
+    params: Option<Path<HashMap<String, String>>>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let route = route.as_ref().map_or("", MatchedPath::as_str);
+    let rule = rule(request.method(), route);
+
+    let access = if !state.auth_required {
+        Access::Unrestricted
+    } else if rule == Rule::Public {
+        return Ok(next.run(request).await);
+    } else {
+        let secret = bearer_token(&request)
+            .ok_or_else(|| ApiError::Unauthorized("Missing API token".to_string()))?;
+        let token = state
+            .db
+            .run(move |db| db.authenticate_token(&secret))
+            .await?
+            .ok_or_else(|| ApiError::Unauthorized("Invalid or revoked API token".to_string()))?;
+        Access::Token(token)
+    };
+
+    let params = params.map(|Path(params)| params).unwrap_or_default();
+    if let Some(game) = params.get("game") {
+        access.require_game(game)?;
+    }
+    let forbidden = |message: &str| Err(ApiError::Forbidden(message.to_string()));
+    match rule {
+        Rule::Public => {}
+        Rule::Member => {
+            if access.is_observer() && request.method() != Method::GET {
+                return forbidden("Observers have read-only access");
+            }
+        }
+        Rule::Gm => match params.get("game") {
+            Some(game) => access.require_gm(game)?,
+            None if matches!(access, Access::Unrestricted) => {}
+            None => return forbidden("Only the GM can do this"),
+        },
+        Rule::AnyGm => {
+            if !access.is_gm_anywhere() {
+                return forbidden("Only a GM can do this");
+            }
+        }
+        Rule::Character => match (params.get("game"), params.get("id")) {
+            (Some(game), Some(id)) => access.require_character(game, id)?,
+            _ => return forbidden("Only the GM can do this"),
+        },
+    }
+
+    request.extensions_mut().insert(access);
+    Ok(next.run(request).await)
+}
+
+/// The token from the `Authorization: Bearer` header or `access_token` query parameter.
+fn bearer_token(request: &Request) -> Option<String> {
+    let from_header = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+
+    from_header.or_else(|| {
+        Query::<TokenQuery>::try_from_uri(request.uri())
+            .ok()
+            .and_then(|Query(query)| query.access_token)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(role: Role, character: Option<&str>) -> Access {
+        Access::Token(ApiToken {
+            id: 1,
+            game: "Game".to_string(),
+            role,
+            character_uuid: character.map(str::to_string),
+            label: None,
+            created_at: 0,
+            revoked_at: None,
+        })
+    }
+
+    #[test]
+    fn test_roles_control_the_right_characters() {
+        let gm = token(Role::Gm, None);
+        let player = token(Role::Player, Some("mine"));
+        let observer = token(Role::Observer, None);
+
+        assert!(gm.controls("Game", "mine") && gm.controls("Game", "theirs"));
+        assert!(!gm.controls("Other", "mine"));
+        assert!(player.controls("Game", "mine"));
+        assert!(!player.controls("Game", "theirs"));
+        assert!(!observer.controls("Game", "mine"));
+        assert!(Access::Unrestricted.controls("Other", "anyone"));
+
+        assert!(player.require_gm("Game").is_err());
+        assert!(matches!(
+            gm.require_game("Other"),
+            Err(ApiError::Forbidden(_))
+        ));
+
+        let games = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        assert!(gm.require_gm_of_all(&games(&["Game"])).is_ok());
+        assert!(gm.require_gm_of_all(&games(&[])).is_ok());
+        assert!(gm.require_gm_of_all(&games(&["Game", "Other"])).is_err());
+        assert!(Access::Unrestricted.require_gm_of_all(&games(&["Other"])).is_ok());
+    }
+
+    #[test]
+    fn test_unlisted_routes_are_gm_only() {
+        assert_eq!(rule(&Method::GET, "/api/health"), Rule::Public);
+        assert_eq!(rule(&Method::POST, "/api/health"), Rule::Gm);
+        assert_eq!(
+            rule(&Method::GET, "/api/games/:game/characters/:id/inventory"),
+            Rule::Character
+        );
+        assert_eq!(
+            rule(&Method::POST, "/api/games/:game/characters/:id/inventory"),
+            Rule::Gm
+        );
+        assert_eq!(
+            rule(&Method::PUT, "/api/games/:game/characters/:id/foundry"),
+            Rule::Gm
+        );
+        assert_eq!(rule(&Method::POST, "/api/games/:game/characters"), Rule::Gm);
+        assert_eq!(rule(&Method::DELETE, "/api/objects/:id"), Rule::AnyGm);
+        assert_eq!(rule(&Method::GET, "/api/games/:game/sessions/:id/recap"), Rule::Member);
+        assert_eq!(rule(&Method::POST, "/api/games/:game/sessions"), Rule::Gm);
+        assert_eq!(
+            rule(&Method::GET, "/api/games/:game/something/new"),
+            Rule::Gm
+        );
+    }
+}
@@ -0,0 +1,441 @@
+//! API server configuration.
+//!
+//! Settings are layered: built-in defaults, then a TOML file, then
+//! environment variables, then command-line flags, each overriding the last.
+//!
+//! | Setting         | File key          | Environment variable     | Flag               |
+//! |-----------------|-------------------|--------------------------|--------------------|
+//! | Config file     | -                 | `TTDIGIRPG_CONFIG`       | `--config <path>`  |
+//! | Bind address    | `bind_address`    | `TTDIGIRPG_BIND`         | `--bind <ip>`      |
+//! | Port            | `port`            | `TTDIGIRPG_PORT`         | `--port <n>`       |
+//! | Allowed origins | `allowed_origins` | `TTDIGIRPG_ORIGINS`      | `--origin <url>`   |
+//! | Allowed methods | `allowed_methods` | `TTDIGIRPG_METHODS`      | `--methods <list>` |
+//! | Database path   | `db_path`         | `TTDIGIRPG_DB_PATH`      | `--db <path>`      |
+//! | Require tokens  | `require_auth`    | `TTDIGIRPG_REQUIRE_AUTH` | `--no-auth`        |
+//!
+//! Environment variables take comma-separated lists. `--origin` may be given
+//! several times. An origin of `*` allows any origin. Without `--config` or
+//! `TTDIGIRPG_CONFIG`, `ttdigirpg.toml` in the working directory is read if
+//! it exists.
+//!
+//! Turning off `require_auth` lets anyone who can reach the server do
+//! anything, so only do it on a trusted machine.
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::Path;
+
+use axum::http::{header, HeaderValue, Method};
+use axum::Router;
+use serde::Deserialize;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use super::server::{api_router, ALLOWED_METHODS};
+use super::state::AppState;
+use crate::entities::database::DEFAULT_DB_PATH;
+
+/// Config file read when none is named explicitly, if it exists.
+pub const DEFAULT_CONFIG_FILE: &str = "ttdigirpg.toml";
+
+/// Port the server listens on by default.
+pub const DEFAULT_PORT: u16 = 8080;
+
+/// Origins allowed by default: Foundry's default port on this machine.
+pub const DEFAULT_ORIGINS: [&str; 2] = ["http://localhost:30000", "http://127.0.0.1:30000"];
+
+/// Reasons a configuration could not be loaded.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The config file couldn't be read
+    Io(String, std::io::Error),
+    /// A setting has an invalid value (the message names the setting)
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "Cannot read config {}: {}", path, err),
+            ConfigError::Invalid(msg) => write!(f, "Invalid configuration: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io(_, err) => Some(err),
+            ConfigError::Invalid(_) => None,
+        }
+    }
+}
+
+/// Everything needed to start the API server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerConfig {
+    /// Address to listen on (`0.0.0.0` to accept LAN connections)
+    pub bind_address: IpAddr,
+    /// Port to listen on
+    pub port: u16,
+    /// Browser origins allowed to call the API
+    pub allowed_origins: Vec<HeaderValue>,
+    /// HTTP methods allowed from browser origins
+    pub allowed_methods: Vec<Method>,
+    /// Path of the SQLite database
+    pub db_path: String,
+    /// Whether requests need an API token (see `api::auth`)
+    pub require_auth: bool,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: DEFAULT_PORT,
+            allowed_origins: DEFAULT_ORIGINS
+                .iter()
+                .map(|origin| HeaderValue::from_static(origin))
+                .collect(),
+            allowed_methods: ALLOWED_METHODS.to_vec(),
+            db_path: DEFAULT_DB_PATH.to_string(),
+            require_auth: true,
+        }
+    }
+}
+
+/// The settings a config file may contain; all are optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    bind_address: Option<String>,
+    port: Option<u16>,
+    allowed_origins: Option<Vec<String>>,
+    allowed_methods: Option<Vec<String>>,
+    db_path: Option<String>,
+    require_auth: Option<bool>,
+}
+
+impl ServerConfig {
+    /// Loads the configuration from the default file, the process
+    /// environment and the given command-line flags.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - Flags only, without the program name or mode
+    pub fn load(args: &[String]) -> Result<Self, ConfigError> {
+        ServerConfig::load_from(args, |key| std::env::var(key).ok())
+    }
+
+    /// Like `load`, but reads environment variables through `env`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ttdigirpg::api::config::ServerConfig;
+    ///
+    /// let env = |key: &str| (key == "TTDIGIRPG_PORT").then(|| "9000".to_string());
+    /// let args = vec!["--bind".to_string(), "0.0.0.0".to_string()];
+    ///
+    /// let config = ServerConfig::load_from(&args, env).unwrap();
+    /// assert_eq!(config.socket_addr().to_string(), "0.0.0.0:9000");
+    /// ```
+    pub fn load_from(
+        args: &[String],
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let mut config = ServerConfig::default();
+
+        let explicit_file = flag_value(args, "--config")?.or_else(|| env("TTDIGIRPG_CONFIG"));
+        match explicit_file {
+            Some(path) => config.apply_file(&path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).is_file() => {
+                config.apply_file(DEFAULT_CONFIG_FILE)?
+            }
+            None => {}
+        }
+
+        config.apply_env(env)?;
+        config.apply_args(args)?;
+        Ok(config)
+    }
+
+    /// Overrides settings with those present in a TOML file.
+    pub fn apply_file(&mut self, path: &str) -> Result<(), ConfigError> {
+        let text =
+            std::fs::read_to_string(path).map_err(|err| ConfigError::Io(path.to_string(), err))?;
+        self.apply_toml(&text).map_err(|err| match err {
+            ConfigError::Invalid(msg) => ConfigError::Invalid(format!("{}: {}", path, msg)),
+            other => other,
+        })
+    }
+
+    /// Overrides settings with those present in a TOML document.
+    pub fn apply_toml(&mut self, text: &str) -> Result<(), ConfigError> {
+        let file: ConfigFile =
+            toml::from_str(text).map_err(|err| ConfigError::Invalid(err.to_string()))?;
+
+        if let Some(address) = file.bind_address {
+            self.bind_address = parse_address(&address)?;
+        }
+        if let Some(port) = file.port {
+            self.port = port;
+        }
+        if let Some(origins) = file.allowed_origins {
+            self.allowed_origins = parse_origins(origins.iter().map(String::as_str))?;
+        }
+        if let Some(methods) = file.allowed_methods {
+            self.allowed_methods = parse_methods(methods.iter().map(String::as_str))?;
+        }
+        if let Some(db_path) = file.db_path {
+            self.db_path = db_path;
+        }
+        if let Some(require_auth) = file.require_auth {
+            self.require_auth = require_auth;
+        }
+        Ok(())
+    }
+
+    /// Overrides settings with the `TTDIGIRPG_*` environment variables that are set.
+    pub fn apply_env(&mut self, env: impl Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        if let Some(address) = env("TTDIGIRPG_BIND") {
+            self.bind_address = parse_address(&address)?;
+        }
+        if let Some(port) = env("TTDIGIRPG_PORT") {
+            self.port = parse_port(&port)?;
+        }
+        if let Some(origins) = env("TTDIGIRPG_ORIGINS") {
+            self.allowed_origins = parse_origins(split_list(&origins))?;
+        }
+        if let Some(methods) = env("TTDIGIRPG_METHODS") {
+            self.allowed_methods = parse_methods(split_list(&methods))?;
+        }
+        if let Some(db_path) = env("TTDIGIRPG_DB_PATH") {
+            self.db_path = db_path;
+        }
+        if let Some(require_auth) = env("TTDIGIRPG_REQUIRE_AUTH") {
+            self.require_auth = parse_bool("TTDIGIRPG_REQUIRE_AUTH", &require_auth)?;
+        }
+        Ok(())
+    }
+
+    /// Overrides settings with command-line flags.
+    ///
+    /// Returns `ConfigError::Invalid` for unknown flags or missing values.
+    pub fn apply_args(&mut self, args: &[String]) -> Result<(), ConfigError> {
+        let mut origins = Vec::new();
+        let mut args = args.iter();
+
+        while let Some(flag) = args.next() {
+            let mut value = || {
+                args.next()
+                    .map(String::as_str)
+                    .ok_or_else(|| ConfigError::Invalid(format!("{} needs a value", flag)))
+            };
+            match flag.as_str() {
+                // Already read by `load_from`
+                "--config" => {
+                    value()?;
+                }
+                "--bind" => self.bind_address = parse_address(value()?)?,
+                "--port" => self.port = parse_port(value()?)?,
+                "--origin" => origins.push(value()?.to_string()),
+                "--methods" => self.allowed_methods = parse_methods(split_list(value()?))?,
+                "--db" => self.db_path = value()?.to_string(),
+                "--no-auth" => self.require_auth = false,
+                other => return Err(ConfigError::Invalid(format!("unknown flag {}", other))),
+            }
+        }
+
+        if !origins.is_empty() {
+            self.allowed_origins = parse_origins(origins.iter().map(String::as_str))?;
+        }
+        Ok(())
+    }
+
+    /// The address and port to listen on.
+    pub fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.bind_address, self.port)
+    }
+
+    /// CORS rules for the configured origins and methods.
+    pub fn cors_layer(&self) -> CorsLayer {
+        let origins = if self.allowed_origins.iter().any(|origin| origin == "*") {
+            AllowOrigin::any()
+        } else {
+            AllowOrigin::list(self.allowed_origins.clone())
+        };
+
+        CorsLayer::new()
+            .allow_origin(origins)
+            .allow_methods(self.allowed_methods.clone())
+            .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION])
+    }
+}
+
+/// Builds the API routes with the CORS and authentication rules from `config`.
+///
+/// Both the server and the tests use this, so they can't drift apart.
+pub fn build_router(config: &ServerConfig, state: AppState) -> Router {
+    api_router(state.with_auth_required(config.require_auth)).layer(config.cors_layer())
+}
+
+/// Reads the value following `flag`, if the flag is present.
+fn flag_value(args: &[String], flag: &str) -> Result<Option<String>, ConfigError> {
+    match args.iter().position(|arg| arg == flag) {
+        None => Ok(None),
+        Some(index) => args
+            .get(index + 1)
+            .cloned()
+            .map(Some)
+            .ok_or_else(|| ConfigError::Invalid(format!("{} needs a value", flag))),
+    }
+}
+
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+fn parse_address(address: &str) -> Result<IpAddr, ConfigError> {
+    address.trim().parse().map_err(|_| {
+        ConfigError::Invalid(format!("bind address {:?} is not an IP address", address))
+    })
+}
+
+fn parse_port(port: &str) -> Result<u16, ConfigError> {
+    port.trim().parse().map_err(|_| {
+        ConfigError::Invalid(format!("port {:?} is not a number from 0 to 65535", port))
+    })
+}
+
+fn parse_bool(setting: &str, value: &str) -> Result<bool, ConfigError> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(ConfigError::Invalid(format!(
+            "{} {:?} is not true or false",
+            setting, value
+        ))),
+    }
+}
+
+fn parse_origins<'a>(
+    origins: impl Iterator<Item = &'a str>,
+) -> Result<Vec<HeaderValue>, ConfigError> {
+    origins
+        .map(|origin| {
+            let origin = origin.trim().trim_end_matches('/');
+            if origin != "*" && !origin.starts_with("http://") && !origin.starts_with("https://") {
+                return Err(ConfigError::Invalid(format!(
+                    "origin {:?} must start with http:// or https://",
+                    origin
+                )));
+            }
+            HeaderValue::from_str(origin)
+                .map_err(|_| ConfigError::Invalid(format!("origin {:?} is not valid", origin)))
+        })
+        .collect()
+}
+
+fn parse_methods<'a>(methods: impl Iterator<Item = &'a str>) -> Result<Vec<Method>, ConfigError> {
+    methods
+        .map(|method| {
+            Method::from_bytes(method.trim().to_ascii_uppercase().as_bytes())
+                .map_err(|_| ConfigError::Invalid(format!("method {:?} is not valid", method)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(flags: &[&str]) -> Vec<String> {
+        flags.iter().map(|flag| flag.to_string()).collect()
+    }
+
+    #[test]
+    fn test_layers_override_in_order() {
+        let mut config = ServerConfig::default();
+        config
+            .apply_toml(
+                r#"
+                bind_address = "0.0.0.0"
+                port = 9000
+                allowed_origins = ["http://192.168.1.20:30000/"]
+                db_path = "games/file.db"
+                "#,
+            )
+            .unwrap();
+        config
+            .apply_env(|key| match key {
+                "TTDIGIRPG_PORT" => Some("9100".to_string()),
+                "TTDIGIRPG_METHODS" => Some("get, post".to_string()),
+                "TTDIGIRPG_REQUIRE_AUTH" => Some("yes".to_string()),
+                _ => None,
+            })
+            .unwrap();
+        assert!(config.require_auth);
+        config
+            .apply_args(&args(&["--db", "games/flag.db", "--origin", "*", "--no-auth"]))
+            .unwrap();
+
+        assert_eq!(config.socket_addr().to_string(), "0.0.0.0:9100");
+        assert_eq!(config.allowed_methods, vec![Method::GET, Method::POST]);
+        assert_eq!(config.allowed_origins, vec![HeaderValue::from_static("*")]);
+        assert_eq!(config.db_path, "games/flag.db");
+        assert!(!config.require_auth);
+    }
+
+    #[test]
+    fn test_file_origins_are_normalised() {
+        let mut config = ServerConfig::default();
+        config
+            .apply_toml(
+                r#"allowed_origins = ["http://10.0.0.5:31000/", "https://vtt.example.com"]"#,
+            )
+            .unwrap();
+        assert_eq!(
+            config.allowed_origins,
+            vec![
+                HeaderValue::from_static("http://10.0.0.5:31000"),
+                HeaderValue::from_static("https://vtt.example.com"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bad_settings_are_rejected() {
+        let mut config = ServerConfig::default();
+        for bad in [
+            vec!["--port", "99999"],
+            vec!["--bind", "localhost"],
+            vec!["--origin", "foundry.local:30000"],
+            vec!["--methods", "GET,NOT A METHOD"],
+            vec!["--port"],
+            vec!["--verbose"],
+        ] {
+            let err = config.apply_args(&args(&bad)).unwrap_err();
+            assert!(matches!(err, ConfigError::Invalid(_)), "{:?}", bad);
+        }
+
+        let err = config.apply_toml("prot = 8080").unwrap_err();
+        assert!(err.to_string().contains("prot"));
+        assert_eq!(config, ServerConfig::default());
+    }
+
+    #[test]
+    fn test_explicit_config_file_is_read() {
+        let path = std::env::temp_dir().join(format!("ttdigirpg-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "port = 7070\n").unwrap();
+        let path = path.to_str().unwrap().to_string();
+
+        let config = ServerConfig::load_from(&args(&["--config", &path]), |_| None).unwrap();
+        assert_eq!(config.port, 7070);
+        std::fs::remove_file(&path).unwrap();
+
+        let err = ServerConfig::load_from(&args(&["--config", &path]), |_| None).unwrap_err();
+        assert!(matches!(err, ConfigError::Io(..)));
+    }
+}
@@ -0,0 +1,188 @@
+use axum::{
+    extract::rejection::{BytesRejection, PathRejection, QueryRejection},
+    extract::ws::rejection::WebSocketUpgradeRejection,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::entities::character::FieldError;
+use crate::entities::error::DbError;
+use crate::systems::foundry::FoundryError;
+
+/// The JSON body of every error response.
+///
+/// Clients should branch on `code`; `message` is for people and may change.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorBody {
+    /// Stable machine-readable error code, e.g. `not_found` (see `ApiError::code`)
+    pub code: String,
+    /// What went wrong
+    pub message: String,
+    /// One entry per invalid field; empty unless `code` is `validation_failed`
+    pub details: Vec<FieldError>,
+}
+
+/// Errors returned by API handlers, each mapped to a precise HTTP status.
+#[derive(Debug)]
+pub enum ApiError {
+    /// 400 - the request couldn't be read: malformed JSON, a bad path segment
+    /// or query string, or a missing WebSocket upgrade
+    BadRequest(String),
+    /// 401 - the request has no valid API token
+    Unauthorized(String),
+    /// 403 - the token's role doesn't allow this request
+    Forbidden(String),
+    /// 404 - the requested resource doesn't exist
+    NotFound(String),
+    /// 405 - the route exists but not with this method
+    MethodNotAllowed(String),
+    /// 409 - the request conflicts with existing data (e.g. a duplicate name)
+    Conflict(String),
+    /// 413 - the request body is over the size limit
+    PayloadTooLarge(String),
+    /// 415 - the request body isn't declared as `application/json`
+    UnsupportedMediaType(String),
+    /// 422 - the request is well-formed but refers to data that doesn't exist
+    Unprocessable(String),
+    /// 422 - the request body failed validation; one entry per bad field
+    Validation(Vec<FieldError>),
+    /// 500 - anything else; details are logged, not returned
+    Internal(String),
+}
+
+impl ApiError {
+    /// The HTTP status code this error is reported with.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::MethodNotAllowed(_) => StatusCode::METHOD_NOT_ALLOWED,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::Unprocessable(_) | ApiError::Validation(_) => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// The `code` reported in the error body.
+    ///
+    /// These are part of the API contract: add new ones freely, but never
+    /// rename one.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::MethodNotAllowed(_) => "method_not_allowed",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::PayloadTooLarge(_) => "payload_too_large",
+            ApiError::UnsupportedMediaType(_) => "unsupported_media_type",
+            ApiError::Unprocessable(_) => "unprocessable",
+            ApiError::Validation(_) => "validation_failed",
+            ApiError::Internal(_) => "internal",
+        }
+    }
+}
+
+impl From<Vec<FieldError>> for ApiError {
+    fn from(fields: Vec<FieldError>) -> Self {
+        ApiError::Validation(fields)
+    }
+}
+
+impl From<DbError> for ApiError {
+    fn from(err: DbError) -> Self {
+        match err {
+            DbError::NotFound(_) => ApiError::NotFound(err.to_string()),
+            DbError::Duplicate(_) => ApiError::Conflict(err.to_string()),
+            DbError::ForeignKeyViolation(_) | DbError::InvalidOperation(_) => {
+                ApiError::Unprocessable(err.to_string())
+            }
+            DbError::Unavailable(_) | DbError::Sqlite(_) => ApiError::Internal(err.to_string()),
+        }
+    }
+}
+
+impl From<FoundryError> for ApiError {
+    fn from(err: FoundryError) -> Self {
+        match err {
+            FoundryError::Invalid(fields) => ApiError::Validation(fields),
+            FoundryError::UnknownTemplate(_) => {
+                ApiError::Validation(vec![FieldError::new("template", err.to_string())])
+            }
+            FoundryError::Db(err) => err.into(),
+            FoundryError::Data(_) => ApiError::Internal(err.to_string()),
+        }
+    }
+}
+
+impl From<BytesRejection> for ApiError {
+    fn from(rejection: BytesRejection) -> Self {
+        if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            ApiError::PayloadTooLarge(rejection.body_text())
+        } else {
+            ApiError::BadRequest(rejection.body_text())
+        }
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+impl From<WebSocketUpgradeRejection> for ApiError {
+    fn from(rejection: WebSocketUpgradeRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let code = self.code().to_string();
+        let (message, details) = match self {
+            ApiError::Validation(fields) => ("Validation failed".to_string(), fields),
+            ApiError::Internal(details) => {
+                eprintln!("Internal API error: {}", details);
+                ("Internal server error".to_string(), Vec::new())
+            }
+            ApiError::BadRequest(message)
+            | ApiError::Unauthorized(message)
+            | ApiError::Forbidden(message)
+            | ApiError::NotFound(message)
+            | ApiError::MethodNotAllowed(message)
+            | ApiError::Conflict(message)
+            | ApiError::PayloadTooLarge(message)
+            | ApiError::UnsupportedMediaType(message)
+            | ApiError::Unprocessable(message) => (message, Vec::new()),
+        };
+        let body = Json(ErrorBody {
+            code,
+            message,
+            details,
+        });
+
+        if status == StatusCode::UNAUTHORIZED {
+            return (status, [(header::WWW_AUTHENTICATE, "Bearer")], body).into_response();
+        }
+        (status, body).into_response()
+    }
+}
+
@@ -0,0 +1,478 @@
+//! Live game events pushed to WebSocket clients.
+//!
+//! Every game has its own channel. Each published event gets the next
+//! sequence number of that game's channel, and the most recent events are
+//! kept so a client that reconnects with `?since=<seq>` receives what it
+//! missed before any new events. If the events it missed are no longer kept
+//! (or the server restarted), the client is sent a `resync` message and
+//! should reload state over the REST API.
+//!
+//! Clients also receive a `heartbeat` message (and a WebSocket ping) at a
+//! fixed interval, and are disconnected if nothing at all comes back from
+//! them for several intervals or if their token has been revoked since.
+
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::extract::ws::{Message, WebSocket};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use tokio::sync::broadcast;
+use tokio::time::{interval, Instant, MissedTickBehavior};
+
+use super::models::{CharacterResource, RollResponse};
+use crate::entities::database::unix_now;
+use crate::entities::sessions::{Scene, Session};
+
+/// Events kept per game for clients resuming after a reconnect.
+pub const HISTORY_LIMIT: usize = 256;
+
+/// Time between heartbeats sent to each client.
+pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Heartbeat intervals a client may stay silent before it is disconnected.
+const MISSED_HEARTBEATS_ALLOWED: u32 = 3;
+
+/// Something that happened in a game.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GameEvent {
+    /// A character was created or its sheet changed
+    CharacterUpdated { character: CharacterResource },
+    /// A character was deleted
+    CharacterDeleted { character: String },
+    /// A character rolled dice through `POST /api/roll`
+    RollMade { roll: RollResponse },
+    /// A character took damage (negative amounts heal)
+    DamageApplied {
+        character: String,
+        amount: i32,
+        #[serde(default)]
+        source: Option<String>,
+    },
+    /// Combat moved on to the next turn
+    TurnAdvanced {
+        round: u32,
+        turn: u32,
+        #[serde(default)]
+        character: Option<String>,
+    },
+    /// The GM awarded a character experience points
+    XpAwarded {
+        character: String,
+        amount: i32,
+        #[serde(default)]
+        reason: Option<String>,
+    },
+    /// A session of the game started
+    SessionStarted { session: Session },
+    /// The game's session ended (its open scene ends with it)
+    SessionEnded { session: Session },
+    /// A scene started; scene-scoped effects such as willpower refresh start here
+    SceneStarted { scene: Scene },
+    /// A scene ended
+    SceneEnded { scene: Scene },
+}
+
+impl GameEvent {
+    /// True for events only the server may publish, because it produced the
+    /// data itself (clients must not be able to forge a roll).
+    pub fn is_server_only(&self) -> bool {
+        matches!(
+            self,
+            GameEvent::CharacterUpdated { .. }
+                | GameEvent::CharacterDeleted { .. }
+                | GameEvent::RollMade { .. }
+                | GameEvent::XpAwarded { .. }
+                | GameEvent::SessionStarted { .. }
+                | GameEvent::SessionEnded { .. }
+                | GameEvent::SceneStarted { .. }
+                | GameEvent::SceneEnded { .. }
+        )
+    }
+}
+
+/// A published event with its place in the game's sequence.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EventEnvelope {
+    pub seq: u64,
+    pub game: String,
+    pub at: i64, // Unix seconds
+    #[serde(flatten)]
+    pub event: GameEvent,
+}
+
+/// Control messages sent alongside events.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ControlMessage {
+    /// Sent periodically; `seq` is the latest sequence number in the game
+    Heartbeat { seq: u64 },
+    /// Events after `since` are lost; reload state and resume from `seq`
+    Resync { since: u64, seq: u64 },
+}
+
+/// One game's channel.
+struct GameChannel {
+    last_seq: u64,
+    history: VecDeque<EventEnvelope>,
+    sender: broadcast::Sender<EventEnvelope>,
+}
+
+impl GameChannel {
+    fn new() -> Self {
+        GameChannel {
+            last_seq: 0,
+            history: VecDeque::with_capacity(HISTORY_LIMIT),
+            sender: broadcast::channel(HISTORY_LIMIT).0,
+        }
+    }
+
+    /// Events after `since`, or `None` if some of them are no longer kept.
+    fn replay(&self, since: u64) -> Option<Vec<EventEnvelope>> {
+        if since > self.last_seq {
+            return None;
+        }
+        let oldest_kept = self.history.front().map_or(self.last_seq + 1, |e| e.seq);
+        if since + 1 < oldest_kept {
+            return None;
+        }
+        Some(
+            self.history
+                .iter()
+                .filter(|e| e.seq > since)
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+/// A client's view of a game channel: what it missed plus a live feed.
+pub struct Subscription {
+    /// Events to send before anything from `receiver`, or `None` if the
+    /// client must resync
+    pub missed: Option<Vec<EventEnvelope>>,
+    /// Latest sequence number at the time of subscribing
+    pub seq: u64,
+    /// Events published from now on
+    pub receiver: broadcast::Receiver<EventEnvelope>,
+}
+
+/// Routes events to the clients subscribed to each game.
+///
+/// Cloning is cheap; clones share the same channels.
+#[derive(Clone)]
+pub struct EventHub {
+    channels: Arc<Mutex<HashMap<String, GameChannel>>>,
+    heartbeat_interval: Duration,
+}
+
+impl Default for EventHub {
+    fn default() -> Self {
+        EventHub::new()
+    }
+}
+
+impl EventHub {
+    /// Creates a hub with no channels and the default heartbeat interval.
+    pub fn new() -> Self {
+        EventHub {
+            channels: Arc::new(Mutex::new(HashMap::new())),
+            heartbeat_interval: DEFAULT_HEARTBEAT_INTERVAL,
+        }
+    }
+
+    /// Sets how often connected clients get a heartbeat.
+    pub fn with_heartbeat_interval(mut self, heartbeat_interval: Duration) -> Self {
+        self.heartbeat_interval = heartbeat_interval;
+        self
+    }
+
+    /// Publishes an event to a game and returns it with its sequence number.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ttdigirpg::api::events::{EventHub, GameEvent};
+    ///
+    /// let hub = EventHub::new();
+    /// let turn = GameEvent::TurnAdvanced { round: 1, turn: 2, character: None };
+    /// assert_eq!(hub.publish("Game", turn.clone()).seq, 1);
+    /// assert_eq!(hub.publish("Game", turn.clone()).seq, 2);
+    /// assert_eq!(hub.publish("Other", turn).seq, 1);
+    /// ```
+    pub fn publish(&self, game: &str, event: GameEvent) -> EventEnvelope {
+        let mut channels = self.lock();
+        let channel = channels
+            .entry(game.to_string())
+            .or_insert_with(GameChannel::new);
+
+        channel.last_seq += 1;
+        let envelope = EventEnvelope {
+            seq: channel.last_seq,
+            game: game.to_string(),
+            at: unix_now(),
+            event,
+        };
+        if channel.history.len() == HISTORY_LIMIT {
+            channel.history.pop_front();
+        }
+        channel.history.push_back(envelope.clone());
+        // No receivers just means nobody is listening right now
+        let _ = channel.sender.send(envelope.clone());
+        envelope
+    }
+
+    /// Subscribes to a game, optionally replaying events after `since`.
+    ///
+    /// Replay and subscription happen under one lock, so no event is missed
+    /// or delivered twice in between. Call `release` once the receiver is
+    /// dropped so a game nobody published to doesn't keep its channel.
+    pub fn subscribe(&self, game: &str, since: Option<u64>) -> Subscription {
+        let mut channels = self.lock();
+        let channel = channels
+            .entry(game.to_string())
+            .or_insert_with(GameChannel::new);
+
+        Subscription {
+            missed: match since {
+                Some(since) => channel.replay(since),
+                None => Some(Vec::new()),
+            },
+            seq: channel.last_seq,
+            receiver: channel.sender.subscribe(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, GameChannel>> {
+        // A panic while publishing can't leave a channel half-updated in a
+        // way that matters, so keep serving after poisoning
+        self.channels.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Drops a game's channel if nobody is subscribed and nothing was ever
+    /// published to it, so connecting to made-up games can't grow the hub.
+    pub fn release(&self, game: &str) {
+        let mut channels = self.lock();
+        if channels.get(game).is_some_and(|channel| {
+            channel.sender.receiver_count() == 0 && channel.history.is_empty()
+        }) {
+            channels.remove(game);
+        }
+    }
+
+    /// Streams a game's events to a connected client until it disconnects.
+    ///
+    /// `still_allowed` is asked on every heartbeat; once it returns false,
+    /// e.g. because the client's token was revoked, the socket is closed.
+    pub async fn serve<F, Fut>(
+        self,
+        mut socket: WebSocket,
+        game: String,
+        since: Option<u64>,
+        still_allowed: F,
+    ) where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = bool>,
+    {
+        self.stream(&mut socket, &game, since, still_allowed).await;
+        self.release(&game);
+    }
+
+    async fn stream<F, Fut>(
+        &self,
+        socket: &mut WebSocket,
+        game: &str,
+        since: Option<u64>,
+        mut still_allowed: F,
+    ) where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = bool>,
+    {
+        let Ok((mut receiver, mut last_sent)) = self.catch_up(socket, game, since).await else {
+            return;
+        };
+
+        let mut heartbeat = interval(self.heartbeat_interval);
+        heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        heartbeat.tick().await;
+        let timeout = self.heartbeat_interval * MISSED_HEARTBEATS_ALLOWED;
+        let mut last_heard = Instant::now();
+
+        loop {
+            tokio::select! {
+                received = receiver.recv() => match received {
+                    Ok(envelope) => {
+                        last_sent = envelope.seq;
+                        if send_json(socket, &envelope).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => {
+                        // The client fell behind the live feed; catch it up
+                        // from history if possible
+                        match self.catch_up(socket, game, Some(last_sent)).await {
+                            Ok((fresh, seq)) => (receiver, last_sent) = (fresh, seq),
+                            Err(_) => return,
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                },
+                incoming = socket.recv() => match incoming {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                    // Pongs, pings and any client chatter all prove it's alive
+                    Some(Ok(_)) => last_heard = Instant::now(),
+                },
+                _ = heartbeat.tick() => {
+                    if last_heard.elapsed() > timeout || !still_allowed().await {
+                        let _ = socket.send(Message::Close(None)).await;
+                        return;
+                    }
+                    let beat = ControlMessage::Heartbeat { seq: last_sent };
+                    if socket.send(Message::Ping(Vec::new())).await.is_err()
+                        || send_json(socket, &beat).await.is_err()
+                    {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Subscribes and sends the client whatever it missed after `since`
+    /// (or a resync notice), returning the live feed and the last sequence
+    /// number the client now has.
+    async fn catch_up(
+        &self,
+        socket: &mut WebSocket,
+        game: &str,
+        since: Option<u64>,
+    ) -> Result<(broadcast::Receiver<EventEnvelope>, u64), axum::Error> {
+        let Subscription {
+            missed,
+            seq,
+            receiver,
+        } = self.subscribe(game, since);
+
+        match missed {
+            Some(missed) => {
+                for envelope in missed {
+                    send_json(socket, &envelope).await?;
+                }
+            }
+            None => {
+                let resync = ControlMessage::Resync {
+                    since: since.unwrap_or(0),
+                    seq,
+                };
+                send_json(socket, &resync).await?;
+            }
+        }
+        Ok((receiver, seq))
+    }
+}
+
+async fn send_json<T: Serialize>(socket: &mut WebSocket, message: &T) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(message).map_err(axum::Error::new)?;
+    socket.send(Message::Text(text)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turn(turn: u32) -> GameEvent {
+        GameEvent::TurnAdvanced {
+            round: 1,
+            turn,
+            character: None,
+        }
+    }
+
+    #[test]
+    fn test_resume_replays_only_missed_events() {
+        let hub = EventHub::new();
+        for n in 1..=3 {
+            hub.publish("Game", turn(n));
+        }
+
+        let sub = hub.subscribe("Game", Some(1));
+        let missed: Vec<u64> = sub.missed.unwrap().iter().map(|e| e.seq).collect();
+        assert_eq!(missed, vec![2, 3]);
+        assert_eq!(sub.seq, 3);
+
+        assert!(hub.subscribe("Game", Some(3)).missed.unwrap().is_empty());
+        // A sequence number from the future means the server restarted
+        assert!(hub.subscribe("Game", Some(9)).missed.is_none());
+    }
+
+    #[test]
+    fn test_resume_past_history_requires_resync() {
+        let hub = EventHub::new();
+        for n in 0..(HISTORY_LIMIT as u32 + 5) {
+            hub.publish("Game", turn(n));
+        }
+
+        assert!(hub.subscribe("Game", Some(2)).missed.is_none());
+        let kept = hub.subscribe("Game", Some(5)).missed.unwrap();
+        assert_eq!(kept.len(), HISTORY_LIMIT);
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_only_get_their_game() {
+        let hub = EventHub::new();
+        let mut sub = hub.subscribe("Game", None);
+
+        hub.publish("Other", turn(1));
+        hub.publish("Game", turn(2));
+
+        let envelope = sub.receiver.recv().await.unwrap();
+        assert_eq!(envelope.game, "Game");
+        assert_eq!(envelope.seq, 1);
+        assert!(sub.receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_released_channels_only_go_once_unused_and_empty() {
+        let hub = EventHub::new();
+        let sub = hub.subscribe("Nowhere", None);
+        hub.release("Nowhere");
+        assert_eq!(hub.lock().len(), 1, "still has a subscriber");
+
+        drop(sub);
+        hub.release("Nowhere");
+        assert!(hub.lock().is_empty());
+
+        // A game with history keeps its channel so clients can resume
+        drop(hub.subscribe("Game", None));
+        hub.publish("Game", turn(1));
+        hub.release("Game");
+        assert_eq!(hub.subscribe("Game", Some(0)).missed.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_event_json_shape() {
+        let envelope = EventEnvelope {
+            seq: 4,
+            game: "Game".to_string(),
+            at: 0,
+            event: GameEvent::DamageApplied {
+                character: "abc".to_string(),
+                amount: 3,
+                source: None,
+            },
+        };
+        let json = serde_json::to_value(&envelope).unwrap();
+        assert_eq!(json["type"], "damage_applied");
+        assert_eq!(json["seq"], 4);
+        assert_eq!(json["amount"], 3);
+
+        let parsed: GameEvent = serde_json::from_value(
+            serde_json::json!({"type": "turn_advanced", "round": 2, "turn": 1}),
+        )
+        .unwrap();
+        assert!(!parsed.is_server_only());
+    }
+}
@@ -0,0 +1,183 @@
+//! Request extractors that reject bad input with an `ApiError`.
+//!
+//! Axum's own `Json`, `Path` and `Query` reply to bad input with plain text.
+//! These wrappers behave the same but report failures in the usual error
+//! body, and `Json` names the offending field of a body that doesn't fit the
+//! expected type. Handlers use them in place of the axum versions.
+
+use axum::{
+    async_trait,
+    body::Bytes,
+    extract::{FromRequest, FromRequestParts, Request},
+    http::{header, request::Parts, HeaderMap},
+    response::{IntoResponse, Response},
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::error::ApiError;
+use crate::entities::character::FieldError;
+
+/// A JSON request or response body.
+///
+/// As a response it is the same as `axum::Json`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for Json<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if !is_json(request.headers()) {
+            return Err(ApiError::UnsupportedMediaType(
+                "Expected a request body with Content-Type: application/json".to_string(),
+            ));
+        }
+        let bytes = Bytes::from_request(request, state).await?;
+        parse_json(&bytes).map(Json)
+    }
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+/// Parses a JSON body, reporting type errors against the field they're in.
+///
+/// Syntax errors are `BadRequest`; well-formed JSON of the wrong shape is a
+/// `Validation` error whose field is the dotted path to the problem (`body`
+/// for the top level).
+fn parse_json<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, ApiError> {
+    let malformed = |err: serde_json::Error| {
+        ApiError::BadRequest(format!(
+            "Malformed JSON body: {}",
+            without_position(&err.to_string())
+        ))
+    };
+    let mut deserializer = serde_json::Deserializer::from_slice(bytes);
+
+    let value = serde_path_to_error::deserialize(&mut deserializer).map_err(|err| {
+        let path = err.path().to_string();
+        let err = err.into_inner();
+        if !err.is_data() {
+            return malformed(err);
+        }
+        let field = if path == "." {
+            "body".to_string()
+        } else {
+            path
+        };
+        ApiError::Validation(vec![FieldError::new(
+            field,
+            without_position(&err.to_string()),
+        )])
+    })?;
+    deserializer.end().map_err(malformed)?;
+    Ok(value)
+}
+
+/// Strips serde_json's " at line 1 column 7" suffix, which means little to API clients.
+fn without_position(message: &str) -> String {
+    match message.rfind(" at line ") {
+        Some(index) => message[..index].to_string(),
+        None => message.to_string(),
+    }
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| {
+            let mime = mime.trim();
+            mime == "application/json"
+                || (mime.starts_with("application/") && mime.ends_with("+json"))
+        })
+}
+
+/// Path parameters, as `axum::extract::Path`.
+#[derive(Debug)]
+pub struct Path<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Path<T>
+where
+    T: DeserializeOwned + Send,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::extract::Path(value) =
+            axum::extract::Path::<T>::from_request_parts(parts, state).await?;
+        Ok(Path(value))
+    }
+}
+
+/// Query string parameters, as `axum::extract::Query`.
+#[derive(Debug)]
+pub struct Query<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Query<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::extract::Query(value) =
+            axum::extract::Query::<T>::from_request_parts(parts, state).await?;
+        Ok(Query(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::systems::dice::DicePool;
+
+    #[test]
+    fn test_type_errors_name_the_field() {
+        let err = parse_json::<DicePool>(br#"{"stats": ["physical"], "difficulty": "hard"}"#)
+            .unwrap_err();
+        let ApiError::Validation(fields) = err else {
+            panic!("expected a validation error, got {:?}", err);
+        };
+        assert_eq!(fields[0].field, "difficulty");
+        assert!(!fields[0].message.contains("line"));
+
+        let err = parse_json::<DicePool>(b"\"physical\"").unwrap_err();
+        assert!(matches!(&err, ApiError::Validation(fields) if fields[0].field == "body"));
+    }
+
+    #[test]
+    fn test_syntax_errors_are_bad_requests() {
+        for body in [&b"{\"stats\": ["[..], b"", b"{\"stats\": []} {}"] {
+            let err = parse_json::<DicePool>(body).unwrap_err();
+            assert!(matches!(err, ApiError::BadRequest(_)), "{:?}", err);
+        }
+    }
+
+    #[test]
+    fn test_json_content_types() {
+        let headers = |value: &'static str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONTENT_TYPE, value.parse().unwrap());
+            headers
+        };
+        assert!(is_json(&headers("application/json")));
+        assert!(is_json(&headers("application/json; charset=utf-8")));
+        assert!(is_json(&headers("application/vnd.foundry+json")));
+        assert!(!is_json(&headers("text/plain")));
+        assert!(!is_json(&HeaderMap::new()));
+    }
+}
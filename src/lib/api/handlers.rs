@@ -1,6 +1,50 @@
-use axum::Json;
-use super::models::{TestRequest, TestResponse};
+use axum::{
+    extract::{
+        ws::{rejection::WebSocketUpgradeRejection, WebSocketUpgrade},
+        State,
+    },
+    http::{Method, StatusCode, Uri},
+    response::Response,
+};
+use super::auth::Access;
+use super::error::{ApiError, ErrorBody};
+use super::extract::{Json, Path, Query};
+use super::events::{EventEnvelope, GameEvent};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde_json::{Map, Value};
+use utoipa::OpenApi;
 
+use super::openapi::ApiDoc;
+use super::models::{
+    AddInventoryRequest, AwardXpRequest, CharacterListQuery, EventStreamQuery, FoundryQuery, CharacterResource, CreateObjectRequest,
+    HealthResponse, InventoryItem, IssueTokenRequest, IssuedToken, ObjectResource, ObjectTypeQuery, ProposeTradeRequest,
+    RollBreakdown, RollModifier, RollRequest, RollResponse, SessionListQuery, SessionRecap,
+    SetQuantityRequest, StartSceneRequest, StartSessionRequest, StatDice, TestRequest, TestResponse, TradeListQuery, TransferRequest, UpdateObjectRequest,
+};
+use super::state::AppState;
+use crate::entities::character::{Character, FieldError};
+use crate::entities::database::{unix_now, Database};
+use crate::entities::error::DbError;
+use crate::entities::records::{
+    merge_sheet_data, CharacterRecord, InventoryEntry, ObjectRecord, Page, PageRequest,
+};
+use crate::entities::rolls::RollRecord;
+use crate::entities::sessions::{LogEntry, LogKind, Scene, Session};
+use crate::entities::tokens::ApiToken;
+use crate::entities::trade::TradeOffer;
+use crate::systems::dice::{DIE_SIDES, MAX_POOL_DICE, MAX_POOL_MODIFIER, MAX_POOL_STATS, MAX_SEED};
+use crate::systems::encumbrance::{Encumbrance, InventoryLoad};
+use crate::systems::foundry::{self, DEFAULT_TEMPLATE};
+
+#[utoipa::path(
+    post,
+    path = "/api/test/echo",
+    tag = "server",
+    request_body = TestRequest,
+    responses(
+        (status = 200, description = "The data that was sent", body = TestResponse),
+    ),
+)]
 pub async fn test_echo(
     Json(payload): Json<TestRequest>,
 ) -> Json<TestResponse> {
@@ -9,3 +53,1704 @@ pub async fn test_echo(
         echo: payload.data,
     })
 }
+
+/// Replies to requests for paths no route matches.
+pub async fn no_such_route(uri: Uri) -> ApiError {
+    ApiError::NotFound(format!("No such endpoint: {}", uri.path()))
+}
+
+/// Replies to requests using a method the matched path doesn't support.
+pub async fn method_not_allowed(method: Method, uri: Uri) -> ApiError {
+    ApiError::MethodNotAllowed(format!("{} is not supported on {}", method, uri.path()))
+}
+
+/// Reports that the server is up and can reach the database.
+#[utoipa::path(
+    get,
+    path = "/api/health",
+    tag = "server",
+    responses(
+        (status = 200, description = "The server is up and the database reachable", body = HealthResponse),
+    ),
+    security(()),
+)]
+pub async fn health(State(state): State<AppState>) -> Result<Json<HealthResponse>, ApiError> {
+    let schema_version = state.db.run(|db| db.schema_version()).await?;
+
+    Ok(Json(HealthResponse {
+        status: "ok".to_string(),
+        schema_version,
+    }))
+}
+
+/// Lists the trade offers a character made or received in a game.
+#[utoipa::path(
+    get,
+    path = "/api/games/{game}/trades",
+    tag = "trades",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        TradeListQuery,
+    ),
+    responses(
+        (status = 200, description = "Offers made by or to the character, newest first", body = Vec<TradeOffer>),
+    ),
+)]
+pub async fn list_trades(
+    State(state): State<AppState>,
+    access: Access,
+    Path(game): Path<String>,
+    Query(query): Query<TradeListQuery>,
+) -> Result<Json<Vec<TradeOffer>>, ApiError> {
+    access.require_character(&game, &query.character)?;
+    let trades = state
+        .db
+        .run(move |db| {
+            db.expire_trades(unix_now())?;
+            db.list_trades(&query.character, query.status)
+        })
+        .await?;
+
+    Ok(Json(trades.into_iter().filter(|t| t.game == game).collect()))
+}
+
+/// Records a new trade offer between two characters of a game.
+#[utoipa::path(
+    post,
+    path = "/api/games/{game}/trades",
+    tag = "trades",
+    params(("game" = String, Path, description = "Name of the game")),
+    request_body = ProposeTradeRequest,
+    responses(
+        (status = 201, description = "The offer was recorded", body = TradeOffer),
+        (status = 404, description = "No such character", body = ErrorBody),
+        (status = 422, description = "The request failed validation or refers to data that doesn't exist", body = ErrorBody),
+    ),
+)]
+pub async fn propose_trade(
+    State(state): State<AppState>,
+    access: Access,
+    Path(game): Path<String>,
+    Json(request): Json<ProposeTradeRequest>,
+) -> Result<(StatusCode, Json<TradeOffer>), ApiError> {
+    access.require_character(&game, &request.from)?;
+    let expires_at = match request.expires_in_seconds {
+        None => None,
+        Some(secs) => match unix_now().checked_add(secs) {
+            Some(expires_at) if secs > 0 => Some(expires_at),
+            _ => {
+                return Err(vec![FieldError::new(
+                    "expires_in_seconds",
+                    "must be a positive number of seconds",
+                )]
+                .into())
+            }
+        },
+    };
+    let trade = state
+        .db
+        .run(move |db| {
+            let from = db
+                .get_character_by_uuid(&request.from)?
+                .filter(|character| character.game == game)
+                .ok_or_else(|| DbError::not_found(format!("character {}", request.from)))?;
+            db.propose_trade(
+                &from.uuid,
+                &request.to,
+                &request.offered,
+                &request.requested,
+                expires_at,
+            )
+        })
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(trade)))
+}
+
+/// Fetches one trade offer. Players only see offers their character is part of.
+#[utoipa::path(
+    get,
+    path = "/api/games/{game}/trades/{id}",
+    tag = "trades",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        ("id" = i64, Path, description = "Trade offer ID"),
+    ),
+    responses(
+        (status = 200, description = "The offer", body = TradeOffer),
+        (status = 404, description = "No such trade offer", body = ErrorBody),
+    ),
+)]
+pub async fn get_trade(
+    State(state): State<AppState>,
+    access: Access,
+    Path((game, trade_id)): Path<(String, i64)>,
+) -> Result<Json<TradeOffer>, ApiError> {
+    let trade = state
+        .db
+        .run(move |db| {
+            db.expire_trades(unix_now())?;
+            db.get_trade(trade_id)
+        })
+        .await?;
+
+    let trade = trade
+        .filter(|trade| trade.game == game)
+        .ok_or_else(|| ApiError::NotFound(format!("Not found: trade offer {}", trade_id)))?;
+    if !access.controls(&game, &trade.from_uuid) {
+        access.require_character(&game, &trade.to_uuid)?;
+    }
+    Ok(Json(trade))
+}
+
+/// Accepts a pending offer, exchanging the items.
+#[utoipa::path(
+    post,
+    path = "/api/games/{game}/trades/{id}/accept",
+    tag = "trades",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        ("id" = i64, Path, description = "Trade offer ID"),
+    ),
+    responses(
+        (status = 200, description = "The offer was accepted and the items exchanged", body = TradeOffer),
+        (status = 404, description = "No such trade offer", body = ErrorBody),
+        (status = 422, description = "The request failed validation or refers to data that doesn't exist", body = ErrorBody),
+    ),
+)]
+pub async fn accept_trade(
+    State(state): State<AppState>,
+    access: Access,
+    Path((game, trade_id)): Path<(String, i64)>,
+) -> Result<Json<TradeOffer>, ApiError> {
+    answer_trade(state, access, game, trade_id, true).await
+}
+
+/// Rejects a pending offer.
+#[utoipa::path(
+    post,
+    path = "/api/games/{game}/trades/{id}/reject",
+    tag = "trades",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        ("id" = i64, Path, description = "Trade offer ID"),
+    ),
+    responses(
+        (status = 200, description = "The offer was rejected", body = TradeOffer),
+        (status = 404, description = "No such trade offer", body = ErrorBody),
+        (status = 422, description = "The request failed validation or refers to data that doesn't exist", body = ErrorBody),
+    ),
+)]
+pub async fn reject_trade(
+    State(state): State<AppState>,
+    access: Access,
+    Path((game, trade_id)): Path<(String, i64)>,
+) -> Result<Json<TradeOffer>, ApiError> {
+    answer_trade(state, access, game, trade_id, false).await
+}
+
+/// Answers an offer on behalf of the character it was made to.
+async fn answer_trade(
+    state: AppState,
+    access: Access,
+    game: String,
+    trade_id: i64,
+    accept: bool,
+) -> Result<Json<TradeOffer>, ApiError> {
+    let trade = state
+        .db
+        .run(move |db| {
+            let trade = db
+                .get_trade(trade_id)?
+                .filter(|trade| trade.game == game)
+                .ok_or_else(|| DbError::not_found(format!("trade offer {}", trade_id)))?;
+            access.require_character(&game, &trade.to_uuid)?;
+            Ok::<_, ApiError>(if accept {
+                db.accept_trade(trade_id, unix_now())?
+            } else {
+                db.reject_trade(trade_id, unix_now())?
+            })
+        })
+        .await?;
+
+    Ok(Json(trade))
+}
+
+/// Lists one page of a game's characters.
+#[utoipa::path(
+    get,
+    path = "/api/games/{game}/characters",
+    tag = "characters",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        CharacterListQuery,
+    ),
+    responses(
+        (status = 200, description = "One page of the game's characters", body = Page<CharacterResource>),
+        (status = 422, description = "The request failed validation or refers to data that doesn't exist", body = ErrorBody),
+    ),
+)]
+pub async fn list_characters(
+    State(state): State<AppState>,
+    Path(game): Path<String>,
+    Query(query): Query<CharacterListQuery>,
+) -> Result<Json<Page<CharacterResource>>, ApiError> {
+    let defaults = PageRequest::default();
+    let page = PageRequest {
+        limit: query.limit.unwrap_or(defaults.limit),
+        offset: query.offset.unwrap_or(defaults.offset),
+        descending: query.descending.unwrap_or(defaults.descending),
+    };
+    let sort = query.sort.unwrap_or_default();
+
+    let records = state
+        .db
+        .run(move |db| db.list_characters(&game, sort, &page))
+        .await?;
+
+    Ok(Json(Page {
+        items: records.items.iter().map(character_resource).collect(),
+        total: records.total,
+        limit: records.limit,
+        offset: records.offset,
+    }))
+}
+
+/// Creates a character from a sheet; stats left out default to 1.
+#[utoipa::path(
+    post,
+    path = "/api/games/{game}/characters",
+    tag = "characters",
+    params(("game" = String, Path, description = "Name of the game")),
+    request_body = Character,
+    responses(
+        (status = 201, description = "The character was created", body = CharacterResource),
+        (status = 409, description = "A character with this name already exists in the game", body = ErrorBody),
+        (status = 422, description = "The request failed validation or refers to data that doesn't exist", body = ErrorBody),
+    ),
+)]
+pub async fn create_character(
+    State(state): State<AppState>,
+    Path(game): Path<String>,
+    Json(body): Json<Map<String, Value>>,
+) -> Result<(StatusCode, Json<CharacterResource>), ApiError> {
+    let sheet = sheet_from_body(body)?;
+    sheet.validate()?;
+
+    let record = state
+        .db
+        .run(move |db| {
+            let uuid = db.insert_character(&sheet.name, &game, Some(&merge_sheet_data(None, &sheet)))?;
+            find_character(db, &game, &uuid)
+        })
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(publish_character(&state, &record))))
+}
+
+/// Fetches one character by UUID.
+#[utoipa::path(
+    get,
+    path = "/api/games/{game}/characters/{id}",
+    tag = "characters",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        ("id" = String, Path, description = "Character UUID"),
+    ),
+    responses(
+        (status = 200, description = "The character", body = CharacterResource),
+        (status = 404, description = "No such character", body = ErrorBody),
+    ),
+)]
+pub async fn get_character(
+    State(state): State<AppState>,
+    Path((game, id)): Path<(String, String)>,
+) -> Result<Json<CharacterResource>, ApiError> {
+    let record = state
+        .db
+        .run(move |db| find_character(db, &game, &id))
+        .await?;
+
+    Ok(Json(character_resource(&record)))
+}
+
+/// Replaces a character's whole sheet; stats left out are reset to 1.
+#[utoipa::path(
+    put,
+    path = "/api/games/{game}/characters/{id}",
+    tag = "characters",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        ("id" = String, Path, description = "Character UUID"),
+    ),
+    request_body = Character,
+    responses(
+        (status = 200, description = "The replaced character", body = CharacterResource),
+        (status = 404, description = "No such character", body = ErrorBody),
+        (status = 409, description = "Another character in the game has the new name", body = ErrorBody),
+        (status = 422, description = "The request failed validation or refers to data that doesn't exist", body = ErrorBody),
+    ),
+)]
+pub async fn replace_character(
+    State(state): State<AppState>,
+    Path((game, id)): Path<(String, String)>,
+    Json(body): Json<Map<String, Value>>,
+) -> Result<Json<CharacterResource>, ApiError> {
+    let sheet = sheet_from_body(body)?;
+    sheet.validate()?;
+
+    let record = state
+        .db
+        .run(move |db| {
+            let record = find_character(db, &game, &id)?;
+            save_sheet(db, &record, &sheet)
+        })
+        .await?;
+
+    Ok(Json(publish_character(&state, &record)))
+}
+
+/// Changes only the sheet fields present in the body.
+#[utoipa::path(
+    patch,
+    path = "/api/games/{game}/characters/{id}",
+    tag = "characters",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        ("id" = String, Path, description = "Character UUID"),
+    ),
+    request_body(content = Object, description = "Sheet fields to change; omitted fields are kept"),
+    responses(
+        (status = 200, description = "The updated character", body = CharacterResource),
+        (status = 404, description = "No such character", body = ErrorBody),
+        (status = 409, description = "Another character in the game has the new name", body = ErrorBody),
+        (status = 422, description = "The request failed validation or refers to data that doesn't exist", body = ErrorBody),
+    ),
+)]
+pub async fn patch_character(
+    State(state): State<AppState>,
+    Path((game, id)): Path<(String, String)>,
+    Json(patch): Json<Value>,
+) -> Result<Json<CharacterResource>, ApiError> {
+    let Value::Object(patch) = patch else {
+        return Err(vec![FieldError::new("body", "must be a JSON object")].into());
+    };
+
+    let record = state
+        .db
+        .run(move |db| {
+            let record = find_character(db, &game, &id)?;
+            let sheet = apply_patch(&record.sheet(), patch)?;
+            sheet.validate()?;
+            save_sheet(db, &record, &sheet)
+        })
+        .await?;
+
+    Ok(Json(publish_character(&state, &record)))
+}
+
+/// Deletes a character along with its inventory.
+#[utoipa::path(
+    delete,
+    path = "/api/games/{game}/characters/{id}",
+    tag = "characters",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        ("id" = String, Path, description = "Character UUID"),
+    ),
+    responses(
+        (status = 204, description = "The character and its inventory were deleted"),
+        (status = 404, description = "No such character", body = ErrorBody),
+    ),
+)]
+pub async fn delete_character(
+    State(state): State<AppState>,
+    Path((game, id)): Path<(String, String)>,
+) -> Result<StatusCode, ApiError> {
+    let record = state
+        .db
+        .run(move |db| {
+            let record = find_character(db, &game, &id)?;
+            db.delete_character_by_uuid(&record.uuid)?;
+            Ok::<_, ApiError>(record)
+        })
+        .await?;
+
+    state.events.publish(
+        &record.game,
+        GameEvent::CharacterDeleted {
+            character: record.uuid,
+        },
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Tells the character's game about its new state and returns that state.
+fn publish_character(state: &AppState, record: &CharacterRecord) -> CharacterResource {
+    let character = character_resource(record);
+    state.events.publish(
+        &record.game,
+        GameEvent::CharacterUpdated {
+            character: character.clone(),
+        },
+    );
+    character
+}
+
+fn character_resource(record: &CharacterRecord) -> CharacterResource {
+    CharacterResource {
+        id: record.uuid.clone(),
+        game: record.game.clone(),
+        sheet: record.sheet(),
+    }
+}
+
+/// Looks a character up by UUID, treating one from another game as missing.
+fn find_character(db: &Database, game: &str, id: &str) -> Result<CharacterRecord, ApiError> {
+    db.get_character_by_uuid(id)?
+        .filter(|record| record.game == game)
+        .ok_or_else(|| ApiError::NotFound(format!("Not found: character {}", id)))
+}
+
+/// Writes a validated sheet, renaming the character if the name changed.
+fn save_sheet(
+    db: &Database,
+    record: &CharacterRecord,
+    sheet: &Character,
+) -> Result<CharacterRecord, ApiError> {
+    db.transaction(|db| {
+        if sheet.name != record.name {
+            db.rename_character(&record.uuid, &sheet.name)?;
+        }
+        let data = merge_sheet_data(record.data.as_deref(), sheet);
+        db.update_character_by_uuid(&record.uuid, &data)?;
+        find_character(db, &record.game, &record.uuid)
+    })
+}
+
+/// Reads a whole sheet from a POST or PUT body, starting from the defaults.
+///
+/// The `id` and `game` a GET returns are ignored so a fetched character can
+/// be sent straight back; any other field the sheet lacks is rejected.
+fn sheet_from_body(mut body: Map<String, Value>) -> Result<Character, ApiError> {
+    body.remove("id");
+    body.remove("game");
+    apply_patch(&Character::default(), body)
+}
+
+/// Applies top-level fields from a PATCH body to a sheet.
+fn apply_patch(sheet: &Character, patch: Map<String, Value>) -> Result<Character, ApiError> {
+    let Ok(Value::Object(mut current)) = serde_json::to_value(sheet) else {
+        return Err(ApiError::Internal("character sheet is not a JSON object".to_string()));
+    };
+
+    let mut errors = Vec::new();
+    for (field, value) in patch {
+        if current.contains_key(&field) {
+            current.insert(field, value);
+        } else {
+            errors.push(FieldError::new(field, "unknown field"));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors.into());
+    }
+
+    serde_path_to_error::deserialize(Value::Object(current)).map_err(|err| {
+        let field = err.path().to_string();
+        vec![FieldError::new(field, err.into_inner().to_string())].into()
+    })
+}
+
+/// Exports a character and its inventory as a Foundry actor document.
+#[utoipa::path(
+    get,
+    path = "/api/games/{game}/characters/{id}/foundry",
+    tag = "foundry",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        ("id" = String, Path, description = "Character UUID"),
+        FoundryQuery,
+    ),
+    responses(
+        (status = 200, description = "The character as a Foundry actor document", body = Object),
+        (status = 404, description = "No such character", body = ErrorBody),
+        (status = 422, description = "The request failed validation or refers to data that doesn't exist", body = ErrorBody),
+    ),
+)]
+pub async fn export_foundry_actor(
+    State(state): State<AppState>,
+    Path((game, id)): Path<(String, String)>,
+    Query(query): Query<FoundryQuery>,
+) -> Result<Json<Value>, ApiError> {
+    let templates = state.foundry.clone();
+    let actor = state
+        .db
+        .run(move |db| {
+            let template = templates.get(query.template.as_deref().unwrap_or(DEFAULT_TEMPLATE))?;
+            let record = find_character(db, &game, &id)?;
+            Ok::<_, ApiError>(foundry::export_character(db, template, &record)?)
+        })
+        .await?;
+
+    Ok(Json(actor))
+}
+
+/// Creates a character from a Foundry actor document.
+#[utoipa::path(
+    post,
+    path = "/api/games/{game}/foundry",
+    tag = "foundry",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        FoundryQuery,
+    ),
+    request_body(content = Object, description = "A Foundry actor document"),
+    responses(
+        (status = 201, description = "The actor was imported as a new character", body = CharacterResource),
+        (status = 409, description = "A character with the actor's name already exists in the game", body = ErrorBody),
+        (status = 422, description = "The request failed validation or refers to data that doesn't exist", body = ErrorBody),
+    ),
+)]
+pub async fn import_foundry_actor(
+    State(state): State<AppState>,
+    Path(game): Path<String>,
+    Query(query): Query<FoundryQuery>,
+    Json(document): Json<Value>,
+) -> Result<(StatusCode, Json<CharacterResource>), ApiError> {
+    let templates = state.foundry.clone();
+    let record = state
+        .db
+        .run(move |db| {
+            let template = templates.get(query.template.as_deref().unwrap_or(DEFAULT_TEMPLATE))?;
+            Ok::<_, ApiError>(foundry::import_character(db, template, &game, &document, None)?)
+        })
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(publish_character(&state, &record))))
+}
+
+/// Replaces a character's sheet and inventory with a Foundry actor document.
+#[utoipa::path(
+    put,
+    path = "/api/games/{game}/characters/{id}/foundry",
+    tag = "foundry",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        ("id" = String, Path, description = "Character UUID"),
+        FoundryQuery,
+    ),
+    request_body(content = Object, description = "A Foundry actor document"),
+    responses(
+        (status = 200, description = "The character was updated from the actor", body = CharacterResource),
+        (status = 404, description = "No such character", body = ErrorBody),
+        (status = 409, description = "Another character in the game has the actor's name", body = ErrorBody),
+        (status = 422, description = "The request failed validation or refers to data that doesn't exist", body = ErrorBody),
+    ),
+)]
+pub async fn reimport_foundry_actor(
+    State(state): State<AppState>,
+    Path((game, id)): Path<(String, String)>,
+    Query(query): Query<FoundryQuery>,
+    Json(document): Json<Value>,
+) -> Result<Json<CharacterResource>, ApiError> {
+    let templates = state.foundry.clone();
+    let record = state
+        .db
+        .run(move |db| {
+            let template = templates.get(query.template.as_deref().unwrap_or(DEFAULT_TEMPLATE))?;
+            let existing = find_character(db, &game, &id)?;
+            Ok::<_, ApiError>(foundry::import_character(
+                db,
+                template,
+                &game,
+                &document,
+                Some(&existing),
+            )?)
+        })
+        .await?;
+
+    Ok(Json(publish_character(&state, &record)))
+}
+
+/// Lists object definitions, optionally only those of one `type`.
+#[utoipa::path(
+    get,
+    path = "/api/objects",
+    tag = "objects",
+    params(ObjectTypeQuery),
+    responses(
+        (status = 200, description = "Object definitions, optionally of one type", body = Vec<ObjectResource>),
+    ),
+)]
+pub async fn list_objects(
+    State(state): State<AppState>,
+    Query(query): Query<ObjectTypeQuery>,
+) -> Result<Json<Vec<ObjectResource>>, ApiError> {
+    let objects = state
+        .db
+        .run(move |db| db.list_objects(query.obj_type.as_deref()))
+        .await?;
+
+    Ok(Json(objects.into_iter().map(object_resource).collect()))
+}
+
+/// Creates an object definition.
+#[utoipa::path(
+    post,
+    path = "/api/objects",
+    tag = "objects",
+    request_body = CreateObjectRequest,
+    responses(
+        (status = 201, description = "The object definition was created", body = ObjectResource),
+        (status = 409, description = "An object with this name already exists", body = ErrorBody),
+        (status = 422, description = "The request failed validation or refers to data that doesn't exist", body = ErrorBody),
+    ),
+)]
+pub async fn create_object(
+    State(state): State<AppState>,
+    Json(request): Json<CreateObjectRequest>,
+) -> Result<(StatusCode, Json<ObjectResource>), ApiError> {
+    let properties = validate_object(&request.name, &request.obj_type, &request.properties)?;
+
+    let object = state
+        .db
+        .run(move |db| {
+            let id = db.insert_object(&request.name, &request.obj_type, properties.as_deref())?;
+            find_object(db, id)
+        })
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(object_resource(object))))
+}
+
+/// Fetches one object definition.
+#[utoipa::path(
+    get,
+    path = "/api/objects/{id}",
+    tag = "objects",
+    params(("id" = i64, Path, description = "Object definition ID")),
+    responses(
+        (status = 200, description = "The object definition", body = ObjectResource),
+        (status = 404, description = "No such object", body = ErrorBody),
+    ),
+)]
+pub async fn get_object(
+    State(state): State<AppState>,
+    Path(object_id): Path<i64>,
+) -> Result<Json<ObjectResource>, ApiError> {
+    let object = state.db.run(move |db| find_object(db, object_id)).await?;
+
+    Ok(Json(object_resource(object)))
+}
+
+/// Changes an object definition's name, type or properties.
+#[utoipa::path(
+    patch,
+    path = "/api/objects/{id}",
+    tag = "objects",
+    params(("id" = i64, Path, description = "Object definition ID")),
+    request_body = UpdateObjectRequest,
+    responses(
+        (status = 200, description = "The updated object definition", body = ObjectResource),
+        (status = 404, description = "No such object", body = ErrorBody),
+        (status = 409, description = "Another object has the new name", body = ErrorBody),
+        (status = 422, description = "The request failed validation or refers to data that doesn't exist", body = ErrorBody),
+    ),
+)]
+pub async fn update_object(
+    State(state): State<AppState>,
+    access: Access,
+    Path(object_id): Path<i64>,
+    Json(request): Json<UpdateObjectRequest>,
+) -> Result<Json<ObjectResource>, ApiError> {
+    let object = state
+        .db
+        .run(move |db| {
+            db.transaction(|db| {
+                let current = find_object(db, object_id)?;
+                access.require_gm_of_all(&db.object_games(object_id)?)?;
+                let name = request.name.unwrap_or(current.name);
+                let obj_type = request.obj_type.unwrap_or(current.obj_type);
+                let properties = match request.properties {
+                    Some(properties) => validate_object(&name, &obj_type, &properties)?,
+                    None => {
+                        validate_object(&name, &obj_type, &Value::Null)?;
+                        current.properties
+                    }
+                };
+
+                db.replace_object(object_id, &name, &obj_type, properties.as_deref())?;
+                find_object(db, object_id)
+            })
+        })
+        .await?;
+
+    Ok(Json(object_resource(object)))
+}
+
+/// Deletes an object definition, removing it from every inventory.
+#[utoipa::path(
+    delete,
+    path = "/api/objects/{id}",
+    tag = "objects",
+    params(("id" = i64, Path, description = "Object definition ID")),
+    responses(
+        (status = 204, description = "The object definition was deleted, along with every copy of it"),
+        (status = 404, description = "No such object", body = ErrorBody),
+    ),
+)]
+pub async fn delete_object(
+    State(state): State<AppState>,
+    access: Access,
+    Path(object_id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    state
+        .db
+        .run(move |db| {
+            db.transaction(|db| {
+                find_object(db, object_id)?;
+                access.require_gm_of_all(&db.object_games(object_id)?)?;
+                db.delete_object(object_id)?;
+                Ok::<_, ApiError>(())
+            })
+        })
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Lists a character's inventory, optionally only objects of one `type`.
+#[utoipa::path(
+    get,
+    path = "/api/games/{game}/characters/{id}/inventory",
+    tag = "inventory",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        ("id" = String, Path, description = "Character UUID"),
+        ObjectTypeQuery,
+    ),
+    responses(
+        (status = 200, description = "The character's inventory, optionally of one object type", body = Vec<InventoryItem>),
+        (status = 404, description = "No such character", body = ErrorBody),
+    ),
+)]
+pub async fn list_inventory(
+    State(state): State<AppState>,
+    Path((game, id)): Path<(String, String)>,
+    Query(query): Query<ObjectTypeQuery>,
+) -> Result<Json<Vec<InventoryItem>>, ApiError> {
+    let entries = state
+        .db
+        .run(move |db| {
+            let character = find_character(db, &game, &id)?;
+            Ok::<_, ApiError>(db.get_character_objects(&game, &character.name)?)
+        })
+        .await?;
+
+    Ok(Json(
+        entries
+            .into_iter()
+            .filter(|entry| query.obj_type.as_ref().is_none_or(|t| &entry.obj_type == t))
+            .map(inventory_item)
+            .collect(),
+    ))
+}
+
+/// Adds objects to a character's inventory, stacking with any already held.
+#[utoipa::path(
+    post,
+    path = "/api/games/{game}/characters/{id}/inventory",
+    tag = "inventory",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        ("id" = String, Path, description = "Character UUID"),
+    ),
+    request_body = AddInventoryRequest,
+    responses(
+        (status = 200, description = "The stack after adding", body = InventoryItem),
+        (status = 404, description = "No such character", body = ErrorBody),
+        (status = 422, description = "The request failed validation or refers to data that doesn't exist", body = ErrorBody),
+    ),
+)]
+pub async fn add_inventory(
+    State(state): State<AppState>,
+    Path((game, id)): Path<(String, String)>,
+    Json(request): Json<AddInventoryRequest>,
+) -> Result<Json<InventoryItem>, ApiError> {
+    validate_quantity(request.quantity)?;
+
+    let entry = state
+        .db
+        .run(move |db| {
+            db.transaction(|db| {
+                let character = find_character(db, &game, &id)?;
+                find_object(db, request.object_id)?;
+                db.add_object_to_character(
+                    &game,
+                    &character.name,
+                    request.object_id,
+                    request.quantity,
+                )?;
+                find_stack(db, &character, request.object_id)
+            })
+        })
+        .await?;
+
+    Ok(Json(inventory_item(entry)))
+}
+
+/// Sets how many of an object a character holds.
+#[utoipa::path(
+    put,
+    path = "/api/games/{game}/characters/{id}/inventory/{object_id}",
+    tag = "inventory",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        ("id" = String, Path, description = "Character UUID"),
+        ("object_id" = i64, Path, description = "Object definition ID"),
+    ),
+    request_body = SetQuantityRequest,
+    responses(
+        (status = 200, description = "The stack with its new quantity", body = InventoryItem),
+        (status = 404, description = "No such character or stack", body = ErrorBody),
+        (status = 422, description = "The request failed validation or refers to data that doesn't exist", body = ErrorBody),
+    ),
+)]
+pub async fn set_inventory_quantity(
+    State(state): State<AppState>,
+    Path((game, id, object_id)): Path<(String, String, i64)>,
+    Json(request): Json<SetQuantityRequest>,
+) -> Result<Json<InventoryItem>, ApiError> {
+    validate_quantity(request.quantity)?;
+
+    let entry = state
+        .db
+        .run(move |db| {
+            let character = find_character(db, &game, &id)?;
+            find_stack(db, &character, object_id)?;
+            db.update_object_quantity(&game, &character.name, object_id, request.quantity)?;
+            find_stack(db, &character, object_id)
+        })
+        .await?;
+
+    Ok(Json(inventory_item(entry)))
+}
+
+/// Removes a whole stack from a character's inventory.
+#[utoipa::path(
+    delete,
+    path = "/api/games/{game}/characters/{id}/inventory/{object_id}",
+    tag = "inventory",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        ("id" = String, Path, description = "Character UUID"),
+        ("object_id" = i64, Path, description = "Object definition ID"),
+    ),
+    responses(
+        (status = 204, description = "The stack was removed"),
+        (status = 404, description = "No such character or stack", body = ErrorBody),
+    ),
+)]
+pub async fn remove_inventory(
+    State(state): State<AppState>,
+    Path((game, id, object_id)): Path<(String, String, i64)>,
+) -> Result<StatusCode, ApiError> {
+    state
+        .db
+        .run(move |db| {
+            let character = find_character(db, &game, &id)?;
+            find_stack(db, &character, object_id)?;
+            db.remove_object_from_character(&game, &character.name, object_id)?;
+            Ok::<_, ApiError>(())
+        })
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Moves some of a stack to another character in the same game.
+#[utoipa::path(
+    post,
+    path = "/api/games/{game}/characters/{id}/inventory/{object_id}/transfer",
+    tag = "inventory",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        ("id" = String, Path, description = "Character UUID"),
+        ("object_id" = i64, Path, description = "Object definition ID"),
+    ),
+    request_body = TransferRequest,
+    responses(
+        (status = 200, description = "Both characters' stacks of the object after the transfer, giver first; a stack given away entirely has quantity 0", body = Vec<InventoryItem>),
+        (status = 404, description = "No such character or stack", body = ErrorBody),
+        (status = 422, description = "The request failed validation or refers to data that doesn't exist", body = ErrorBody),
+    ),
+)]
+pub async fn transfer_inventory(
+    State(state): State<AppState>,
+    Path((game, id, object_id)): Path<(String, String, i64)>,
+    Json(request): Json<TransferRequest>,
+) -> Result<Json<Vec<InventoryItem>>, ApiError> {
+    validate_quantity(request.quantity)?;
+
+    let stacks = state
+        .db
+        .run(move |db| {
+            let giver = find_character(db, &game, &id)?;
+            db.transfer_object(&giver.uuid, &request.to, object_id, request.quantity)?;
+            let receiver = find_character(db, &game, &request.to)?;
+            let object = find_object(db, object_id)?;
+            [giver, receiver]
+                .iter()
+                .map(|character| {
+                    let quantity = db
+                        .get_character_objects(&game, &character.name)?
+                        .into_iter()
+                        .find(|entry| entry.object_id == object_id)
+                        .map_or(0, |entry| entry.quantity);
+                    Ok::<_, ApiError>(InventoryItem {
+                        quantity,
+                        ..empty_stack(&object)
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .await?;
+
+    Ok(Json(stacks))
+}
+
+/// An empty stack of an object, for filling in with a quantity.
+fn empty_stack(object: &ObjectRecord) -> InventoryItem {
+    InventoryItem {
+        object_id: object.id,
+        name: object.name.clone(),
+        obj_type: object.obj_type.clone(),
+        quantity: 0,
+        properties: parse_properties(object.properties.as_deref()),
+    }
+}
+
+fn object_resource(object: ObjectRecord) -> ObjectResource {
+    ObjectResource {
+        id: object.id,
+        name: object.name,
+        obj_type: object.obj_type,
+        properties: parse_properties(object.properties.as_deref()),
+    }
+}
+
+fn inventory_item(entry: InventoryEntry) -> InventoryItem {
+    InventoryItem {
+        object_id: entry.object_id,
+        name: entry.name,
+        obj_type: entry.obj_type,
+        quantity: entry.quantity,
+        properties: parse_properties(entry.properties.as_deref()),
+    }
+}
+
+/// Parses a stored `properties` string; text that isn't JSON is returned as a string.
+fn parse_properties(properties: Option<&str>) -> Value {
+    match properties {
+        Some(text) => serde_json::from_str(text).unwrap_or_else(|_| Value::String(text.to_string())),
+        None => Value::Null,
+    }
+}
+
+fn find_object(db: &Database, object_id: i64) -> Result<ObjectRecord, ApiError> {
+    db.get_object(object_id)?
+        .ok_or_else(|| ApiError::NotFound(format!("Not found: object {}", object_id)))
+}
+
+/// Looks up one stack of a character's inventory.
+fn find_stack(
+    db: &Database,
+    character: &CharacterRecord,
+    object_id: i64,
+) -> Result<InventoryEntry, ApiError> {
+    db.get_character_objects(&character.game, &character.name)?
+        .into_iter()
+        .find(|entry| entry.object_id == object_id)
+        .ok_or_else(|| {
+            ApiError::NotFound(format!(
+                "Not found: object {} in {}'s inventory",
+                object_id, character.name
+            ))
+        })
+}
+
+/// Checks an object definition and returns its properties as stored text.
+fn validate_object(
+    name: &str,
+    obj_type: &str,
+    properties: &Value,
+) -> Result<Option<String>, ApiError> {
+    let mut errors = Vec::new();
+    if name.trim().is_empty() {
+        errors.push(FieldError::new("name", "must not be blank"));
+    }
+    if obj_type.trim().is_empty() {
+        errors.push(FieldError::new("type", "must not be blank"));
+    }
+    if !matches!(properties, Value::Object(_) | Value::Null) {
+        errors.push(FieldError::new("properties", "must be a JSON object or null"));
+    }
+    if !errors.is_empty() {
+        return Err(errors.into());
+    }
+
+    Ok((!properties.is_null()).then(|| properties.to_string()))
+}
+
+fn validate_quantity(quantity: i32) -> Result<(), ApiError> {
+    if quantity < 1 {
+        return Err(vec![FieldError::new("quantity", "must be at least 1")].into());
+    }
+    Ok(())
+}
+
+/// Rolls a pool for a character and logs the result.
+///
+/// The server picks the seed, applies the character's encumbrance penalty and
+/// stores the full breakdown, so the stored roll is the authoritative one.
+#[utoipa::path(
+    post,
+    path = "/api/roll",
+    tag = "dice",
+    request_body = RollRequest,
+    responses(
+        (status = 201, description = "The roll was made and logged", body = RollResponse),
+        (status = 404, description = "No such character", body = ErrorBody),
+        (status = 422, description = "The request failed validation or refers to data that doesn't exist", body = ErrorBody),
+    ),
+)]
+pub async fn roll(
+    State(state): State<AppState>,
+    access: Access,
+    Json(request): Json<RollRequest>,
+) -> Result<(StatusCode, Json<RollResponse>), ApiError> {
+    access.require_character(&request.game, &request.character)?;
+    let mut errors = Vec::new();
+    if !(2..=DIE_SIDES).contains(&request.pool.difficulty) {
+        errors.push(FieldError::new(
+            "pool.difficulty",
+            format!("must be between 2 and {}", DIE_SIDES),
+        ));
+    }
+    if !(-MAX_POOL_MODIFIER..=MAX_POOL_MODIFIER).contains(&request.pool.modifier) {
+        errors.push(FieldError::new(
+            "pool.modifier",
+            format!("must be between -{0} and {0}", MAX_POOL_MODIFIER),
+        ));
+    }
+    if request.pool.stats.len() > MAX_POOL_STATS {
+        errors.push(FieldError::new(
+            "pool.stats",
+            format!("must list at most {} stats", MAX_POOL_STATS),
+        ));
+    }
+    if !errors.is_empty() {
+        return Err(errors.into());
+    }
+
+    let seed = rand::thread_rng().gen_range(0..MAX_SEED);
+    let record = state
+        .db
+        .run(move |db| {
+            let character = find_character(db, &request.game, &request.character)?;
+            let sheet = character.sheet();
+            let load = InventoryLoad::from_inventory(
+                &db.get_character_objects(&character.game, &character.name)?,
+                &db.get_carried_instances(&character.game, &character.name)?,
+            );
+
+            let mut modifiers = Vec::new();
+            if request.pool.modifier != 0 {
+                modifiers.push(RollModifier {
+                    source: "request".to_string(),
+                    dice: request.pool.modifier,
+                });
+            }
+            let pool = Encumbrance::for_character(&sheet, load).apply(request.pool.clone());
+            if pool.modifier != request.pool.modifier {
+                modifiers.push(RollModifier {
+                    source: "encumbrance".to_string(),
+                    dice: pool.modifier - request.pool.modifier,
+                });
+            }
+            let dice = pool.size(&sheet);
+            if dice > MAX_POOL_DICE {
+                return Err(vec![FieldError::new(
+                    "pool",
+                    format!("comes to {} dice, more than the {} a roll may use", dice, MAX_POOL_DICE),
+                )]
+                .into());
+            }
+
+            let result = pool.roll(&sheet, &mut StdRng::seed_from_u64(seed));
+            let breakdown = RollBreakdown {
+                game: character.game.clone(),
+                character: character.uuid.clone(),
+                character_name: character.name.clone(),
+                label: request.label,
+                stats: pool
+                    .stats
+                    .iter()
+                    .map(|&stat| StatDice {
+                        stat,
+                        rating: sheet.stat(stat),
+                    })
+                    .collect(),
+                modifiers,
+                dice,
+                success: result.is_success(),
+                result,
+            };
+            let breakdown = serde_json::to_value(&breakdown)
+                .map_err(|err| ApiError::Internal(err.to_string()))?;
+            Ok::<_, ApiError>(db.record_roll(&character.game, &character.uuid, seed, &breakdown)?)
+        })
+        .await?;
+
+    let roll = roll_response(record)?;
+    state.events.publish(
+        &roll.breakdown.game,
+        GameEvent::RollMade { roll: roll.clone() },
+    );
+    Ok((StatusCode::CREATED, Json(roll)))
+}
+
+/// Fetches a logged roll by ID.
+#[utoipa::path(
+    get,
+    path = "/api/rolls/{id}",
+    tag = "dice",
+    params(("id" = i64, Path, description = "Roll ID")),
+    responses(
+        (status = 200, description = "The logged roll", body = RollResponse),
+        (status = 404, description = "No such roll", body = ErrorBody),
+    ),
+)]
+pub async fn get_roll(
+    State(state): State<AppState>,
+    access: Access,
+    Path(id): Path<i64>,
+) -> Result<Json<RollResponse>, ApiError> {
+    let record = state
+        .db
+        .run(move |db| db.get_roll(id))
+        .await?
+        .filter(|record| access.require_game(&record.game).is_ok())
+        .ok_or_else(|| ApiError::NotFound(format!("Not found: roll {}", id)))?;
+
+    Ok(Json(roll_response(record)?))
+}
+
+fn roll_response(record: RollRecord) -> Result<RollResponse, ApiError> {
+    let breakdown = serde_json::from_value(record.breakdown)
+        .map_err(|err| ApiError::Internal(format!("roll {}: {}", record.id, err)))?;
+    Ok(RollResponse {
+        id: record.id,
+        seed: record.seed,
+        rolled_at: record.created_at,
+        session_id: record.session_id,
+        scene_id: record.scene_id,
+        breakdown,
+    })
+}
+
+/// Upgrades to a WebSocket streaming a game's live events.
+///
+/// Pass `?since=<seq>` after a reconnect to receive the events missed in between.
+#[utoipa::path(
+    get,
+    path = "/api/games/{game}/events",
+    tag = "events",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        EventStreamQuery,
+        ("access_token" = Option<String>, Query, description = "API token, for clients that can't set the Authorization header"),
+    ),
+    responses(
+        (status = 101, description = "Switched to a WebSocket sending one `EventEnvelope` JSON message per event"),
+        (status = 400, description = "The request wasn't a WebSocket upgrade", body = ErrorBody),
+    ),
+)]
+pub async fn event_stream(
+    State(state): State<AppState>,
+    access: Access,
+    Path(game): Path<String>,
+    Query(query): Query<EventStreamQuery>,
+    upgrade: Result<WebSocketUpgrade, WebSocketUpgradeRejection>,
+) -> Result<Response, ApiError> {
+    let events = state.events.clone();
+    // The token was checked once for the upgrade; recheck it on every
+    // heartbeat so revoking it also ends the stream
+    let still_allowed = move || {
+        let (access, db) = (access.clone(), state.db.clone());
+        async move { access.is_still_valid(&db).await }
+    };
+    Ok(upgrade?.on_upgrade(move |socket| events.serve(socket, game, query.since, still_allowed)))
+}
+
+/// Publishes a client-reported event, such as damage or a new turn, to a game.
+///
+/// Events the server produces itself (character changes, rolls) are rejected.
+#[utoipa::path(
+    post,
+    path = "/api/games/{game}/events",
+    tag = "events",
+    params(("game" = String, Path, description = "Name of the game")),
+    request_body = GameEvent,
+    responses(
+        (status = 202, description = "The event was published", body = EventEnvelope),
+        (status = 404, description = "No such character", body = ErrorBody),
+        (status = 422, description = "The request failed validation or refers to data that doesn't exist", body = ErrorBody),
+    ),
+)]
+pub async fn publish_event(
+    State(state): State<AppState>,
+    Path(game): Path<String>,
+    Json(event): Json<GameEvent>,
+) -> Result<(StatusCode, Json<EventEnvelope>), ApiError> {
+    if event.is_server_only() {
+        return Err(vec![FieldError::new("type", "this event is published by the server")].into());
+    }
+
+    let lookup_game = game.clone();
+    match event.clone() {
+        // Damage goes into the character log, tagged with the open session and scene
+        GameEvent::DamageApplied {
+            character,
+            amount,
+            source,
+        } => {
+            state
+                .db
+                .run(move |db| {
+                    let character = find_character(db, &lookup_game, &character)?;
+                    db.log_character(&character.uuid, LogKind::Damage, amount, source.as_deref())?;
+                    Ok::<_, ApiError>(())
+                })
+                .await?;
+        }
+        GameEvent::TurnAdvanced {
+            character: Some(character),
+            ..
+        } => {
+            state
+                .db
+                .run(move |db| find_character(db, &lookup_game, &character))
+                .await?;
+        }
+        _ => {}
+    }
+
+    Ok((StatusCode::ACCEPTED, Json(state.events.publish(&game, event))))
+}
+
+/// Lists a game's sessions, newest first.
+#[utoipa::path(
+    get,
+    path = "/api/games/{game}/sessions",
+    tag = "sessions",
+    params(("game" = String, Path, description = "Name of the game"), SessionListQuery),
+    responses(
+        (status = 200, description = "The game's sessions, newest first", body = Vec<Session>),
+    ),
+)]
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    Path(game): Path<String>,
+    Query(query): Query<SessionListQuery>,
+) -> Result<Json<Vec<Session>>, ApiError> {
+    let limit = query.limit.unwrap_or(20).clamp(1, 100);
+    let sessions = state
+        .db
+        .run(move |db| db.list_sessions(&game, limit))
+        .await?;
+    Ok(Json(sessions))
+}
+
+/// Starts a session of the game; fails while another one is open.
+#[utoipa::path(
+    post,
+    path = "/api/games/{game}/sessions",
+    tag = "sessions",
+    params(("game" = String, Path, description = "Name of the game")),
+    request_body = StartSessionRequest,
+    responses(
+        (status = 201, description = "The session was started", body = Session),
+        (status = 422, description = "A session is already open, or the request failed validation", body = ErrorBody),
+    ),
+)]
+pub async fn start_session(
+    State(state): State<AppState>,
+    Path(game): Path<String>,
+    Json(request): Json<StartSessionRequest>,
+) -> Result<(StatusCode, Json<Session>), ApiError> {
+    validate_seed(request.seed)?;
+    let session_game = game.clone();
+    let session = state
+        .db
+        .run(move |db| {
+            db.start_session(
+                &session_game,
+                request.title.as_deref(),
+                request.seed,
+                &request.participants,
+            )
+        })
+        .await?;
+
+    state.events.publish(
+        &game,
+        GameEvent::SessionStarted {
+            session: session.clone(),
+        },
+    );
+    Ok((StatusCode::CREATED, Json(session)))
+}
+
+/// Fetches one of the game's sessions.
+#[utoipa::path(
+    get,
+    path = "/api/games/{game}/sessions/{id}",
+    tag = "sessions",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        ("id" = i64, Path, description = "Session ID"),
+    ),
+    responses(
+        (status = 200, description = "The session", body = Session),
+        (status = 404, description = "No such session", body = ErrorBody),
+    ),
+)]
+pub async fn get_session(
+    State(state): State<AppState>,
+    Path((game, session_id)): Path<(String, i64)>,
+) -> Result<Json<Session>, ApiError> {
+    let session = state
+        .db
+        .run(move |db| find_session(db, &game, session_id))
+        .await?;
+    Ok(Json(session))
+}
+
+/// Ends a session, and its open scene if it has one.
+#[utoipa::path(
+    post,
+    path = "/api/games/{game}/sessions/{id}/end",
+    tag = "sessions",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        ("id" = i64, Path, description = "Session ID"),
+    ),
+    responses(
+        (status = 200, description = "The ended session", body = Session),
+        (status = 404, description = "No such open session", body = ErrorBody),
+    ),
+)]
+pub async fn end_session(
+    State(state): State<AppState>,
+    Path((game, session_id)): Path<(String, i64)>,
+) -> Result<Json<Session>, ApiError> {
+    let session_game = game.clone();
+    let session = state
+        .db
+        .run(move |db| {
+            find_session(db, &session_game, session_id)?;
+            Ok::<_, ApiError>(db.end_session(session_id)?)
+        })
+        .await?;
+
+    state.events.publish(
+        &game,
+        GameEvent::SessionEnded {
+            session: session.clone(),
+        },
+    );
+    Ok(Json(session))
+}
+
+/// Summarizes a session: its scenes, rolls, damage and XP.
+#[utoipa::path(
+    get,
+    path = "/api/games/{game}/sessions/{id}/recap",
+    tag = "sessions",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        ("id" = i64, Path, description = "Session ID"),
+    ),
+    responses(
+        (status = 200, description = "What happened in the session", body = SessionRecap),
+        (status = 404, description = "No such session", body = ErrorBody),
+    ),
+)]
+pub async fn session_recap(
+    State(state): State<AppState>,
+    Path((game, session_id)): Path<(String, i64)>,
+) -> Result<Json<SessionRecap>, ApiError> {
+    let (session, scenes, rolls, log) = state
+        .db
+        .run(move |db| {
+            let session = find_session(db, &game, session_id)?;
+            Ok::<_, ApiError>((
+                session,
+                db.list_scenes(session_id)?,
+                db.list_session_rolls(session_id)?,
+                db.session_log(session_id)?,
+            ))
+        })
+        .await?;
+
+    Ok(Json(SessionRecap {
+        session,
+        scenes,
+        rolls: rolls
+            .into_iter()
+            .map(roll_response)
+            .collect::<Result<_, _>>()?,
+        log,
+    }))
+}
+
+/// Starts a scene in an open session; fails while another scene is open.
+#[utoipa::path(
+    post,
+    path = "/api/games/{game}/sessions/{id}/scenes",
+    tag = "sessions",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        ("id" = i64, Path, description = "Session ID"),
+    ),
+    request_body = StartSceneRequest,
+    responses(
+        (status = 201, description = "The scene was started", body = Scene),
+        (status = 404, description = "No such open session", body = ErrorBody),
+        (status = 422, description = "A scene is already open, or the request failed validation", body = ErrorBody),
+    ),
+)]
+pub async fn start_scene(
+    State(state): State<AppState>,
+    Path((game, session_id)): Path<(String, i64)>,
+    Json(request): Json<StartSceneRequest>,
+) -> Result<(StatusCode, Json<Scene>), ApiError> {
+    validate_seed(request.seed)?;
+    let session_game = game.clone();
+    let scene = state
+        .db
+        .run(move |db| {
+            find_session(db, &session_game, session_id)?;
+            Ok::<_, ApiError>(db.start_scene(
+                session_id,
+                request.title.as_deref(),
+                request.seed,
+                &request.participants,
+            )?)
+        })
+        .await?;
+
+    state.events.publish(
+        &game,
+        GameEvent::SceneStarted {
+            scene: scene.clone(),
+        },
+    );
+    Ok((StatusCode::CREATED, Json(scene)))
+}
+
+/// Ends a scene.
+#[utoipa::path(
+    post,
+    path = "/api/games/{game}/sessions/{id}/scenes/{scene_id}/end",
+    tag = "sessions",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        ("id" = i64, Path, description = "Session ID"),
+        ("scene_id" = i64, Path, description = "Scene ID"),
+    ),
+    responses(
+        (status = 200, description = "The ended scene", body = Scene),
+        (status = 404, description = "No such open scene", body = ErrorBody),
+    ),
+)]
+pub async fn end_scene(
+    State(state): State<AppState>,
+    Path((game, session_id, scene_id)): Path<(String, i64, i64)>,
+) -> Result<Json<Scene>, ApiError> {
+    let session_game = game.clone();
+    let scene = state
+        .db
+        .run(move |db| {
+            find_session(db, &session_game, session_id)?;
+            db.get_scene(scene_id)?
+                .filter(|scene| scene.session_id == session_id)
+                .ok_or_else(|| ApiError::NotFound(format!("Not found: scene {}", scene_id)))?;
+            Ok::<_, ApiError>(db.end_scene(scene_id)?)
+        })
+        .await?;
+
+    state.events.publish(
+        &game,
+        GameEvent::SceneEnded {
+            scene: scene.clone(),
+        },
+    );
+    Ok(Json(scene))
+}
+
+/// Awards a character XP, tagged with the game's open session and scene.
+#[utoipa::path(
+    post,
+    path = "/api/games/{game}/characters/{id}/xp",
+    tag = "sessions",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        ("id" = String, Path, description = "Character UUID"),
+    ),
+    request_body = AwardXpRequest,
+    responses(
+        (status = 201, description = "The award, as logged", body = LogEntry),
+        (status = 404, description = "No such character", body = ErrorBody),
+    ),
+)]
+pub async fn award_xp(
+    State(state): State<AppState>,
+    Path((game, id)): Path<(String, String)>,
+    Json(request): Json<AwardXpRequest>,
+) -> Result<(StatusCode, Json<LogEntry>), ApiError> {
+    let lookup_game = game.clone();
+    let reason = request.reason.clone();
+    let entry = state
+        .db
+        .run(move |db| {
+            let character = find_character(db, &lookup_game, &id)?;
+            Ok::<_, ApiError>(db.log_character(
+                &character.uuid,
+                LogKind::Xp,
+                request.amount,
+                request.reason.as_deref(),
+            )?)
+        })
+        .await?;
+
+    state.events.publish(
+        &game,
+        GameEvent::XpAwarded {
+            character: entry.character_uuid.clone(),
+            amount: entry.amount,
+            reason,
+        },
+    );
+    Ok((StatusCode::CREATED, Json(entry)))
+}
+
+fn find_session(db: &Database, game: &str, session_id: i64) -> Result<Session, ApiError> {
+    db.get_session(session_id)?
+        .filter(|session| session.game == game)
+        .ok_or_else(|| ApiError::NotFound(format!("Not found: session {}", session_id)))
+}
+
+fn validate_seed(seed: Option<u64>) -> Result<(), ApiError> {
+    if seed.is_some_and(|seed| seed >= MAX_SEED) {
+        return Err(vec![FieldError::new("seed", "must be below 2^53")].into());
+    }
+    Ok(())
+}
+
+/// Lists a game's API tokens, including revoked ones. Secrets are never shown.
+#[utoipa::path(
+    get,
+    path = "/api/games/{game}/tokens",
+    tag = "tokens",
+    params(("game" = String, Path, description = "Name of the game")),
+    responses(
+        (status = 200, description = "The game's tokens, including revoked ones", body = Vec<ApiToken>),
+    ),
+)]
+pub async fn list_tokens(
+    State(state): State<AppState>,
+    Path(game): Path<String>,
+) -> Result<Json<Vec<ApiToken>>, ApiError> {
+    let tokens = state.db.run(move |db| db.list_tokens(&game)).await?;
+    Ok(Json(tokens))
+}
+
+/// Issues a token for the game; the response is the only time its secret is shown.
+#[utoipa::path(
+    post,
+    path = "/api/games/{game}/tokens",
+    tag = "tokens",
+    params(("game" = String, Path, description = "Name of the game")),
+    request_body = IssueTokenRequest,
+    responses(
+        (status = 201, description = "The token, with its secret", body = IssuedToken),
+        (status = 422, description = "The request failed validation or refers to data that doesn't exist", body = ErrorBody),
+    ),
+)]
+pub async fn issue_token(
+    State(state): State<AppState>,
+    Path(game): Path<String>,
+    Json(request): Json<IssueTokenRequest>,
+) -> Result<(StatusCode, Json<IssuedToken>), ApiError> {
+    let (token, secret) = state
+        .db
+        .run(move |db| {
+            db.issue_token(
+                &game,
+                request.role,
+                request.character.as_deref(),
+                request.label.as_deref(),
+            )
+        })
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(IssuedToken { token, secret })))
+}
+
+/// Revokes one of the game's tokens.
+#[utoipa::path(
+    delete,
+    path = "/api/games/{game}/tokens/{id}",
+    tag = "tokens",
+    params(
+        ("game" = String, Path, description = "Name of the game"),
+        ("id" = i64, Path, description = "Token ID"),
+    ),
+    responses(
+        (status = 204, description = "The token was revoked"),
+        (status = 404, description = "No such unrevoked token", body = ErrorBody),
+    ),
+)]
+pub async fn revoke_token(
+    State(state): State<AppState>,
+    Path((game, token_id)): Path<(String, i64)>,
+) -> Result<StatusCode, ApiError> {
+    state
+        .db
+        .run(move |db| {
+            db.get_token(token_id)?
+                .filter(|token| token.game == game)
+                .ok_or_else(|| DbError::not_found(format!("token {}", token_id)))?;
+            db.revoke_token(token_id)
+        })
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Serves the OpenAPI document describing every endpoint.
+#[utoipa::path(
+    get,
+    path = "/api/openapi.json",
+    tag = "server",
+    responses(
+        (status = 200, description = "The OpenAPI 3 document", body = Object),
+    ),
+    security(()),
+)]
+pub async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
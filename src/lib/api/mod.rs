@@ -1,6 +1,13 @@
+pub mod auth;
+pub mod config;
+pub mod error;
+pub mod events;
+pub mod extract;
 pub mod models;
+pub mod openapi;
 pub mod handlers;
 pub mod server;
+pub mod state;
 
 #[cfg(test)]
 mod tests;
@@ -1,13 +1,243 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use utoipa::{IntoParams, ToSchema};
 
-#[derive(Debug, Deserialize)]
+use crate::entities::character::{Character, Stat};
+use crate::entities::records::CharacterSort;
+use crate::entities::sessions::{LogEntry, Scene, Session};
+use crate::entities::tokens::{ApiToken, Role};
+use crate::entities::trade::{TradeItem, TradeStatus};
+use crate::systems::dice::{DicePool, RollResult};
+
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct TestRequest {
     pub data: Value,  // Accept any JSON
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct TestResponse {
     pub message: String,
     pub echo: Value,  // Echo back whatever was sent
 }
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HealthResponse {
+    pub status: String,
+    pub schema_version: u32,  // Database schema version after migrations
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ProposeTradeRequest {
+    pub from: String,  // UUID of the proposing character
+    pub to: String,    // UUID of the character receiving the offer
+    #[serde(default)]
+    pub offered: Vec<TradeItem>,
+    #[serde(default)]
+    pub requested: Vec<TradeItem>,
+    pub expires_in_seconds: Option<i64>,  // Must be positive; no deadline when omitted
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TradeListQuery {
+    pub character: String,  // UUID of either party
+    pub status: Option<TradeStatus>,
+}
+
+/// A character as exchanged over the API: its sheet plus identifying fields.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CharacterResource {
+    pub id: String,    // Character UUID
+    pub game: String,
+    #[serde(flatten)]
+    pub sheet: Character,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CharacterListQuery {
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+    pub descending: Option<bool>,
+    pub sort: Option<CharacterSort>,
+}
+
+/// An object definition with its `properties` parsed into JSON.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ObjectResource {
+    pub id: i64,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub obj_type: String,
+    pub properties: Value,  // null when the object has none
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateObjectRequest {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub obj_type: String,
+    #[serde(default)]
+    pub properties: Value,  // Must be a JSON object or null
+}
+
+/// Fields to change on an object definition; omitted fields are kept.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateObjectRequest {
+    pub name: Option<String>,
+    #[serde(rename = "type")]
+    pub obj_type: Option<String>,
+    pub properties: Option<Value>,  // Replaces all properties when present
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ObjectTypeQuery {
+    #[serde(rename = "type")]
+    pub obj_type: Option<String>,
+}
+
+/// One inventory stack with its object's `properties` parsed into JSON.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InventoryItem {
+    pub object_id: i64,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub obj_type: String,
+    pub quantity: i32,
+    pub properties: Value,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AddInventoryRequest {
+    pub object_id: i64,
+    #[serde(default = "default_quantity")]
+    pub quantity: i32,
+}
+
+fn default_quantity() -> i32 {
+    1
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SetQuantityRequest {
+    pub quantity: i32,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct TransferRequest {
+    pub to: String,  // UUID of the receiving character
+    #[serde(default = "default_quantity")]
+    pub quantity: i32,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct RollRequest {
+    pub game: String,
+    pub character: String,  // UUID of the rolling character
+    pub pool: DicePool,
+    pub label: Option<String>,  // Free text such as "Pick the lock", shown with the result
+}
+
+/// A stat's contribution to a rolled pool.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct StatDice {
+    pub stat: Stat,
+    pub rating: u32,
+}
+
+/// A bonus or penalty applied to a rolled pool.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RollModifier {
+    pub source: String,  // "request" or "encumbrance"
+    pub dice: i32,
+}
+
+/// Everything about a roll; stored as the roll log's `breakdown` JSON.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RollBreakdown {
+    pub game: String,
+    pub character: String,  // UUID
+    pub character_name: String,
+    pub label: Option<String>,
+    pub stats: Vec<StatDice>,
+    pub modifiers: Vec<RollModifier>,
+    pub dice: u32,  // Pool size after modifiers
+    #[serde(flatten)]
+    pub result: RollResult,
+    pub success: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RollResponse {
+    pub id: i64,
+    pub seed: u64,  // Reseeding a StdRng with this reproduces the faces
+    pub rolled_at: i64,
+    pub session_id: Option<i64>,  // The session open when the roll was made
+    pub scene_id: Option<i64>,    // The scene open when the roll was made
+    #[serde(flatten)]
+    pub breakdown: RollBreakdown,
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EventStreamQuery {
+    pub since: Option<u64>,  // Last sequence number the client saw
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FoundryQuery {
+    pub template: Option<String>,  // Template ID; the World of Darkness template when omitted
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct IssueTokenRequest {
+    pub role: Role,
+    pub character: Option<String>,  // UUID of the character a player token controls
+    pub label: Option<String>,  // Who the token is for, e.g. the player's name
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct IssuedToken {
+    #[serde(flatten)]
+    pub token: ApiToken,
+    pub secret: String,  // Shown only once; send as `Authorization: Bearer <secret>`
+}
+
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SessionListQuery {
+    pub limit: Option<u32>,  // 20 when omitted, at most 100
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct StartSessionRequest {
+    pub title: Option<String>,
+    pub seed: Option<u64>,  // Random when omitted; must be below 2^53
+    #[serde(default)]
+    pub participants: Vec<String>,  // UUIDs of the characters present from the start
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub struct StartSceneRequest {
+    pub title: Option<String>,
+    pub seed: Option<u64>,  // Derived from the session's seed when omitted; must be below 2^53
+    #[serde(default)]
+    pub participants: Vec<String>,  // UUIDs of the characters in the scene; they join the session too
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AwardXpRequest {
+    pub amount: i32,
+    pub reason: Option<String>,  // What the XP is for, shown in recaps
+}
+
+/// Everything that happened in a session, for "last session" recaps.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SessionRecap {
+    pub session: Session,
+    pub scenes: Vec<Scene>,
+    pub rolls: Vec<RollResponse>,  // Oldest first
+    pub log: Vec<LogEntry>,        // Damage and XP, oldest first
+}
@@ -0,0 +1,147 @@
+//! OpenAPI 3 description of the API, served at `/api/openapi.json`.
+//!
+//! Operations come from the `#[utoipa::path]` attributes on the handlers and
+//! schemas from the `ToSchema` types they use. Every route added to
+//! `server::api_router` must be listed in `ApiDoc` too; the API tests check
+//! each response against this document, so a handler whose types drift from
+//! it fails them.
+
+use utoipa::openapi::path::Operation;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::openapi::{ContentBuilder, OpenApi as OpenApiDocument, Ref, ResponseBuilder};
+use utoipa::{Modify, OpenApi};
+
+use super::error::ErrorBody;
+use super::handlers;
+use crate::entities::records::CharacterSort;
+
+/// Name of the bearer token security scheme (repeated in `ApiDoc`'s `security`).
+pub const SECURITY_SCHEME: &str = "token";
+
+/// The API's OpenAPI document.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "ttdigirpg API",
+        description = "Characters, inventories, trades, dice, sessions and live events for FoundryVTT modules."
+    ),
+    paths(
+        handlers::test_echo,
+        handlers::health,
+        handlers::openapi_json,
+        handlers::list_characters,
+        handlers::create_character,
+        handlers::get_character,
+        handlers::replace_character,
+        handlers::patch_character,
+        handlers::delete_character,
+        handlers::export_foundry_actor,
+        handlers::reimport_foundry_actor,
+        handlers::import_foundry_actor,
+        handlers::list_inventory,
+        handlers::add_inventory,
+        handlers::set_inventory_quantity,
+        handlers::remove_inventory,
+        handlers::transfer_inventory,
+        handlers::list_objects,
+        handlers::create_object,
+        handlers::get_object,
+        handlers::update_object,
+        handlers::delete_object,
+        handlers::list_trades,
+        handlers::propose_trade,
+        handlers::get_trade,
+        handlers::accept_trade,
+        handlers::reject_trade,
+        handlers::event_stream,
+        handlers::publish_event,
+        handlers::roll,
+        handlers::get_roll,
+        handlers::list_sessions,
+        handlers::start_session,
+        handlers::get_session,
+        handlers::end_session,
+        handlers::session_recap,
+        handlers::start_scene,
+        handlers::end_scene,
+        handlers::award_xp,
+        handlers::list_tokens,
+        handlers::issue_token,
+        handlers::revoke_token,
+    ),
+    // Query parameter types aren't collected automatically
+    components(schemas(ErrorBody, CharacterSort)),
+    modifiers(&TokenAuth),
+    security(("token" = []))
+)]
+pub struct ApiDoc;
+
+/// Declares the bearer token scheme and the errors every protected route can return.
+struct TokenAuth;
+
+impl Modify for TokenAuth {
+    fn modify(&self, openapi: &mut OpenApiDocument) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            SECURITY_SCHEME,
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some(
+                        "A per-game API token; WebSocket clients may pass it as `?access_token=`",
+                    ))
+                    .build(),
+            ),
+        );
+
+        for item in openapi.paths.paths.values_mut() {
+            let operations = [
+                &mut item.get,
+                &mut item.put,
+                &mut item.post,
+                &mut item.delete,
+                &mut item.patch,
+            ];
+            for operation in operations.into_iter().flatten() {
+                add_common_errors(operation);
+            }
+        }
+    }
+}
+
+/// Adds the errors any operation can return: 400 for an unreadable request,
+/// 413 and 415 for a bad body, 401 and 403 where a token is needed, and 500.
+///
+/// Only public operations set their own (empty) `security`; the rest inherit
+/// the document's token requirement.
+fn add_common_errors(operation: &mut Operation) {
+    let mut errors = vec![
+        ("400", "The request couldn't be read (bad JSON, path or query string)"),
+        ("500", "The server failed; details are only logged"),
+    ];
+    if operation.request_body.is_some() {
+        errors.push(("413", "The request body is too large"));
+        errors.push(("415", "The request body isn't `application/json`"));
+    }
+    if operation.security.is_none() {
+        errors.push(("401", "The API token is missing, invalid or revoked"));
+        errors.push(("403", "The token's role or game doesn't allow this"));
+    }
+
+    for (status, description) in errors {
+        let response = ResponseBuilder::new()
+            .description(description)
+            .content(
+                "application/json",
+                ContentBuilder::new()
+                    .schema(Some(Ref::from_schema_name("ErrorBody")))
+                    .build(),
+            )
+            .build();
+        operation
+            .responses
+            .responses
+            .entry(status.to_string())
+            .or_insert(response.into());
+    }
+}
@@ -1,33 +1,180 @@
-use axum::{routing::post, Router, http::{Method, header}};
-use tower_http::cors::CorsLayer;
+use axum::{middleware, routing::{delete, get, post, put, MethodRouter}, Router, http::Method};
 
+use super::auth;
+use super::config::{build_router, ServerConfig};
 use super::handlers;
+use super::state::AppState;
+use crate::entities::pool::{DbPool, DEFAULT_POOL_SIZE};
+use crate::systems::foundry::FoundryTemplates;
 
-/// Runs the API server for testing purposes
+/// HTTP methods browser clients may use unless the server config lists others.
+pub const ALLOWED_METHODS: [Method; 5] = [
+    Method::GET,
+    Method::POST,
+    Method::PUT,
+    Method::PATCH,
+    Method::DELETE,
+];
+
+/// Every API path with the handlers for each of its methods.
+///
+/// `api_router` mounts these; the OpenAPI test reads the same table to check
+/// that nothing routed goes undocumented.
+pub(crate) fn routes() -> Vec<(&'static str, MethodRouter<AppState>)> {
+    vec![
+        ("/api/test/echo", post(handlers::test_echo)),
+        ("/api/health", get(handlers::health)),
+        ("/api/openapi.json", get(handlers::openapi_json)),
+        (
+            "/api/games/:game/characters",
+            get(handlers::list_characters).post(handlers::create_character),
+        ),
+        (
+            "/api/games/:game/characters/:id",
+            get(handlers::get_character)
+                .put(handlers::replace_character)
+                .patch(handlers::patch_character)
+                .delete(handlers::delete_character),
+        ),
+        (
+            "/api/games/:game/characters/:id/foundry",
+            get(handlers::export_foundry_actor).put(handlers::reimport_foundry_actor),
+        ),
+        ("/api/games/:game/foundry", post(handlers::import_foundry_actor)),
+        (
+            "/api/games/:game/characters/:id/inventory",
+            get(handlers::list_inventory).post(handlers::add_inventory),
+        ),
+        (
+            "/api/games/:game/characters/:id/inventory/:object_id",
+            put(handlers::set_inventory_quantity).delete(handlers::remove_inventory),
+        ),
+        (
+            "/api/games/:game/characters/:id/inventory/:object_id/transfer",
+            post(handlers::transfer_inventory),
+        ),
+        (
+            "/api/objects",
+            get(handlers::list_objects).post(handlers::create_object),
+        ),
+        (
+            "/api/objects/:id",
+            get(handlers::get_object)
+                .patch(handlers::update_object)
+                .delete(handlers::delete_object),
+        ),
+        (
+            "/api/games/:game/trades",
+            get(handlers::list_trades).post(handlers::propose_trade),
+        ),
+        ("/api/games/:game/trades/:id", get(handlers::get_trade)),
+        ("/api/games/:game/trades/:id/accept", post(handlers::accept_trade)),
+        ("/api/games/:game/trades/:id/reject", post(handlers::reject_trade)),
+        (
+            "/api/games/:game/events",
+            get(handlers::event_stream).post(handlers::publish_event),
+        ),
+        ("/api/roll", post(handlers::roll)),
+        ("/api/rolls/:id", get(handlers::get_roll)),
+        (
+            "/api/games/:game/sessions",
+            get(handlers::list_sessions).post(handlers::start_session),
+        ),
+        ("/api/games/:game/sessions/:id", get(handlers::get_session)),
+        ("/api/games/:game/sessions/:id/end", post(handlers::end_session)),
+        ("/api/games/:game/sessions/:id/recap", get(handlers::session_recap)),
+        ("/api/games/:game/sessions/:id/scenes", post(handlers::start_scene)),
+        (
+            "/api/games/:game/sessions/:id/scenes/:scene_id/end",
+            post(handlers::end_scene),
+        ),
+        ("/api/games/:game/characters/:id/xp", post(handlers::award_xp)),
+        (
+            "/api/games/:game/tokens",
+            get(handlers::list_tokens).post(handlers::issue_token),
+        ),
+        ("/api/games/:game/tokens/:id", delete(handlers::revoke_token)),
+    ]
+}
+
+/// Builds the API routes around the shared handler state.
+///
+/// Every route goes through `auth::authorize`. CORS is left to the caller;
+/// `config::build_router` adds the configured rules.
+pub fn api_router(state: AppState) -> Router {
+    routes()
+        .into_iter()
+        .fold(Router::new(), |router, (path, methods)| router.route(path, methods))
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::authorize))
+        .fallback(handlers::no_such_route)
+        .method_not_allowed_fallback(handlers::method_not_allowed)
+        .with_state(state)
+}
+
+/// Runs the API server until it is stopped.
+///
+/// # Arguments
+/// * `config` - Where to listen, which browser origins to allow and which database to use
 ///
 /// # Returns
 /// * `Result<(), Box<dyn std::error::Error>>` - Ok if server runs successfully, Err otherwise
-pub async fn run_api_server() -> Result<(), Box<dyn std::error::Error>> {
-    // Set up CORS for localhost
-    let cors = CorsLayer::new()
-        .allow_origin([
-            "http://localhost:30000".parse()?,
-            "http://127.0.0.1:30000".parse()?,
-        ])
-        .allow_methods([Method::POST])
-        .allow_headers([header::CONTENT_TYPE]);
-
-    // Build the router with our test endpoint
-    let app = Router::new()
-        .route("/api/test/echo", post(handlers::test_echo))
-        .layer(cors);
-
-    // Bind to localhost:8080
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:8080").await?;
-
-    println!("Test API server running on http://127.0.0.1:8080");
+pub async fn run_api_server(config: &ServerConfig) -> Result<(), Box<dyn std::error::Error>> {
+    // Make sure the database directory exists before opening the pool
+    if let Some(parent) = std::path::Path::new(&config.db_path).parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+    let db = DbPool::open(&config.db_path, DEFAULT_POOL_SIZE)?;
+    let foundry_templates = FoundryTemplates::load_default()?;
+
+    let state = AppState::new(db).with_foundry_templates(foundry_templates);
+    let app = build_router(config, state);
+
+    let listener = tokio::net::TcpListener::bind(config.socket_addr()).await?;
+
+    println!("API server running on http://{}", listener.local_addr()?);
+    println!("Database: {}", config.db_path);
+    let origins: Vec<&str> = config
+        .allowed_origins
+        .iter()
+        .filter_map(|origin| origin.to_str().ok())
+        .collect();
+    println!("Allowed origins: {}", origins.join(", "));
+    if !config.require_auth {
+        println!("WARNING: API tokens are not required; anyone who can connect has full access");
+    }
     println!("Endpoints:");
     println!("  POST /api/test/echo - Echo back any JSON data");
+    println!("  GET  /api/health    - Server and database status");
+    println!("  GET  /api/openapi.json - OpenAPI 3 description of every endpoint");
+    println!("  GET|POST /api/games/:game/characters - List or create characters");
+    println!("  GET|PUT|PATCH|DELETE /api/games/:game/characters/:id - Read, replace, update or delete a character");
+    println!("  GET|PUT /api/games/:game/characters/:id/foundry - Export or re-import a Foundry actor (?template=)");
+    println!("  POST /api/games/:game/foundry - Import a Foundry actor as a new character (?template=)");
+    println!("  GET|POST /api/games/:game/characters/:id/inventory - List or add inventory (?type= filters)");
+    println!("  PUT|DELETE /api/games/:game/characters/:id/inventory/:object_id - Set quantity or remove");
+    println!("  POST /api/games/:game/characters/:id/inventory/:object_id/transfer - Give to another character");
+    println!("  GET|POST /api/objects - List (?type= filters) or create object definitions");
+    println!("  GET|PATCH|DELETE /api/objects/:id - Read, edit or delete an object definition");
+    println!("  GET  /api/games/:game/trades?character=<uuid> - List a character's trade offers");
+    println!("  POST /api/games/:game/trades - Propose a trade");
+    println!("  GET  /api/games/:game/trades/:id - Show a trade offer");
+    println!("  POST /api/games/:game/trades/:id/accept - Accept a trade");
+    println!("  POST /api/games/:game/trades/:id/reject - Reject a trade");
+    println!("  GET  /api/games/:game/events?since=<seq> - WebSocket stream of live game events");
+    println!("  POST /api/games/:game/events - Publish damage or turn events to a game");
+    println!("  POST /api/roll - Roll a character's dice pool and log the result");
+    println!("  GET  /api/rolls/:id - Show a logged roll");
+    println!("  GET|POST /api/games/:game/sessions - List sessions, or start one (GM only)");
+    println!("  GET  /api/games/:game/sessions/:id - Show a session and its participants");
+    println!("  GET  /api/games/:game/sessions/:id/recap - Scenes, rolls, damage and XP of a session");
+    println!("  POST /api/games/:game/sessions/:id/end - End a session (GM only)");
+    println!("  POST /api/games/:game/sessions/:id/scenes - Start a scene (GM only)");
+    println!("  POST /api/games/:game/sessions/:id/scenes/:scene_id/end - End a scene (GM only)");
+    println!("  POST /api/games/:game/characters/:id/xp - Award a character XP (GM only)");
+    println!("  GET|POST /api/games/:game/tokens - List or issue API tokens (GM only)");
+    println!("  DELETE /api/games/:game/tokens/:id - Revoke an API token (GM only)");
     println!("\nPress Ctrl+C to stop the server");
 
     // Run the server
@@ -0,0 +1,46 @@
+use std::sync::Arc;
+
+use super::events::EventHub;
+use crate::entities::pool::DbPool;
+use crate::systems::foundry::FoundryTemplates;
+
+/// Shared state handed to every API handler.
+///
+/// Cloning is cheap: every field is a handle to shared data.
+#[derive(Clone)]
+pub struct AppState {
+    /// Pooled access to the game database
+    pub db: DbPool,
+    /// Live event channels for WebSocket clients
+    pub events: EventHub,
+    /// Templates for Foundry actor import and export
+    pub foundry: Arc<FoundryTemplates>,
+    /// Whether requests need an API token (see `auth`)
+    pub auth_required: bool,
+}
+
+impl AppState {
+    /// Creates the handler state around an open database pool, with no
+    /// events published yet and only the built-in Foundry template.
+    /// Authentication is required until `with_auth_required(false)`.
+    pub fn new(db: DbPool) -> Self {
+        AppState {
+            db,
+            events: EventHub::new(),
+            foundry: Arc::new(FoundryTemplates::builtin()),
+            auth_required: true,
+        }
+    }
+
+    /// Replaces the built-in Foundry template set.
+    pub fn with_foundry_templates(mut self, templates: FoundryTemplates) -> Self {
+        self.foundry = Arc::new(templates);
+        self
+    }
+
+    /// Turns API token checks on or off.
+    pub fn with_auth_required(mut self, required: bool) -> Self {
+        self.auth_required = required;
+        self
+    }
+}
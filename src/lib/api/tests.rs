@@ -1,28 +1,176 @@
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
     use super::super::*;
     use axum::{
         body::Body,
-        http::{Request, StatusCode, Method, header},
+        http::{Request, StatusCode},
         Router,
     };
     use serde_json::json;
     use tower::util::ServiceExt;
-    use tower_http::cors::CorsLayer;
+    use utoipa::OpenApi;
 
-    /// Helper function to create a test router
+    /// Helper function to create a test router backed by an in-memory database
     fn create_test_router() -> Router {
-        let cors = CorsLayer::new()
-            .allow_origin([
-                "http://localhost:30000".parse().unwrap(),
-                "http://127.0.0.1:30000".parse().unwrap(),
-            ])
-            .allow_methods([Method::POST])
-            .allow_headers([header::CONTENT_TYPE]);
+        create_test_app().0
+    }
+
+    /// Like `create_test_router`, but also returns the pool so tests can seed data
+    fn create_test_app() -> (Router, crate::entities::pool::DbPool) {
+        let db = crate::entities::pool::DbPool::in_memory().unwrap();
+        let app = config::build_router(&open_config(), state::AppState::new(db.clone()));
+        (app, db)
+    }
+
+    /// The default config without token checks, for tests that aren't about auth
+    fn open_config() -> config::ServerConfig {
+        config::ServerConfig {
+            require_auth: false,
+            ..config::ServerConfig::default()
+        }
+    }
+
+    /// Sends a request with an optional JSON body and returns the status and JSON reply
+    async fn send(
+        app: &Router,
+        method: &str,
+        uri: &str,
+        body: Option<serde_json::Value>,
+    ) -> (StatusCode, serde_json::Value) {
+        send_as(app, None, method, uri, body).await
+    }
+
+    /// Like `send`, with an optional `Authorization: Bearer` token
+    async fn send_as(
+        app: &Router,
+        token: Option<&str>,
+        method: &str,
+        uri: &str,
+        body: Option<serde_json::Value>,
+    ) -> (StatusCode, serde_json::Value) {
+        let sent = body.clone();
+        let mut builder = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            builder = builder.header("authorization", format!("Bearer {}", token));
+        }
+        let request = match body {
+            Some(body) => builder
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string())),
+            None => builder.body(Body::empty()),
+        }
+        .unwrap();
 
-        Router::new()
-            .route("/api/test/echo", axum::routing::post(handlers::test_echo))
-            .layer(cors)
+        let (status, json) = reply_of(app, request).await;
+        check_against_spec(method, uri, sent.as_ref(), status, &json);
+        (status, json)
+    }
+
+    /// Sends a raw body with the given content type, for requests `send` can't make
+    async fn send_raw(
+        app: &Router,
+        method: &str,
+        uri: &str,
+        content_type: &str,
+        body: &str,
+    ) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", content_type)
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let (status, json) = reply_of(app, request).await;
+        if documented_operation(method, uri).is_some() {
+            check_against_spec(method, uri, None, status, &json);
+        }
+        (status, json)
+    }
+
+    /// Runs a request and parses the JSON reply (`Null` if there is no body)
+    async fn reply_of(app: &Router, request: Request<Body>) -> (StatusCode, serde_json::Value) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json = if bytes.is_empty() {
+            serde_json::Value::Null
+        } else {
+            serde_json::from_slice(&bytes).unwrap()
+        };
+        (status, json)
+    }
+
+    /// The OpenAPI document as JSON
+    fn spec() -> &'static serde_json::Value {
+        static SPEC: std::sync::OnceLock<serde_json::Value> = std::sync::OnceLock::new();
+        SPEC.get_or_init(|| serde_json::to_value(openapi::ApiDoc::openapi()).unwrap())
+    }
+
+    /// The documented operation serving `method` and `uri`, if any
+    fn documented_operation(method: &str, uri: &str) -> Option<&'static serde_json::Value> {
+        let path = uri.split('?').next().unwrap();
+        let segments: Vec<&str> = path.split('/').collect();
+        spec()["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .find(|(template, _)| {
+                let parts: Vec<&str> = template.split('/').collect();
+                parts.len() == segments.len()
+                    && parts
+                        .iter()
+                        .zip(&segments)
+                        .all(|(part, segment)| part.starts_with('{') || part == segment)
+            })
+            .and_then(|(_, item)| item.get(method.to_ascii_lowercase()))
+    }
+
+    /// Panics unless `value` is valid against a schema from the OpenAPI document
+    fn assert_matches_schema(schema: &serde_json::Value, value: &serde_json::Value, what: &str) {
+        let mut root = schema.clone();
+        root["components"] = spec()["components"].clone();
+        let validator = jsonschema::validator_for(&root).unwrap();
+        let errors: Vec<String> = validator.iter_errors(value).map(|e| e.to_string()).collect();
+        assert!(
+            errors.is_empty(),
+            "{} doesn't match the OpenAPI schema: {:?}\n{}",
+            what,
+            errors,
+            value
+        );
+    }
+
+    /// Checks an exchange against the OpenAPI document, so handlers can't drift from it:
+    /// the route and status must be documented, the response must match its schema,
+    /// and so must the request body of any successful request.
+    fn check_against_spec(
+        method: &str,
+        uri: &str,
+        request_body: Option<&serde_json::Value>,
+        status: StatusCode,
+        response_body: &serde_json::Value,
+    ) {
+        let what = format!("{} {} ({})", method, uri, status);
+        let operation = documented_operation(method, uri)
+            .unwrap_or_else(|| panic!("{} is not in the OpenAPI document", what));
+        let response = operation["responses"]
+            .get(status.as_str())
+            .unwrap_or_else(|| panic!("{}: status is not documented", what));
+
+        match response["content"]["application/json"].get("schema") {
+            Some(schema) => {
+                assert_matches_schema(schema, response_body, &format!("response to {}", what))
+            }
+            None => assert!(response_body.is_null(), "{}: undocumented response body", what),
+        }
+        if let (true, Some(body)) = (status.is_success(), request_body) {
+            let schema = &operation["requestBody"]["content"]["application/json"]["schema"];
+            assert!(!schema.is_null(), "{}: request body is not documented", what);
+            assert_matches_schema(schema, body, &format!("request body of {}", what));
+        }
     }
 
     #[tokio::test]
@@ -60,4 +208,1223 @@ mod tests {
         assert_eq!(body_json["echo"]["test"], "hello");
         assert_eq!(body_json["echo"]["number"], 42);
     }
+
+    #[tokio::test]
+    async fn test_health_endpoint_reads_database() {
+        let app = create_test_router();
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("GET")
+                    .uri("/api/health")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body_json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(body_json["status"], "ok");
+        assert_eq!(
+            body_json["schema_version"],
+            crate::entities::migrations::latest_version()
+        );
+    }
+
+    async fn error_response(err: error::ApiError) -> (StatusCode, serde_json::Value) {
+        use axum::response::IntoResponse;
+
+        let response = err.into_response();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_db_errors_map_to_status_codes() {
+        use crate::entities::error::DbError;
+
+        let cases = [
+            (DbError::not_found("character Alice"), StatusCode::NOT_FOUND, "not_found"),
+            (DbError::Duplicate("name taken".to_string()), StatusCode::CONFLICT, "conflict"),
+            (
+                DbError::ForeignKeyViolation("no such object".to_string()),
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "unprocessable",
+            ),
+            (
+                DbError::invalid("not enough gold"),
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "unprocessable",
+            ),
+            (
+                DbError::Unavailable("pool closed".to_string()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal",
+            ),
+            (
+                DbError::Sqlite(rusqlite::Error::InvalidQuery),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal",
+            ),
+        ];
+
+        for (db_error, expected, code) in cases {
+            let (status, body) = error_response(db_error.into()).await;
+            assert_eq!(status, expected);
+            assert_eq!(body["code"], code);
+            assert!(body["message"].is_string());
+            assert_eq!(body["details"], json!([]));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_internal_error_hides_details() {
+        let (_, body) = error_response(error::ApiError::Internal("disk I/O error".to_string())).await;
+
+        assert_eq!(body["code"], "internal");
+        assert_eq!(body["message"], "Internal server error");
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_character_from_database_is_conflict() {
+        use crate::entities::database::Database;
+
+        let db = Database::new(":memory:").unwrap();
+        db.insert_character("Alice", "Game", None).unwrap();
+        let err = db.insert_character("Alice", "Game", None).unwrap_err();
+
+        let (status, body) = error_response(err.into()).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "conflict");
+        assert!(body["message"].as_str().unwrap().starts_with("Duplicate entry"));
+    }
+
+    #[tokio::test]
+    async fn test_trade_offer_flow() {
+        let (app, db) = create_test_app();
+        let (alice, bob, gold, sword) = db
+            .run(|db| {
+                let alice = db.insert_character("Alice", "Game", None)?;
+                let bob = db.insert_character("Bob", "Game", None)?;
+                let gold = db.insert_object("Gold Coin", "currency", None)?;
+                let sword = db.insert_object("Sword", "weapon", None)?;
+                db.add_object_to_character("Game", "Alice", gold, 50)?;
+                db.add_object_to_character("Game", "Bob", sword, 1)?;
+                Ok::<_, crate::entities::error::DbError>((alice, bob, gold, sword))
+            })
+            .await
+            .unwrap();
+
+        let (status, offer) = send(
+            &app,
+            "POST",
+            "/api/games/Game/trades",
+            Some(json!({
+                "from": alice,
+                "to": bob,
+                "offered": [{"object_id": gold, "quantity": 20}],
+                "requested": [{"object_id": sword, "quantity": 1}],
+                "expires_in_seconds": 3600
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(offer["status"], "pending");
+        let id = offer["id"].as_i64().unwrap();
+
+        let (status, listed) = send(
+            &app,
+            "GET",
+            &format!("/api/games/Game/trades?character={}&status=pending", bob),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(listed.as_array().unwrap().len(), 1);
+
+        let (status, accepted) = send(
+            &app,
+            "POST",
+            &format!("/api/games/Game/trades/{}/accept", id),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(accepted["status"], "accepted");
+
+        let (status, _) = send(
+            &app,
+            "POST",
+            &format!("/api/games/Game/trades/{}/reject", id),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, _) = send(&app, "GET", &format!("/api/games/Other/trades/{}", id), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let bob_items = db
+            .run(|db| db.get_character_objects("Game", "Bob"))
+            .await
+            .unwrap();
+        assert_eq!(bob_items.len(), 1);
+        assert_eq!(bob_items[0].object_id, gold);
+        assert_eq!(bob_items[0].quantity, 20);
+    }
+
+    #[tokio::test]
+    async fn test_trade_offering_unowned_items_is_unprocessable() {
+        let (app, db) = create_test_app();
+        let (alice, bob) = db
+            .run(|db| {
+                let alice = db.insert_character("Alice", "Game", None)?;
+                let bob = db.insert_character("Bob", "Game", None)?;
+                Ok::<_, crate::entities::error::DbError>((alice, bob))
+            })
+            .await
+            .unwrap();
+
+        let (status, body) = send(
+            &app,
+            "POST",
+            "/api/games/Game/trades",
+            Some(json!({
+                "from": alice,
+                "to": bob,
+                "offered": [{"object_id": 1, "quantity": 1}]
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "unprocessable");
+        assert!(body["message"].as_str().unwrap().starts_with("Invalid operation"));
+
+        // Deadlines must lie in the future and not overflow the clock
+        for expires_in_seconds in [json!(0), json!(-60), json!(i64::MAX)] {
+            let (status, body) = send(
+                &app,
+                "POST",
+                "/api/games/Game/trades",
+                Some(json!({"from": alice, "to": bob, "expires_in_seconds": expires_in_seconds})),
+            )
+            .await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+            assert_eq!(body["details"][0]["field"], "expires_in_seconds");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_character_crud_round_trip() {
+        let app = create_test_router();
+
+        let (status, created) = send(
+            &app,
+            "POST",
+            "/api/games/Noir/characters",
+            Some(json!({"name": "Sam Spade", "streetwise": 4})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(created["game"], "Noir");
+        assert_eq!(created["streetwise"], 4);
+        assert_eq!(created["physical"], 1);
+        let uri = format!("/api/games/Noir/characters/{}", created["id"].as_str().unwrap());
+
+        let (status, fetched) = send(&app, "GET", &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(fetched, created);
+
+        let (status, patched) = send(&app, "PATCH", &uri, Some(json!({"brawl": 3}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(patched["brawl"], 3);
+        assert_eq!(patched["streetwise"], 4);
+
+        let (status, replaced) = send(
+            &app,
+            "PUT",
+            &uri,
+            Some(json!({"name": "Samuel Spade", "mental": 3})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(replaced["name"], "Samuel Spade");
+        assert_eq!(replaced["brawl"], 1, "PUT resets omitted stats");
+
+        let (status, page) = send(&app, "GET", "/api/games/Noir/characters?limit=10", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(page["total"], 1);
+        assert_eq!(page["items"][0]["name"], "Samuel Spade");
+
+        let (status, _) = send(&app, "GET", &uri.replace("Noir", "Western"), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = send(&app, "DELETE", &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&app, "GET", &uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_character_validation_errors_are_structured() {
+        let app = create_test_router();
+
+        let (status, body) = send(
+            &app,
+            "POST",
+            "/api/games/Noir/characters",
+            Some(json!({"name": "", "physical": 0})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "validation_failed");
+        assert_eq!(
+            body["details"],
+            json!([
+                {"field": "name", "message": "must not be blank"},
+                {"field": "physical", "message": "must be between 1 and 10"}
+            ])
+        );
+
+        let (_, created) = send(
+            &app,
+            "POST",
+            "/api/games/Noir/characters",
+            Some(json!({"name": "Marlowe"})),
+        )
+        .await;
+        let uri = format!("/api/games/Noir/characters/{}", created["id"].as_str().unwrap());
+
+        let (status, body) = send(&app, "PATCH", &uri, Some(json!({"charisma": 5}))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["details"][0]["field"], "charisma");
+
+        // Creating and replacing reject the same typos instead of dropping them
+        let (status, body) = send(
+            &app,
+            "POST",
+            "/api/games/Noir/characters",
+            Some(json!({"name": "Spade", "charisma": 5})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["details"], json!([{"field": "charisma", "message": "unknown field"}]));
+
+        let (status, body) =
+            send(&app, "PUT", &uri, Some(json!({"name": "Marlowe", "charisma": 5}))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["details"][0]["field"], "charisma");
+
+        // ...but what a GET returns, id and game included, can be PUT back as is
+        let (_, mut fetched) = send(&app, "GET", &uri, None).await;
+        fetched["social"] = json!(3);
+        let (status, body) = send(&app, "PUT", &uri, Some(fetched.clone())).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, fetched);
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_requests_get_the_error_envelope() {
+        let app = create_test_router();
+        let characters = "/api/games/Noir/characters";
+
+        let (status, body) =
+            send_raw(&app, "POST", characters, "application/json", r#"{"name": "#).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "bad_request");
+
+        let (status, body) = send_raw(&app, "POST", characters, "text/plain", "{}").await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(body["code"], "unsupported_media_type");
+
+        // Well-formed JSON of the wrong shape names the field
+        let (status, body) = send_raw(
+            &app,
+            "POST",
+            characters,
+            "application/json",
+            r#"{"name": "Ash", "physical": "strong"}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "validation_failed");
+        assert_eq!(body["details"][0]["field"], "physical");
+
+        let roll = r#"{"game": "Noir", "character": "x", "pool": {"stats": ["wisdom"]}}"#;
+        let (status, body) = send_raw(&app, "POST", "/api/roll", "application/json", roll).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["details"][0]["field"], "pool.stats[0]");
+
+        // Bad path segments and query strings
+        let (status, body) = send(&app, "GET", "/api/objects/sword", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "bad_request");
+        let (status, body) = send(&app, "GET", "/api/games/Noir/characters?sort=age", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "bad_request");
+
+        // Unknown routes and methods
+        let (status, body) = send_raw(&app, "GET", "/api/spells", "application/json", "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "not_found");
+        let (status, body) = send_raw(&app, "DELETE", "/api/health", "application/json", "").await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(body["code"], "method_not_allowed");
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_character_name_is_conflict() {
+        let app = create_test_router();
+        let body = json!({"name": "Marlowe"});
+
+        let (status, _) = send(&app, "POST", "/api/games/Noir/characters", Some(body.clone())).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (status, _) = send(&app, "POST", "/api/games/Noir/characters", Some(body)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_object_definitions_round_trip() {
+        let app = create_test_router();
+
+        let (status, sword) = send(
+            &app,
+            "POST",
+            "/api/objects",
+            Some(json!({"name": "Sword", "type": "weapon", "properties": {"damage": 10}})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(sword["properties"], json!({"damage": 10}));
+        send(
+            &app,
+            "POST",
+            "/api/objects",
+            Some(json!({"name": "Potion", "type": "consumable"})),
+        )
+        .await;
+
+        let (_, weapons) = send(&app, "GET", "/api/objects?type=weapon", None).await;
+        assert_eq!(weapons.as_array().unwrap().len(), 1);
+        assert_eq!(weapons[0]["name"], "Sword");
+
+        let uri = format!("/api/objects/{}", sword["id"]);
+        let (status, edited) = send(&app, "PATCH", &uri, Some(json!({"name": "Longsword"}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(edited["name"], "Longsword");
+        assert_eq!(edited["properties"]["damage"], 10);
+
+        let (status, body) = send(&app, "PATCH", &uri, Some(json!({"properties": [1, 2]}))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["details"][0]["field"], "properties");
+
+        let (status, _) = send(&app, "DELETE", &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&app, "GET", &uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_inventory_endpoints() {
+        let app = create_test_router();
+        let (_, alice) = send(&app, "POST", "/api/games/G/characters", Some(json!({"name": "Alice"}))).await;
+        let (_, bob) = send(&app, "POST", "/api/games/G/characters", Some(json!({"name": "Bob"}))).await;
+        let (_, arrow) = send(
+            &app,
+            "POST",
+            "/api/objects",
+            Some(json!({"name": "Arrow", "type": "ammo", "properties": {"weight": 0.05}})),
+        )
+        .await;
+        let (_, bow) = send(&app, "POST", "/api/objects", Some(json!({"name": "Bow", "type": "weapon"}))).await;
+        let inventory = format!("/api/games/G/characters/{}/inventory", alice["id"].as_str().unwrap());
+
+        let (status, stack) = send(
+            &app,
+            "POST",
+            &inventory,
+            Some(json!({"object_id": arrow["id"], "quantity": 20})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(stack["quantity"], 20);
+        assert_eq!(stack["properties"]["weight"], 0.05);
+        send(&app, "POST", &inventory, Some(json!({"object_id": bow["id"]}))).await;
+
+        let (_, weapons) = send(&app, "GET", &format!("{}?type=weapon", inventory), None).await;
+        assert_eq!(weapons.as_array().unwrap().len(), 1);
+        assert_eq!(weapons[0]["name"], "Bow");
+
+        let arrows = format!("{}/{}", inventory, arrow["id"]);
+        let (status, stack) = send(&app, "PUT", &arrows, Some(json!({"quantity": 12}))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(stack["quantity"], 12);
+
+        let (status, _) = send(&app, "PUT", &arrows, Some(json!({"quantity": 0}))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, stacks) = send(
+            &app,
+            "POST",
+            &format!("{}/transfer", arrows),
+            Some(json!({"to": bob["id"], "quantity": 5})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let quantities: Vec<_> = stacks
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|stack| (stack["name"].clone(), stack["quantity"].clone()))
+            .collect();
+        assert_eq!(quantities, vec![(json!("Arrow"), json!(7)), (json!("Arrow"), json!(5))]);
+
+        let (status, _) = send(
+            &app,
+            "POST",
+            &format!("{}/transfer", arrows),
+            Some(json!({"to": bob["id"], "quantity": 50})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        // Giving away the whole stack leaves the giver with an empty one
+        let (status, stacks) = send(
+            &app,
+            "POST",
+            &format!("{}/transfer", arrows),
+            Some(json!({"to": bob["id"], "quantity": 7})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(stacks[0]["quantity"], 0);
+        assert_eq!(stacks[1]["quantity"], 12);
+        send(&app, "POST", &inventory, Some(json!({"object_id": arrow["id"], "quantity": 1}))).await;
+
+        let (status, _) = send(&app, "DELETE", &arrows, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&app, "DELETE", &arrows, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_roll_returns_and_stores_full_breakdown() {
+        use crate::systems::dice::DicePool;
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let app = create_test_router();
+        let (_, porter) = send(
+            &app,
+            "POST",
+            "/api/games/G/characters",
+            Some(json!({"name": "Porter", "physical": 2, "athletics": 3})),
+        )
+        .await;
+        let (_, anvil) = send(
+            &app,
+            "POST",
+            "/api/objects",
+            Some(json!({"name": "Anvil", "type": "gear", "properties": {"weight": 55}})),
+        )
+        .await;
+        let inventory = format!("/api/games/G/characters/{}/inventory", porter["id"].as_str().unwrap());
+        send(&app, "POST", &inventory, Some(json!({"object_id": anvil["id"]}))).await;
+
+        let (status, roll) = send(
+            &app,
+            "POST",
+            "/api/roll",
+            Some(json!({
+                "game": "G",
+                "character": porter["id"],
+                "pool": {"stats": ["physical", "athletics"], "modifier": 1, "difficulty": 7},
+                "label": "Climb the wall"
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(roll["character_name"], "Porter");
+        assert_eq!(roll["label"], "Climb the wall");
+        assert_eq!(roll["stats"], json!([{"stat": "physical", "rating": 2}, {"stat": "athletics", "rating": 3}]));
+        assert_eq!(
+            roll["modifiers"],
+            json!([{"source": "request", "dice": 1}, {"source": "encumbrance", "dice": -1}])
+        );
+        assert_eq!(roll["dice"], 5);
+        assert_eq!(roll["difficulty"], 7);
+        assert_eq!(roll["faces"].as_array().unwrap().len(), 5);
+
+        // The seed reproduces the faces exactly
+        let mut sheet = crate::entities::character::Character::new("Porter".to_string());
+        sheet.physical = 2;
+        sheet.athletics = 3;
+        let replay = DicePool::new(&[]).with_modifier(5).with_difficulty(7).roll(
+            &sheet,
+            &mut StdRng::seed_from_u64(roll["seed"].as_u64().unwrap()),
+        );
+        assert_eq!(roll["faces"], json!(replay.faces));
+        assert_eq!(roll["successes"], replay.successes);
+        assert_eq!(roll["botch"], replay.botch);
+
+        let (status, stored) = send(&app, "GET", &format!("/api/rolls/{}", roll["id"]), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(stored, roll);
+    }
+
+    #[tokio::test]
+    async fn test_roll_rejects_bad_requests() {
+        let app = create_test_router();
+        let (_, alice) = send(&app, "POST", "/api/games/G/characters", Some(json!({"name": "Alice"}))).await;
+
+        let (status, body) = send(
+            &app,
+            "POST",
+            "/api/roll",
+            Some(json!({"game": "G", "character": alice["id"], "pool": {"stats": ["social"], "difficulty": 11}})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["details"][0]["field"], "pool.difficulty");
+
+        // Huge pools are refused before any dice are rolled
+        let (status, body) = send(
+            &app,
+            "POST",
+            "/api/roll",
+            Some(json!({"game": "G", "character": alice["id"], "pool": {"stats": ["social"], "modifier": i32::MAX}})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["details"][0]["field"], "pool.modifier");
+        let (status, body) = send(
+            &app,
+            "POST",
+            "/api/roll",
+            Some(json!({"game": "G", "character": alice["id"], "pool": {"stats": vec!["social"; 5], "modifier": i32::MIN}})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["details"][0]["field"], "pool.modifier");
+        assert_eq!(body["details"][1]["field"], "pool.stats");
+
+        // Stats and modifier may each be in range yet add up to too many dice
+        let (_, titan) = send(
+            &app,
+            "POST",
+            "/api/games/G/characters",
+            Some(json!({"name": "Titan", "physical": 10, "social": 10, "mental": 10, "brawl": 10})),
+        )
+        .await;
+        let (status, body) = send(
+            &app,
+            "POST",
+            "/api/roll",
+            Some(json!({"game": "G", "character": titan["id"], "pool": {"stats": ["physical", "social", "mental", "brawl"], "modifier": 20}})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["details"][0]["field"], "pool");
+
+        let (status, _) = send(
+            &app,
+            "POST",
+            "/api/roll",
+            Some(json!({"game": "Other", "character": alice["id"], "pool": {"stats": ["social"]}})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = send(&app, "GET", "/api/rolls/999", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_sessions_tag_rolls_damage_and_xp() {
+        let app = create_test_router();
+        let (_, mara) = send(&app, "POST", "/api/games/G/characters", Some(json!({"name": "Mara"}))).await;
+        let (_, vic) = send(&app, "POST", "/api/games/G/characters", Some(json!({"name": "Vic"}))).await;
+        let roll = json!({"game": "G", "character": mara["id"], "pool": {"stats": ["physical"]}});
+
+        // Rolls outside a session aren't tagged
+        let (_, early) = send(&app, "POST", "/api/roll", Some(roll.clone())).await;
+        assert_eq!(early["session_id"], json!(null));
+
+        let (status, body) = send(&app, "POST", "/api/games/G/sessions", Some(json!({"seed": 1u64 << 53}))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["details"][0]["field"], "seed");
+
+        let (status, session) = send(
+            &app,
+            "POST",
+            "/api/games/G/sessions",
+            Some(json!({"title": "Night one", "seed": 42, "participants": [mara["id"]]})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(session["seed"], 42);
+        let (status, _) = send(&app, "POST", "/api/games/G/sessions", Some(json!({}))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let base = format!("/api/games/G/sessions/{}", session["id"]);
+        let (status, scene) = send(&app, "POST", &format!("{}/scenes", base), Some(json!({"title": "Docks"}))).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let (_, tagged) = send(&app, "POST", "/api/roll", Some(roll)).await;
+        assert_eq!(tagged["session_id"], session["id"]);
+        assert_eq!(tagged["scene_id"], scene["id"]);
+        let (status, _) = send(
+            &app,
+            "POST",
+            "/api/games/G/events",
+            Some(json!({"type": "damage_applied", "character": vic["id"], "amount": 2, "source": "Knife"})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+
+        let (status, _) = send(&app, "POST", &format!("{}/scenes/{}/end", base, scene["id"]), None).await;
+        assert_eq!(status, StatusCode::OK);
+        let xp = format!("/api/games/G/characters/{}/xp", mara["id"].as_str().unwrap());
+        let (status, award) = send(&app, "POST", &xp, Some(json!({"amount": 3, "reason": "Found the ledger"}))).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(award["scene_id"], json!(null));
+
+        let (status, ended) = send(&app, "POST", &format!("{}/end", base), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(ended["ended_at"].is_i64());
+        assert_eq!(ended["participants"], json!([mara["id"], vic["id"]]));
+
+        let (status, recap) = send(&app, "GET", &format!("{}/recap", base), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(recap["scenes"][0]["title"], "Docks");
+        assert_eq!(recap["scenes"][0]["participants"], json!([mara["id"], vic["id"]]));
+        assert_eq!(recap["rolls"], json!([tagged]));
+        let log: Vec<_> = recap["log"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| (entry["kind"].clone(), entry["amount"].clone()))
+            .collect();
+        assert_eq!(log, vec![(json!("damage"), json!(2)), (json!("xp"), json!(3))]);
+
+        let (_, sessions) = send(&app, "GET", "/api/games/G/sessions", None).await;
+        assert_eq!(sessions.as_array().unwrap().len(), 1);
+        let (status, _) = send(&app, "GET", &format!("/api/games/Other/sessions/{}", session["id"]), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_recaps_keep_deleted_characters_history() {
+        let app = create_test_router();
+        let (_, vic) = send(&app, "POST", "/api/games/G/characters", Some(json!({"name": "Vic"}))).await;
+        let (_, session) = send(&app, "POST", "/api/games/G/sessions", Some(json!({"participants": [vic["id"]]}))).await;
+        let (_, rolled) = send(
+            &app,
+            "POST",
+            "/api/roll",
+            Some(json!({"game": "G", "character": vic["id"], "pool": {"stats": ["physical"]}})),
+        )
+        .await;
+        send(
+            &app,
+            "POST",
+            "/api/games/G/events",
+            Some(json!({"type": "damage_applied", "character": vic["id"], "amount": 4})),
+        )
+        .await;
+        let xp = format!("/api/games/G/characters/{}/xp", vic["id"].as_str().unwrap());
+        send(&app, "POST", &xp, Some(json!({"amount": 1}))).await;
+
+        let sheet = format!("/api/games/G/characters/{}", vic["id"].as_str().unwrap());
+        let (status, _) = send(&app, "DELETE", &sheet, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let (status, recap) = send(&app, "GET", &format!("/api/games/G/sessions/{}/recap", session["id"]), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(recap["rolls"], json!([rolled]));
+        let log: Vec<_> = recap["log"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| (entry["character_uuid"].clone(), entry["kind"].clone(), entry["amount"].clone()))
+            .collect();
+        assert_eq!(
+            log,
+            vec![(vic["id"].clone(), json!("damage"), json!(4)), (vic["id"].clone(), json!("xp"), json!(1))]
+        );
+    }
+
+    /// Serves the API on a random local port; returns the address and the router for REST calls
+    async fn spawn_server() -> (std::net::SocketAddr, Router) {
+        use std::future::IntoFuture;
+
+        let mut state = state::AppState::new(crate::entities::pool::DbPool::in_memory().unwrap());
+        state.events = events::EventHub::new()
+            .with_heartbeat_interval(std::time::Duration::from_millis(100));
+        let app = config::build_router(&open_config(), state);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(axum::serve(listener, app.clone()).into_future());
+        (addr, app)
+    }
+
+    type EventSocket = tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >;
+
+    /// Reads the next JSON text message, skipping pings
+    async fn next_json(socket: &mut EventSocket) -> serde_json::Value {
+        use futures_util::StreamExt;
+        use tokio_tungstenite::tungstenite::Message;
+
+        loop {
+            let message = tokio::time::timeout(std::time::Duration::from_secs(5), socket.next())
+                .await
+                .expect("no message within 5s")
+                .unwrap()
+                .unwrap();
+            if let Message::Text(text) = message {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    /// Like `next_json`, but also skips heartbeats
+    async fn next_event(socket: &mut EventSocket) -> serde_json::Value {
+        loop {
+            let message = next_json(socket).await;
+            if message["type"] != "heartbeat" {
+                return message;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_pushes_resumes_and_heartbeats() {
+        let (addr, app) = spawn_server().await;
+        let url = format!("ws://{}/api/games/G/events", addr);
+        let (mut socket, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+
+        let (_, alice) = send(&app, "POST", "/api/games/G/characters", Some(json!({"name": "Alice"}))).await;
+        send(&app, "POST", "/api/games/Other/characters", Some(json!({"name": "Bob"}))).await;
+        let event = next_event(&mut socket).await;
+        assert_eq!(event["type"], "character_updated");
+        assert_eq!(event["seq"], 1);
+        assert_eq!(event["character"]["name"], "Alice");
+
+        let (status, published) = send(
+            &app,
+            "POST",
+            "/api/games/G/events",
+            Some(json!({"type": "damage_applied", "character": alice["id"], "amount": 2})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(published["seq"], 2);
+        assert_eq!(next_event(&mut socket).await["type"], "damage_applied");
+
+        let (status, _) = send(
+            &app,
+            "POST",
+            "/api/games/G/events",
+            Some(json!({"type": "character_deleted", "character": alice["id"]})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let heartbeat = next_json(&mut socket).await;
+        assert_eq!(heartbeat, json!({"type": "heartbeat", "seq": 2}));
+        drop(socket);
+
+        // Events published while disconnected are replayed on resume
+        send(
+            &app,
+            "POST",
+            "/api/games/G/events",
+            Some(json!({"type": "turn_advanced", "round": 1, "turn": 2})),
+        )
+        .await;
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("{}?since=1", url))
+            .await
+            .unwrap();
+        assert_eq!(next_event(&mut socket).await["seq"], 2);
+        assert_eq!(next_event(&mut socket).await["type"], "turn_advanced");
+
+        let (mut socket, _) = tokio_tungstenite::connect_async(format!("{}?since=40", url))
+            .await
+            .unwrap();
+        assert_eq!(next_event(&mut socket).await, json!({"type": "resync", "since": 40, "seq": 3}));
+    }
+
+    #[tokio::test]
+    async fn test_event_stream_ends_when_its_token_is_revoked() {
+        use std::future::IntoFuture;
+        use crate::entities::tokens::Role;
+        use futures_util::StreamExt;
+
+        let db = crate::entities::pool::DbPool::in_memory().unwrap();
+        let mut state = state::AppState::new(db.clone());
+        state.events = events::EventHub::new()
+            .with_heartbeat_interval(std::time::Duration::from_millis(100));
+        let app = config::build_router(&config::ServerConfig::default(), state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(axum::serve(listener, app).into_future());
+
+        let (token, secret) = db
+            .run(|db| db.issue_token("Game", Role::Observer, None, None))
+            .await
+            .unwrap();
+        let url = format!("ws://{}/api/games/Game/events?access_token={}", addr, secret);
+        let (mut socket, _) = tokio_tungstenite::connect_async(&url).await.unwrap();
+        assert_eq!(next_json(&mut socket).await["type"], "heartbeat");
+
+        db.run(move |db| db.revoke_token(token.id)).await.unwrap();
+        let closed = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            loop {
+                match socket.next().await {
+                    Some(Ok(message)) if message.is_close() => break,
+                    Some(Ok(_)) => continue,
+                    _ => break,
+                }
+            }
+        })
+        .await;
+        assert!(closed.is_ok(), "the stream outlived its token");
+    }
+
+    #[tokio::test]
+    async fn test_foundry_import_export_round_trip() {
+        let app = create_test_router();
+        let actor = json!({
+            "name": "Mara",
+            "type": "character",
+            "img": "icons/mara.webp",
+            "system": { "attributes": { "physical": { "value": 3 } } },
+            "items": [{ "name": "Rope", "type": "gear", "system": { "quantity": 2, "weight": 3 } }]
+        });
+
+        let (status, mara) = send(&app, "POST", "/api/games/G/foundry", Some(actor.clone())).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(mara["physical"], 3);
+        let (status, _) = send(&app, "POST", "/api/games/G/foundry", Some(actor)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let uri = format!("/api/games/G/characters/{}/foundry", mara["id"].as_str().unwrap());
+        let (status, mut exported) = send(&app, "GET", &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(exported["img"], "icons/mara.webp");
+        assert_eq!(exported["items"][0]["system"]["quantity"], 2);
+        assert_eq!(exported["flags"]["ttdigirpg"]["id"], mara["id"]);
+
+        exported["system"]["attributes"]["physical"]["value"] = json!(4);
+        let (status, updated) = send(&app, "PUT", &uri, Some(exported)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(updated["physical"], 4);
+
+        let (status, body) = send(&app, "GET", &format!("{}?template=nosuch", uri), None).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["details"][0]["field"], "template");
+
+        let (status, body) = send(
+            &app,
+            "POST",
+            "/api/games/G/foundry",
+            Some(json!({ "name": "Npc", "type": "vehicle" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["details"][0]["field"], "type");
+    }
+
+    #[tokio::test]
+    async fn test_cors_follows_configured_origins() {
+        let mut server_config = config::ServerConfig::default();
+        server_config
+            .apply_args(&["--origin".to_string(), "http://192.168.1.20:31000".to_string()])
+            .unwrap();
+        let db = crate::entities::pool::DbPool::in_memory().unwrap();
+        let app = config::build_router(&server_config, state::AppState::new(db));
+
+        let preflight = |origin: &str| {
+            Request::builder()
+                .method("OPTIONS")
+                .uri("/api/health")
+                .header("origin", origin)
+                .header("access-control-request-method", "GET")
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let response = app.clone().oneshot(preflight("http://192.168.1.20:31000")).await.unwrap();
+        assert_eq!(
+            response.headers()["access-control-allow-origin"],
+            "http://192.168.1.20:31000"
+        );
+
+        let response = app.oneshot(preflight("http://localhost:30000")).await.unwrap();
+        assert!(response.headers().get("access-control-allow-origin").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_roles_limit_what_tokens_can_do() {
+        use crate::entities::tokens::Role;
+
+        let db = crate::entities::pool::DbPool::in_memory().unwrap();
+        let app = config::build_router(
+            &config::ServerConfig::default(),
+            state::AppState::new(db.clone()),
+        );
+        let (alice, bob, gm, player, observer, outsider) = db
+            .run(|db| {
+                let alice = db.insert_character("Alice", "Game", None)?;
+                let bob = db.insert_character("Bob", "Game", None)?;
+                let (_, gm) = db.issue_token("Game", Role::Gm, None, None)?;
+                let (_, player) = db.issue_token("Game", Role::Player, Some(&alice), None)?;
+                let (_, observer) = db.issue_token("Game", Role::Observer, None, None)?;
+                let (_, outsider) = db.issue_token("Other", Role::Gm, None, None)?;
+                Ok::<_, crate::entities::error::DbError>((
+                    alice, bob, gm, player, observer, outsider,
+                ))
+            })
+            .await
+            .unwrap();
+        let list = "/api/games/Game/characters";
+        let sheet = |uuid: &str| format!("/api/games/Game/characters/{}", uuid);
+
+        // Health is public; everything else needs a valid token for the game
+        let (status, _) = send(&app, "GET", "/api/health", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, body) = send(&app, "GET", list, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["code"], "unauthorized");
+        let (status, _) = send_as(&app, Some("ttd_forged"), "GET", list, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = send_as(&app, Some(&outsider), "GET", list, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send(&app, "GET", &format!("{}?access_token={}", list, observer), None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Observers only read
+        let patch = Some(json!({"brawl": 2}));
+        let (status, _) = send_as(&app, Some(&observer), "PATCH", &sheet(&alice), patch.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // Players change only their own character and see only its inventory
+        let (status, _) = send_as(&app, Some(&player), "PATCH", &sheet(&alice), patch.clone()).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send_as(&app, Some(&player), "PATCH", &sheet(&bob), patch.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let bob_inventory = format!("{}/inventory", sheet(&bob));
+        let (status, _) = send_as(&app, Some(&player), "GET", &bob_inventory, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send_as(&app, Some(&player), "POST", list, Some(json!({"name": "Carol"}))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // Items and object definitions only come from the GM; players can
+        // read their Foundry actor and give their items away
+        let (_, gold) = send_as(&app, Some(&gm), "POST", "/api/objects", Some(json!({"name": "Gold", "type": "currency"}))).await;
+        let alice_inventory = format!("{}/inventory", sheet(&alice));
+        let grant = Some(json!({"object_id": gold["id"], "quantity": 1000}));
+        let (status, _) = send_as(&app, Some(&player), "POST", &alice_inventory, grant.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send_as(&app, Some(&gm), "POST", &alice_inventory, Some(json!({"object_id": gold["id"], "quantity": 10}))).await;
+        assert_eq!(status, StatusCode::OK);
+        let alice_gold = format!("{}/{}", alice_inventory, gold["id"]);
+        let (status, _) = send_as(&app, Some(&player), "PUT", &alice_gold, Some(json!({"quantity": 1000}))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let transfer = Some(json!({"to": bob, "quantity": 4}));
+        let (status, _) = send_as(&app, Some(&player), "POST", &format!("{}/transfer", alice_gold), transfer).await;
+        assert_eq!(status, StatusCode::OK);
+        let foundry = format!("{}/foundry", sheet(&alice));
+        let (status, actor) = send_as(&app, Some(&player), "GET", &foundry, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send_as(&app, Some(&player), "PUT", &foundry, Some(actor.clone())).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send_as(&app, Some(&gm), "PUT", &foundry, Some(actor)).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let roll = |character: &str| {
+            Some(json!({
+                "game": "Game",
+                "character": character,
+                "pool": {"stats": ["physical"], "difficulty": 6},
+            }))
+        };
+        let (status, _) = send_as(&app, Some(&player), "POST", "/api/roll", roll(&bob)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, rolled) = send_as(&app, Some(&player), "POST", "/api/roll", roll(&alice)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let roll_uri = format!("/api/rolls/{}", rolled["id"]);
+        let (status, _) = send_as(&app, Some(&outsider), "GET", &roll_uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // The GM does everything in the game, including managing tokens
+        let (status, _) = send_as(&app, Some(&gm), "PATCH", &sheet(&bob), patch).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send_as(&app, Some(&player), "GET", "/api/games/Game/tokens", None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, issued) = send_as(
+            &app,
+            Some(&gm),
+            "POST",
+            "/api/games/Game/tokens",
+            Some(json!({"role": "player", "character": bob, "label": "Sam"})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let bob_token = issued["secret"].as_str().unwrap().to_string();
+        let (status, _) = send_as(&app, Some(&bob_token), "GET", &bob_inventory, None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let revoke = format!("/api/games/Game/tokens/{}", issued["id"]);
+        let (status, _) = send_as(&app, Some(&gm), "DELETE", &revoke, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send_as(&app, Some(&bob_token), "GET", &bob_inventory, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_gms_cannot_change_objects_other_games_use() {
+        use crate::entities::tokens::Role;
+
+        let db = crate::entities::pool::DbPool::in_memory().unwrap();
+        let app = config::build_router(
+            &config::ServerConfig::default(),
+            state::AppState::new(db.clone()),
+        );
+        let (sword, shared, gm, other_gm) = db
+            .run(|db| {
+                db.insert_character("Alice", "Game", None)?;
+                db.insert_character("Zed", "Other", None)?;
+                let sword = db.insert_object("Sword", "weapon", None)?;
+                let shared = db.insert_object("Rope", "gear", None)?;
+                db.add_object_to_character("Game", "Alice", sword, 1)?;
+                db.add_object_to_character("Game", "Alice", shared, 1)?;
+                db.add_object_to_character("Other", "Zed", shared, 1)?;
+                let (_, gm) = db.issue_token("Game", Role::Gm, None, None)?;
+                let (_, other_gm) = db.issue_token("Other", Role::Gm, None, None)?;
+                Ok::<_, crate::entities::error::DbError>((sword, shared, gm, other_gm))
+            })
+            .await
+            .unwrap();
+        let object = |id: i64| format!("/api/objects/{}", id);
+
+        // An object used in another game is off limits to this game's GM
+        let (status, body) = send_as(&app, Some(&other_gm), "DELETE", &object(sword), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(body["message"].as_str().unwrap().contains("Game"));
+        let rename = Some(json!({"name": "Frayed rope"}));
+        let (status, _) = send_as(&app, Some(&gm), "PATCH", &object(shared), rename.clone()).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send_as(&app, Some(&gm), "DELETE", &object(shared), None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, zed) = send_as(&app, Some(&other_gm), "GET", "/api/games/Other/characters", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let zed_inventory = format!("/api/games/Other/characters/{}/inventory", zed["items"][0]["id"].as_str().unwrap());
+        let (_, inventory) = send_as(&app, Some(&other_gm), "GET", &zed_inventory, None).await;
+        assert_eq!(inventory[0]["name"], "Rope");
+
+        // Objects used only in the GM's own game can still be edited and deleted
+        let (status, _) = send_as(&app, Some(&gm), "PATCH", &object(sword), Some(json!({"name": "Blade"}))).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send_as(&app, Some(&gm), "DELETE", &object(sword), None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send_as(&app, Some(&gm), "DELETE", &object(sword), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_openapi_document_covers_every_route() {
+        let app = create_test_router();
+        let (status, served) = send(&app, "GET", "/api/openapi.json", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(&served, spec());
+
+        // Every schema reference resolves
+        let document = served.to_string();
+        for reference in document.split("\"#/components/schemas/").skip(1) {
+            let name = &reference[..reference.find('"').unwrap()];
+            assert!(
+                !spec()["components"]["schemas"][name].is_null(),
+                "{} is referenced but not defined",
+                name
+            );
+        }
+
+        // Every route in the router is documented, with every method it answers...
+        let routes: Vec<String> = server::routes()
+            .into_iter()
+            .map(|(route, _)| {
+                route
+                    .split('/')
+                    .map(|part| match part.strip_prefix(':') {
+                        Some(param) => format!("{{{}}}", param),
+                        None => part.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("/")
+            })
+            .collect();
+        let documented: Vec<&String> = spec()["paths"].as_object().unwrap().keys().collect();
+        for route in &routes {
+            assert!(documented.contains(&route), "{} is not documented", route);
+            let uri = route
+                .split('/')
+                .map(|part| if part.starts_with('{') { "1" } else { part })
+                .collect::<Vec<_>>()
+                .join("/");
+            for method in ["GET", "POST", "PUT", "PATCH", "DELETE"] {
+                let request = Request::builder()
+                    .method(method)
+                    .uri(&uri)
+                    .body(Body::empty())
+                    .unwrap();
+                let response = app.clone().oneshot(request).await.unwrap();
+                if response.status() != StatusCode::METHOD_NOT_ALLOWED {
+                    assert!(
+                        !spec()["paths"][route][method.to_ascii_lowercase()].is_null(),
+                        "{} {} is routed but not documented",
+                        method,
+                        route
+                    );
+                }
+            }
+        }
+
+        // ...and every documented operation reaches a handler
+        for (path, item) in spec()["paths"].as_object().unwrap() {
+            assert!(routes.contains(path), "{} is documented but not routed", path);
+            let uri = path
+                .split('/')
+                .map(|part| if part.starts_with('{') { "1" } else { part })
+                .collect::<Vec<_>>()
+                .join("/");
+            for method in item.as_object().unwrap().keys() {
+                let request = Request::builder()
+                    .method(method.to_ascii_uppercase().as_str())
+                    .uri(&uri)
+                    .body(Body::empty())
+                    .unwrap();
+                let response = app.clone().oneshot(request).await.unwrap();
+                assert_ne!(
+                    response.status(),
+                    StatusCode::METHOD_NOT_ALLOWED,
+                    "{} {} is documented but not routed",
+                    method,
+                    path
+                );
+            }
+        }
+    }
 }
@@ -1,9 +1,18 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use std::fmt;
+use std::str::FromStr;
+
 /// Represents a character in the TTRPG system
 ///
 /// The character system is inspired by World of Darkness, with three core attributes
 /// and skills divided into three categories. All stats default to 1 and use u32 to
 /// allow flexibility without artificial caps (though 1-5 is the typical range).
-#[derive(Debug, Clone)]
+///
+/// Serializes as a flat JSON object of the name and every stat; stats missing
+/// when deserializing default to 1.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
 pub struct Character {
     /// The character's name
     pub name: String,
@@ -47,6 +56,41 @@ pub struct Character {
     pub occult: u32,
 }
 
+/// Highest rating `Character::validate` accepts for any stat.
+///
+/// Well above the usual 5 so supernatural characters fit, but low enough to
+/// catch typos like `33`.
+pub const MAX_STAT_RATING: u32 = 10;
+
+/// Longest character name `Character::validate` accepts.
+pub const MAX_NAME_LENGTH: usize = 100;
+
+/// One problem found by `Character::validate`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct FieldError {
+    /// The offending field (e.g. `"physical"`)
+    pub field: String,
+    /// What is wrong with it
+    pub message: String,
+}
+
+impl FieldError {
+    /// Creates an error for `field`.
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        FieldError {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl Default for Character {
+    /// An unnamed character with every stat at 1.
+    fn default() -> Self {
+        Character::new(String::new())
+    }
+}
+
 impl Character {
     /// Creates a new character with the given name and all stats defaulting to 1
     pub fn new(name: String) -> Self {
@@ -74,6 +118,110 @@ impl Character {
         }
     }
 
+    /// Returns the current rating of a single stat.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ttdigirpg::entities::character::{Character, Stat};
+    ///
+    /// let mut character = Character::new("Eldric".to_string());
+    /// character.stealth = 3;
+    /// assert_eq!(character.stat(Stat::Stealth), 3);
+    /// ```
+    pub fn stat(&self, stat: Stat) -> u32 {
+        match stat {
+            Stat::Physical => self.physical,
+            Stat::Social => self.social,
+            Stat::Mental => self.mental,
+            Stat::Athletics => self.athletics,
+            Stat::Awareness => self.awareness,
+            Stat::Brawl => self.brawl,
+            Stat::Streetwise => self.streetwise,
+            Stat::Combat => self.combat,
+            Stat::Stealth => self.stealth,
+            Stat::Survival => self.survival,
+            Stat::Performance => self.performance,
+            Stat::Academics => self.academics,
+            Stat::Science => self.science,
+            Stat::Investigation => self.investigation,
+            Stat::Occult => self.occult,
+        }
+    }
+
+    /// Returns a mutable reference to a single stat's rating.
+    pub fn stat_mut(&mut self, stat: Stat) -> &mut u32 {
+        match stat {
+            Stat::Physical => &mut self.physical,
+            Stat::Social => &mut self.social,
+            Stat::Mental => &mut self.mental,
+            Stat::Athletics => &mut self.athletics,
+            Stat::Awareness => &mut self.awareness,
+            Stat::Brawl => &mut self.brawl,
+            Stat::Streetwise => &mut self.streetwise,
+            Stat::Combat => &mut self.combat,
+            Stat::Stealth => &mut self.stealth,
+            Stat::Survival => &mut self.survival,
+            Stat::Performance => &mut self.performance,
+            Stat::Academics => &mut self.academics,
+            Stat::Science => &mut self.science,
+            Stat::Investigation => &mut self.investigation,
+            Stat::Occult => &mut self.occult,
+        }
+    }
+
+    /// Checks the sheet before it is saved.
+    ///
+    /// The name must be non-blank and at most `MAX_NAME_LENGTH` characters.
+    /// Attributes must be between 1 and `MAX_STAT_RATING`; talents, skills and
+    /// knowledges between 0 and `MAX_STAT_RATING`.
+    ///
+    /// # Returns
+    ///
+    /// Returns every problem found, not just the first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ttdigirpg::entities::character::Character;
+    ///
+    /// let mut character = Character::new("Eldric".to_string());
+    /// assert!(character.validate().is_ok());
+    ///
+    /// character.mental = 0;
+    /// let errors = character.validate().unwrap_err();
+    /// assert_eq!(errors[0].field, "mental");
+    /// ```
+    pub fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+
+        if self.name.trim().is_empty() {
+            errors.push(FieldError::new("name", "must not be blank"));
+        } else if self.name.chars().count() > MAX_NAME_LENGTH {
+            errors.push(FieldError::new(
+                "name",
+                format!("must be at most {} characters", MAX_NAME_LENGTH),
+            ));
+        }
+
+        for stat in Stat::ALL {
+            let minimum = if stat.is_attribute() { 1 } else { 0 };
+            let rating = self.stat(stat);
+            if rating < minimum || rating > MAX_STAT_RATING {
+                errors.push(FieldError::new(
+                    stat.name(),
+                    format!("must be between {} and {}", minimum, MAX_STAT_RATING),
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
     /// Displays the character sheet in a formatted terminal output.
     ///
     /// Renders a visually appealing character sheet to stdout using Unicode
@@ -155,6 +303,101 @@ impl Character {
     }
 }
 
+/// Names every attribute, talent, skill and knowledge on a `Character`.
+///
+/// Used wherever a stat has to be referred to by value rather than by field,
+/// e.g. when describing a dice pool such as `stealth` vs `awareness`.
+/// Parses from and displays as the lowercase field name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Stat {
+    // Attributes
+    Physical,
+    Social,
+    Mental,
+    // Talents
+    Athletics,
+    Awareness,
+    Brawl,
+    Streetwise,
+    // Skills
+    Combat,
+    Stealth,
+    Survival,
+    Performance,
+    // Knowledges
+    Academics,
+    Science,
+    Investigation,
+    Occult,
+}
+
+impl Stat {
+    /// Every stat, in character sheet order.
+    pub const ALL: [Stat; 15] = [
+        Stat::Physical,
+        Stat::Social,
+        Stat::Mental,
+        Stat::Athletics,
+        Stat::Awareness,
+        Stat::Brawl,
+        Stat::Streetwise,
+        Stat::Combat,
+        Stat::Stealth,
+        Stat::Survival,
+        Stat::Performance,
+        Stat::Academics,
+        Stat::Science,
+        Stat::Investigation,
+        Stat::Occult,
+    ];
+
+    /// Returns the lowercase field name of this stat (e.g. `"stealth"`).
+    pub fn name(self) -> &'static str {
+        match self {
+            Stat::Physical => "physical",
+            Stat::Social => "social",
+            Stat::Mental => "mental",
+            Stat::Athletics => "athletics",
+            Stat::Awareness => "awareness",
+            Stat::Brawl => "brawl",
+            Stat::Streetwise => "streetwise",
+            Stat::Combat => "combat",
+            Stat::Stealth => "stealth",
+            Stat::Survival => "survival",
+            Stat::Performance => "performance",
+            Stat::Academics => "academics",
+            Stat::Science => "science",
+            Stat::Investigation => "investigation",
+            Stat::Occult => "occult",
+        }
+    }
+
+    /// Returns true for the three core attributes.
+    pub fn is_attribute(self) -> bool {
+        matches!(self, Stat::Physical | Stat::Social | Stat::Mental)
+    }
+}
+
+impl fmt::Display for Stat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Stat {
+    type Err = String;
+
+    /// Parses a stat from its field name, ignoring case and surrounding whitespace.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let wanted = s.trim().to_ascii_lowercase();
+        Stat::ALL
+            .into_iter()
+            .find(|stat| stat.name() == wanted)
+            .ok_or_else(|| format!("Unknown stat: {}", s))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -200,4 +443,56 @@ mod tests {
         assert_eq!(character.combat, 5);
         assert_eq!(character.brawl, 3);
     }
+
+    #[test]
+    fn test_stat_lookup_matches_fields() {
+        let mut character = Character::new("Sneak".to_string());
+        character.stealth = 4;
+        character.awareness = 2;
+
+        assert_eq!(character.stat(Stat::Stealth), 4);
+        assert_eq!(character.stat(Stat::Awareness), 2);
+        assert_eq!(character.stat(Stat::Occult), 1);
+    }
+
+    #[test]
+    fn test_stat_parse_round_trip() {
+        for stat in Stat::ALL {
+            assert_eq!(stat.to_string().parse::<Stat>(), Ok(stat));
+        }
+
+        assert_eq!(" Stealth ".parse::<Stat>(), Ok(Stat::Stealth));
+        assert!("charisma".parse::<Stat>().is_err());
+    }
+
+    #[test]
+    fn test_character_json_round_trip_and_defaults() {
+        let mut character = Character::new("Ada".to_string());
+        character.science = 4;
+
+        let value = serde_json::to_value(&character).unwrap();
+        assert_eq!(value["name"], "Ada");
+        assert_eq!(value["science"], 4);
+        assert_eq!(serde_json::from_value::<Character>(value).unwrap(), character);
+
+        let sparse: Character = serde_json::from_str(r#"{"name": "Bo", "brawl": 3}"#).unwrap();
+        assert_eq!(sparse.brawl, 3);
+        assert_eq!(sparse.physical, 1);
+    }
+
+    #[test]
+    fn test_validate_reports_every_problem() {
+        let mut character = Character::new("  ".to_string());
+        character.physical = 0;
+        character.occult = 0;
+        character.combat = MAX_STAT_RATING + 1;
+
+        let fields: Vec<String> = character
+            .validate()
+            .unwrap_err()
+            .into_iter()
+            .map(|error| error.field)
+            .collect();
+        assert_eq!(fields, vec!["name", "physical", "combat"]);
+    }
 }
@@ -0,0 +1,435 @@
+//! Containers and nested inventory.
+//!
+//! Any item instance whose properties include `"capacity"` (kilograms of
+//! contents) can hold other instances; an optional `"slots"` property also
+//! caps how many items fit directly inside. Containers nest, so a pouch can
+//! sit in a backpack in a car trunk.
+//!
+//! Every instance is in at most one place: held by a character, stored in a
+//! container, or left at a location such as a hideout. Moving an instance
+//! clears its previous place.
+
+use super::database::Database;
+use super::error::{DbError, DbResult};
+use super::items::ItemInstance;
+
+/// Recursive query yielding `?1` and every instance nested inside it.
+const SUBTREE: &str = "WITH RECURSIVE tree(id) AS (
+         SELECT ?1
+         UNION ALL
+         SELECT child.id FROM item_instances child JOIN tree ON child.container_id = tree.id
+     )
+     SELECT id FROM tree";
+
+impl Database {
+    // ==================== CONTAINER METHODS ====================
+
+    /// Puts an instance inside a container instance.
+    ///
+    /// The container must be in the same game, must have a `capacity`, must
+    /// have room for the instance's total weight (including anything already
+    /// inside it) and a free slot if it has `slots`. Every container around it
+    /// with a `capacity` must have room for that weight too. An instance can't be put
+    /// inside itself or inside something it contains.
+    ///
+    /// # Arguments
+    ///
+    /// * `instance_id` - The instance to store
+    /// * `container_id` - The container instance to store it in
+    ///
+    /// # Returns
+    ///
+    /// Returns `DbError::NotFound` if either instance doesn't exist, or
+    /// `DbError::InvalidOperation` if a rule above is broken.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ttdigirpg::entities::database::Database;
+    ///
+    /// let db = Database::new(":memory:").unwrap();
+    /// db.insert_character("Alice", "Game", None).unwrap();
+    /// let pack = db.insert_object("Backpack", "container", Some(r#"{"weight": 1, "capacity": 20}"#)).unwrap();
+    /// let rope = db.insert_object("Rope", "gear", Some(r#"{"weight": 3}"#)).unwrap();
+    ///
+    /// let backpack = db.create_item_instance(pack, "Game", Some("Alice")).unwrap();
+    /// let coil = db.create_item_instance(rope, "Game", None).unwrap();
+    /// db.move_instance_into_container(coil, backpack).unwrap();
+    ///
+    /// assert_eq!(db.instance_total_weight(backpack).unwrap(), 4.0);
+    /// ```
+    pub fn move_instance_into_container(
+        &self,
+        instance_id: i64,
+        container_id: i64,
+    ) -> DbResult<()> {
+        self.transaction(|db| {
+            let item = db.require_instance(instance_id)?;
+            let container = db.require_instance(container_id)?;
+
+            if item.game != container.game {
+                return Err(DbError::invalid(format!(
+                    "{} and {} are in different games",
+                    item.name, container.name
+                )));
+            }
+            let capacity = container.capacity().ok_or_else(|| {
+                DbError::invalid(format!("{} is not a container", container.name))
+            })?;
+            if db
+                .subtree(instance_id)?
+                .iter()
+                .any(|nested| nested.id == container_id)
+            {
+                return Err(DbError::invalid(format!(
+                    "{} cannot be put inside itself",
+                    item.name
+                )));
+            }
+
+            let contents = db.get_container_contents(container_id)?;
+            let already_inside = item.container_id == Some(container_id);
+            if let Some(slots) = container.slots() {
+                if !already_inside && contents.len() as u64 >= slots {
+                    return Err(DbError::invalid(format!(
+                        "{} has no free slots ({} of {} used)",
+                        container.name,
+                        contents.len(),
+                        slots
+                    )));
+                }
+            }
+
+            // The item's weight lands in the container and in every container
+            // around it, except those it is already inside
+            let adding = db.instance_total_weight(instance_id)?;
+            let current_holders = db.enclosing_containers(&item)?;
+            let mut holders = vec![(container.clone(), capacity)];
+            for outer in db.enclosing_containers(&container)? {
+                if let Some(capacity) = outer.capacity() {
+                    holders.push((outer, capacity));
+                }
+            }
+            for (holder, capacity) in holders {
+                let mut stored = db.instance_total_weight(holder.id)? - holder.weight();
+                if current_holders
+                    .iter()
+                    .any(|current| current.id == holder.id)
+                {
+                    stored -= adding;
+                }
+                if stored + adding > capacity {
+                    return Err(DbError::invalid(format!(
+                        "{} can hold {} kg but would contain {} kg",
+                        holder.name,
+                        capacity,
+                        stored + adding
+                    )));
+                }
+            }
+
+            db.set_instance_place(instance_id, None, Some(container_id), None)
+        })
+    }
+
+    /// Hands an instance (and anything inside it) to a character.
+    pub fn move_instance_to_character(
+        &self,
+        instance_id: i64,
+        character_name: &str,
+    ) -> DbResult<()> {
+        self.set_instance_place(instance_id, Some(character_name), None, None)
+    }
+
+    /// Leaves an instance (and anything inside it) at a location.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ttdigirpg::entities::database::Database;
+    ///
+    /// let db = Database::new(":memory:").unwrap();
+    /// let safe = db.insert_object("Safe", "container", Some(r#"{"capacity": 200}"#)).unwrap();
+    /// let copy = db.create_item_instance(safe, "Game", None).unwrap();
+    /// db.move_instance_to_location(copy, "Harbour Hideout").unwrap();
+    ///
+    /// let stash = db.get_location_instances("Game", "Harbour Hideout").unwrap();
+    /// assert_eq!(stash[0].id, copy);
+    /// ```
+    pub fn move_instance_to_location(&self, instance_id: i64, location: &str) -> DbResult<()> {
+        self.set_instance_place(instance_id, None, None, Some(location))
+    }
+
+    /// Lists the instances directly inside a container.
+    pub fn get_container_contents(&self, container_id: i64) -> DbResult<Vec<ItemInstance>> {
+        self.query_instances("WHERE i.container_id = ?1", [container_id])
+    }
+
+    /// Lists the instances left directly at a location (not those inside containers there).
+    pub fn get_location_instances(
+        &self,
+        game: &str,
+        location: &str,
+    ) -> DbResult<Vec<ItemInstance>> {
+        self.query_instances("WHERE i.game = ?1 AND i.location = ?2", (game, location))
+    }
+
+    /// Lists everything a character carries: held instances and, recursively,
+    /// everything packed inside them.
+    pub fn get_carried_instances(
+        &self,
+        game: &str,
+        character_name: &str,
+    ) -> DbResult<Vec<ItemInstance>> {
+        self.query_instances(
+            "WHERE i.id IN (
+                 WITH RECURSIVE carried(id) AS (
+                     SELECT id FROM item_instances WHERE game = ?1 AND character_name = ?2
+                     UNION ALL
+                     SELECT child.id FROM item_instances child
+                     JOIN carried ON child.container_id = carried.id
+                 )
+                 SELECT id FROM carried
+             )",
+            (game, character_name),
+        )
+    }
+
+    /// Weight of an instance plus everything nested inside it, in kilograms.
+    pub fn instance_total_weight(&self, instance_id: i64) -> DbResult<f64> {
+        let tree = self.subtree(instance_id)?;
+        if tree.is_empty() {
+            return Err(DbError::not_found(format!("item instance {}", instance_id)));
+        }
+        Ok(tree.iter().map(ItemInstance::weight).sum())
+    }
+
+    /// The instance itself and every instance nested inside it.
+    fn subtree(&self, instance_id: i64) -> DbResult<Vec<ItemInstance>> {
+        self.query_instances(&format!("WHERE i.id IN ({})", SUBTREE), [instance_id])
+    }
+
+    /// The containers an instance sits in, innermost first.
+    fn enclosing_containers(&self, instance: &ItemInstance) -> DbResult<Vec<ItemInstance>> {
+        let mut containers: Vec<ItemInstance> = Vec::new();
+        let mut next = instance.container_id;
+        while let Some(container_id) = next {
+            let container = self.require_instance(container_id)?;
+            next = container.container_id;
+            containers.push(container);
+        }
+        Ok(containers)
+    }
+
+    fn require_instance(&self, instance_id: i64) -> DbResult<ItemInstance> {
+        self.get_item_instance(instance_id)?
+            .ok_or_else(|| DbError::not_found(format!("item instance {}", instance_id)))
+    }
+
+    /// Sets where an instance is, clearing the other two places.
+    fn set_instance_place(
+        &self,
+        instance_id: i64,
+        character_name: Option<&str>,
+        container_id: Option<i64>,
+        location: Option<&str>,
+    ) -> DbResult<()> {
+        let updated = self.conn.execute(
+            "UPDATE item_instances SET character_name = ?1, container_id = ?2, location = ?3
+             WHERE id = ?4",
+            (character_name, container_id, location, instance_id),
+        )?;
+        if updated == 0 {
+            return Err(DbError::not_found(format!("item instance {}", instance_id)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Gear {
+        db: Database,
+        backpack: i64,
+        pouch: i64,
+        rope: i64,
+        anvil: i64,
+    }
+
+    fn setup_gear() -> Gear {
+        let db = Database::new(":memory:").unwrap();
+        db.insert_character("Alice", "Game", None).unwrap();
+        let pack = db
+            .insert_object(
+                "Backpack",
+                "container",
+                Some(r#"{"weight": 1, "capacity": 20}"#),
+            )
+            .unwrap();
+        let small = db
+            .insert_object(
+                "Pouch",
+                "container",
+                Some(r#"{"weight": 0.5, "capacity": 2, "slots": 1}"#),
+            )
+            .unwrap();
+        let rope = db
+            .insert_object("Rope", "gear", Some(r#"{"weight": 3}"#))
+            .unwrap();
+        let anvil = db
+            .insert_object("Anvil", "gear", Some(r#"{"weight": 50}"#))
+            .unwrap();
+
+        Gear {
+            backpack: db
+                .create_item_instance(pack, "Game", Some("Alice"))
+                .unwrap(),
+            pouch: db
+                .create_item_instance(small, "Game", Some("Alice"))
+                .unwrap(),
+            rope: db
+                .create_item_instance(rope, "Game", Some("Alice"))
+                .unwrap(),
+            anvil: db.create_item_instance(anvil, "Game", None).unwrap(),
+            db,
+        }
+    }
+
+    #[test]
+    fn test_nested_weight_is_recursive() {
+        let g = setup_gear();
+        g.db.move_instance_into_container(g.rope, g.backpack)
+            .unwrap();
+        g.db.move_instance_into_container(g.pouch, g.backpack)
+            .unwrap();
+
+        assert_eq!(g.db.instance_total_weight(g.backpack).unwrap(), 4.5);
+        let rope = g.db.get_item_instance(g.rope).unwrap().unwrap();
+        assert_eq!(rope.container_id, Some(g.backpack));
+        assert_eq!(rope.character_name, None);
+
+        // Only the backpack is held directly, but all three are carried
+        assert_eq!(
+            g.db.get_character_instances("Game", "Alice").unwrap().len(),
+            1
+        );
+        assert_eq!(
+            g.db.get_carried_instances("Game", "Alice").unwrap().len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_capacity_and_slots_are_enforced() {
+        let g = setup_gear();
+
+        let err =
+            g.db.move_instance_into_container(g.anvil, g.backpack)
+                .unwrap_err();
+        assert!(matches!(err, DbError::InvalidOperation(_)));
+
+        let err =
+            g.db.move_instance_into_container(g.rope, g.pouch)
+                .unwrap_err();
+        assert!(matches!(err, DbError::InvalidOperation(_)));
+
+        let err =
+            g.db.move_instance_into_container(g.backpack, g.rope)
+                .unwrap_err();
+        assert!(
+            matches!(err, DbError::InvalidOperation(_)),
+            "rope is not a container"
+        );
+    }
+
+    #[test]
+    fn test_outer_containers_must_have_room_too() {
+        let g = setup_gear();
+        let sack =
+            g.db.insert_object(
+                "Sack",
+                "container",
+                Some(r#"{"weight": 1, "capacity": 100}"#),
+            )
+            .unwrap();
+        let toolbox =
+            g.db.insert_object("Toolbox", "gear", Some(r#"{"weight": 12}"#))
+                .unwrap();
+        let sack = g.db.create_item_instance(sack, "Game", None).unwrap();
+        let toolbox = g.db.create_item_instance(toolbox, "Game", None).unwrap();
+        g.db.move_instance_into_container(sack, g.backpack).unwrap();
+        g.db.move_instance_into_container(toolbox, g.backpack)
+            .unwrap();
+
+        // The sack has room for the anvil, but the backpack around it doesn't
+        let err =
+            g.db.move_instance_into_container(g.anvil, sack)
+                .unwrap_err();
+        assert!(matches!(err, DbError::InvalidOperation(msg) if msg.contains("Backpack")));
+        assert_eq!(g.db.instance_total_weight(g.backpack).unwrap(), 14.0);
+
+        // Moving something deeper into the same backpack doesn't count it twice
+        g.db.move_instance_into_container(toolbox, sack).unwrap();
+        assert_eq!(g.db.instance_total_weight(g.backpack).unwrap(), 14.0);
+    }
+
+    #[test]
+    fn test_cannot_create_cycles() {
+        let g = setup_gear();
+        g.db.move_instance_into_container(g.pouch, g.backpack)
+            .unwrap();
+
+        for (item, container) in [(g.backpack, g.backpack), (g.backpack, g.pouch)] {
+            let err =
+                g.db.move_instance_into_container(item, container)
+                    .unwrap_err();
+            assert!(matches!(err, DbError::InvalidOperation(_)));
+        }
+    }
+
+    #[test]
+    fn test_containers_can_sit_at_locations() {
+        let g = setup_gear();
+        g.db.move_instance_into_container(g.rope, g.backpack)
+            .unwrap();
+        g.db.move_instance_to_location(g.backpack, "Hideout")
+            .unwrap();
+
+        let stash = g.db.get_location_instances("Game", "Hideout").unwrap();
+        assert_eq!(stash.len(), 1);
+        assert_eq!(stash[0].character_name, None);
+        assert_eq!(
+            g.db.get_container_contents(g.backpack).unwrap()[0].id,
+            g.rope
+        );
+        assert_eq!(
+            g.db.get_carried_instances("Game", "Alice").unwrap().len(),
+            1
+        );
+
+        g.db.move_instance_to_character(g.backpack, "Alice")
+            .unwrap();
+        assert!(g
+            .db
+            .get_location_instances("Game", "Hideout")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_deleting_container_keeps_contents() {
+        let g = setup_gear();
+        g.db.move_instance_into_container(g.rope, g.backpack)
+            .unwrap();
+        g.db.move_instance_to_location(g.backpack, "Hideout")
+            .unwrap();
+
+        assert_eq!(g.db.delete_item_instance(g.backpack).unwrap(), 1);
+
+        let rope = g.db.get_item_instance(g.rope).unwrap().unwrap();
+        assert_eq!(rope.container_id, None);
+        assert_eq!(rope.location.as_deref(), Some("Hideout"));
+    }
+}
@@ -1,10 +1,31 @@
 //! Database management module for persistent game data storage.
 //! This module handles SQLite database initialization, table creation,
 //! and provides constructors for both shared and user-specific databases.
-use rusqlite::{Connection, Result};
+use rusqlite::{Connection, OptionalExtension};
+use std::cell::Cell;
 use std::path::Path;
+use std::time::Duration;
 use uuid::Uuid;
 
+use super::error::{DbError, DbResult};
+use super::migrations;
+use super::records::{
+    CharacterRecord, CharacterSort, GameSummary, InventoryEntry, ObjectRecord, Page, PageRequest,
+};
+
+/// Default location of the shared game database.
+pub const DEFAULT_DB_PATH: &str = "src/database/game_data.db";
+
+/// How long a connection waits for another connection's write lock.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Current wall-clock time as Unix seconds, used for timestamps stored as INTEGER.
+pub fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
 /// Wrapper around a SQLite database connection for game data persistence.
 ///
 /// The Database struct manages SQLite connections and provides methods for
@@ -12,18 +33,18 @@ use uuid::Uuid;
 /// dynamically-named user/character-specific databases.
 pub struct Database {
     /// The underlying SQLite connection
-    conn: Connection,
+    pub(crate) conn: Connection,
+    /// How many `transaction` calls are currently open on this connection
+    tx_depth: Cell<u32>,
 }
 
 impl Database {
     /// Creates or opens a database at the specified path.
     ///
-    /// If the database file doesn't exist, this method will:
-    /// 1. Create a new database file at the specified path
-    /// 2. Initialize tables by calling `create_tables`
-    /// 3. Print a confirmation message
-    ///
-    /// If the database file already exists, it will simply open the existing database.
+    /// Opening always brings the schema up to date: any migrations newer than
+    /// the file's `user_version` are applied in order (see `migrations`). A
+    /// brand-new file, an existing-but-empty file and a save from an older
+    /// version all end up with the current schema.
     ///
     /// # Arguments
     ///
@@ -31,7 +52,7 @@ impl Database {
     ///
     /// # Returns
     ///
-    /// Returns a `Result<Database>` containing the initialized database or an error.
+    /// Returns a `DbResult<Database>` containing the initialized database or an error.
     ///
     /// # Examples
     ///
@@ -42,22 +63,8 @@ impl Database {
     /// let db = Database::new(":memory:").expect("Failed to create database");
     /// // Database created successfully if we get here
     /// ```
-    pub fn new(db_path: &str) -> Result<Self> {
-        // Check if database file already exists
-        let db_exists = Path::new(db_path).exists();
-        let conn = Connection::open(db_path)?;
-
-        // Enable foreign key constraints
-        conn.execute("PRAGMA foreign_keys = ON", [])?;
-
-        if !db_exists {
-            println!("Creating new Database! At {}", db_path);
-            Self::create_tables(&conn)?;
-        } else {
-            println!("Opening existing database at {}", db_path);
-        }
-
-        Ok(Database { conn })
+    pub fn new(db_path: &str) -> DbResult<Self> {
+        Self::open(db_path)
     }
 
     /// Creates or opens a user/character-specific database.
@@ -76,7 +83,7 @@ impl Database {
     ///
     /// # Returns
     ///
-    /// Returns a `Result<Database>` containing the initialized database or an error.
+    /// Returns a `DbResult<Database>` containing the initialized database or an error.
     ///
     /// # Examples
     ///
@@ -89,23 +96,51 @@ impl Database {
     /// // Database created successfully with sanitized name
     /// ```
     ///
-    pub fn new_with_name(db_path: &str, name: &str) -> Result<Self> {
+    pub fn new_with_name(db_path: &str, name: &str) -> DbResult<Self> {
         let full_name_string_path: String = Database::name_combiner(db_path, name);
+        Self::open(&full_name_string_path)
+    }
 
-        let full_name_string_path_exists: bool = Path::new(&full_name_string_path).exists();
-        let conn = Connection::open(full_name_string_path)?;
+    /// Opens a connection, enables foreign keys and WAL, and migrates the schema.
+    ///
+    /// WAL journaling lets readers on other connections keep working while one
+    /// connection writes; the busy timeout makes competing writers wait for the
+    /// lock instead of failing immediately.
+    pub(crate) fn open(db_path: &str) -> DbResult<Self> {
+        if Path::new(db_path).exists() {
+            eprintln!("Opening existing database at {}", db_path);
+        } else {
+            eprintln!("Creating new Database! At {}", db_path);
+        }
+
+        let mut conn = Connection::open(db_path)?;
 
         // Enable foreign key constraints
         conn.execute("PRAGMA foreign_keys = ON", [])?;
 
-        if !full_name_string_path_exists {
-            println!("Creating new Database! At {}", db_path);
-            Self::create_tables(&conn)?;
-        } else {
-            println!("Opening existing database at {}", db_path);
-        }
+        // In-memory databases report "memory" here and stay unchanged
+        conn.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+
+        migrations::migrate(&mut conn)?;
+
+        Ok(Database {
+            conn,
+            tx_depth: Cell::new(0),
+        })
+    }
+
+    /// Returns the schema version this database has been migrated to.
+    pub fn schema_version(&self) -> DbResult<u32> {
+        Ok(migrations::current_version(&self.conn)?)
+    }
 
-        Ok(Database { conn })
+    /// Returns the SQLite journal mode of this connection (`"wal"` for files,
+    /// `"memory"` for in-memory databases).
+    pub fn journal_mode(&self) -> DbResult<String> {
+        Ok(self
+            .conn
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))?)
     }
 
     /// Combines two strings into a valid file path by concatenating and sanitizing.
@@ -153,66 +188,54 @@ impl Database {
         }
     }
 
-    /// Initializes database tables for a new database.
+    // ==================== TRANSACTIONS ====================
+
+    /// Runs a composite operation atomically.
     ///
-    /// This private method is called when a new database is created. It executes
-    /// SQL statements to create the necessary table schema.
+    /// The closure receives this same `Database`, so any combination of the
+    /// regular methods can be used inside it. If the closure returns `Ok`, every
+    /// change it made is committed together; if it returns `Err` (or panics),
+    /// all of them are rolled back and the error is passed through.
     ///
-    /// Creates three tables:
-    /// - `characters`: Stores character data with game context and flexible JSON data
-    /// - `character_objects`: Tracks ownership/associations between characters and objects
-    /// - `objects`: Defines object templates with flexible JSON properties
+    /// Transactions nest: an inner `transaction` call becomes a savepoint, so an
+    /// inner failure only undoes the inner work unless the error is propagated.
     ///
     /// # Arguments
     ///
-    /// * `conn` - Reference to the SQLite connection where tables should be created
+    /// * `operation` - Closure performing the multi-step operation
     ///
     /// # Returns
     ///
-    /// Returns `Ok(())` on success, or an error if table creation fails.
-    fn create_tables(conn: &Connection) -> Result<()> {
-        // Characters table - stores character data with game context
-        conn.execute(
-            "CREATE TABLE characters (
-                uuid TEXT NOT NULL UNIQUE,
-                name TEXT NOT NULL,
-                game TEXT NOT NULL,
-                data TEXT,
-                PRIMARY KEY (name, game)
-            )",
-            [],
-        )?;
-
-        // Objects table - defines what objects are (templates/definitions)
-        conn.execute(
-            "CREATE TABLE objects (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                name TEXT NOT NULL,
-                type TEXT NOT NULL,
-                properties TEXT
-            )",
-            [],
-        )?;
-
-        // Character objects table - tracks ownership/associations
-        conn.execute(
-            "CREATE TABLE character_objects (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                game TEXT NOT NULL,
-                character_name TEXT NOT NULL,
-                object_id INTEGER NOT NULL,
-                quantity INTEGER DEFAULT 1,
-                FOREIGN KEY (object_id) REFERENCES objects(id) ON DELETE CASCADE,
-                FOREIGN KEY (character_name, game) REFERENCES characters(name, game) ON DELETE CASCADE
-            )",
-            [],
-        )?;
-
-        println!("Tables created successfully!");
-        println!("  - characters: Stores character data");
-        println!("  - objects: Stores object definitions");
-        println!("  - character_objects: Tracks character ownership");
-        Ok(())
+    /// Returns whatever the closure returned. The closure's error type only has to
+    /// accept `DbError`, so game logic can fail with its own errors.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ttdigirpg::entities::database::Database;
+    /// use ttdigirpg::entities::error::DbError;
+    ///
+    /// let db = Database::new(":memory:").unwrap();
+    /// let result: Result<(), DbError> = db.transaction(|db| {
+    ///     db.insert_character("Alice", "Knives Out", None)?;
+    ///     Err(DbError::not_found("the second half of this operation"))
+    /// });
+    ///
+    /// assert!(result.is_err());
+    /// assert!(db.get_character("Alice", "Knives Out").unwrap().is_none());
+    /// ```
+    pub fn transaction<T, E, F>(&self, operation: F) -> Result<T, E>
+    where
+        F: FnOnce(&Database) -> Result<T, E>,
+        E: From<DbError>,
+    {
+        let savepoint = Savepoint::begin(self).map_err(E::from)?;
+        let result = operation(self);
+        if result.is_ok() {
+            savepoint.commit().map_err(E::from)?;
+        }
+        // On error the savepoint is dropped here, which rolls it back
+        result
     }
 
     // ==================== CHARACTER METHODS ====================
@@ -227,8 +250,8 @@ impl Database {
     ///
     /// # Returns
     ///
-    /// Returns the UUID of the newly inserted character, or an error if insertion fails
-    /// (e.g., if a character with the same name already exists in this game).
+    /// Returns the UUID of the newly inserted character, or `DbError::Duplicate` if a
+    /// character with the same name already exists in this game.
     ///
     /// # Examples
     ///
@@ -238,10 +261,11 @@ impl Database {
     /// let db = Database::new(":memory:").unwrap();
     /// let uuid = db.insert_character("Alice", "Knives Out", Some("{\"level\": 5}")).unwrap();
     /// ```
-    pub fn insert_character(&self, name: &str, game: &str, data: Option<&str>) -> Result<String> {
+    pub fn insert_character(&self, name: &str, game: &str, data: Option<&str>) -> DbResult<String> {
         let uuid = Uuid::new_v4().to_string();
         self.conn.execute(
-            "INSERT INTO characters (uuid, name, game, data) VALUES (?1, ?2, ?3, ?4)",
+            "INSERT INTO characters (uuid, name, game, data, created_at)
+             VALUES (?1, ?2, ?3, ?4, datetime('now'))",
             (&uuid, name, game, data),
         )?;
         Ok(uuid)
@@ -256,23 +280,16 @@ impl Database {
     ///
     /// # Returns
     ///
-    /// Returns `Some((uuid, name, game, data))` if found, or `None` if not found.
-    pub fn get_character(
-        &self,
-        name: &str,
-        game: &str,
-    ) -> Result<Option<(String, String, String, Option<String>)>> {
-        let mut stmt = self
+    /// Returns `Some(CharacterRecord)` if found, or `None` if not found.
+    pub fn get_character(&self, name: &str, game: &str) -> DbResult<Option<CharacterRecord>> {
+        Ok(self
             .conn
-            .prepare("SELECT uuid, name, game, data FROM characters WHERE name = ?1 AND game = ?2")?;
-
-        let mut rows = stmt.query((name, game))?;
-
-        if let Some(row) = rows.next()? {
-            Ok(Some((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
-        } else {
-            Ok(None)
-        }
+            .query_row(
+                "SELECT uuid, name, game, data FROM characters WHERE name = ?1 AND game = ?2",
+                (name, game),
+                CharacterRecord::from_row,
+            )
+            .optional()?)
     }
 
     /// Updates a character's data in the database.
@@ -286,7 +303,7 @@ impl Database {
     /// # Returns
     ///
     /// Returns the number of rows updated (should be 1 if successful, 0 if character not found).
-    pub fn update_character(&self, name: &str, game: &str, data: &str) -> Result<usize> {
+    pub fn update_character(&self, name: &str, game: &str, data: &str) -> DbResult<usize> {
         Ok(self.conn.execute(
             "UPDATE characters SET data = ?1 WHERE name = ?2 AND game = ?3",
             (data, name, game),
@@ -303,13 +320,181 @@ impl Database {
     /// # Returns
     ///
     /// Returns the number of rows deleted (should be 1 if successful, 0 if character not found).
-    pub fn delete_character(&self, name: &str, game: &str) -> Result<usize> {
+    pub fn delete_character(&self, name: &str, game: &str) -> DbResult<usize> {
         Ok(self.conn.execute(
             "DELETE FROM characters WHERE name = ?1 AND game = ?2",
             (name, game),
         )?)
     }
 
+    // ==================== CHARACTER UUID METHODS ====================
+    //
+    // The UUID assigned on insert never changes, so external systems (such as
+    // Foundry actor IDs) can hold on to it across renames.
+
+    /// Retrieves a character by its UUID.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(CharacterRecord)` if found, or `None` if not found.
+    pub fn get_character_by_uuid(&self, uuid: &str) -> DbResult<Option<CharacterRecord>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT uuid, name, game, data FROM characters WHERE uuid = ?1",
+                [uuid],
+                CharacterRecord::from_row,
+            )
+            .optional()?)
+    }
+
+    /// Updates a character's data by UUID.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of rows updated (1 if successful, 0 if character not found).
+    pub fn update_character_by_uuid(&self, uuid: &str, data: &str) -> DbResult<usize> {
+        Ok(self.conn.execute(
+            "UPDATE characters SET data = ?1 WHERE uuid = ?2",
+            (data, uuid),
+        )?)
+    }
+
+    /// Deletes a character by UUID. Owned objects are removed by cascade.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of rows deleted (1 if successful, 0 if character not found).
+    pub fn delete_character_by_uuid(&self, uuid: &str) -> DbResult<usize> {
+        Ok(self
+            .conn
+            .execute("DELETE FROM characters WHERE uuid = ?1", [uuid])?)
+    }
+
+    /// Renames a character, keeping its UUID and inventory.
+    ///
+    /// # Arguments
+    ///
+    /// * `uuid` - The character's UUID
+    /// * `new_name` - The new name, which must be unused in the character's game
+    ///
+    /// # Returns
+    ///
+    /// Returns `DbError::NotFound` if no character has this UUID, or
+    /// `DbError::Duplicate` if the name is already taken in the same game.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ttdigirpg::entities::database::Database;
+    ///
+    /// let db = Database::new(":memory:").unwrap();
+    /// let uuid = db.insert_character("Alice", "Knives Out", None).unwrap();
+    /// db.rename_character(&uuid, "Alicia").unwrap();
+    ///
+    /// assert_eq!(db.get_character_by_uuid(&uuid).unwrap().unwrap().name, "Alicia");
+    /// ```
+    pub fn rename_character(&self, uuid: &str, new_name: &str) -> DbResult<()> {
+        let updated = self.conn.execute(
+            "UPDATE characters SET name = ?1 WHERE uuid = ?2",
+            (new_name, uuid),
+        )?;
+        if updated == 0 {
+            return Err(DbError::not_found(format!("character {}", uuid)));
+        }
+        Ok(())
+    }
+
+    // ==================== LISTING METHODS ====================
+
+    /// Lists one page of the characters in a game.
+    ///
+    /// # Arguments
+    ///
+    /// * `game` - The game to list
+    /// * `sort` - Column to order by; ties are broken by name
+    /// * `page` - Limit, offset and direction
+    ///
+    /// # Returns
+    ///
+    /// Returns the requested page and the total number of characters in the game.
+    pub fn list_characters(
+        &self,
+        game: &str,
+        sort: CharacterSort,
+        page: &PageRequest,
+    ) -> DbResult<Page<CharacterRecord>> {
+        let order_column = match sort {
+            CharacterSort::Name => "name",
+            CharacterSort::CreatedAt => "created_at",
+        };
+        let sql = format!(
+            "SELECT uuid, name, game, data FROM characters
+             WHERE game = ?1
+             ORDER BY {col} {dir}, name {dir}
+             LIMIT ?2 OFFSET ?3",
+            col = order_column,
+            dir = page.direction()
+        );
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(
+            (game, page.effective_limit(), page.offset),
+            CharacterRecord::from_row,
+        )?;
+        let items = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let total = self.conn.query_row(
+            "SELECT COUNT(*) FROM characters WHERE game = ?1",
+            [game],
+            |row| row.get(0),
+        )?;
+
+        Ok(Page {
+            items,
+            total,
+            limit: page.effective_limit(),
+            offset: page.offset,
+        })
+    }
+
+    /// Lists one page of games that have characters, ordered by game name.
+    ///
+    /// # Returns
+    ///
+    /// Returns each game with its character count, plus the total number of games.
+    pub fn list_games(&self, page: &PageRequest) -> DbResult<Page<GameSummary>> {
+        let sql = format!(
+            "SELECT game, COUNT(*) FROM characters
+             GROUP BY game
+             ORDER BY game {}
+             LIMIT ?1 OFFSET ?2",
+            page.direction()
+        );
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map((page.effective_limit(), page.offset), |row| {
+            Ok(GameSummary {
+                game: row.get(0)?,
+                character_count: row.get(1)?,
+            })
+        })?;
+        let items = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let total = self.conn.query_row(
+            "SELECT COUNT(DISTINCT game) FROM characters",
+            [],
+            |row| row.get(0),
+        )?;
+
+        Ok(Page {
+            items,
+            total,
+            limit: page.effective_limit(),
+            offset: page.offset,
+        })
+    }
+
     // ==================== OBJECT METHODS ====================
 
     /// Inserts a new object definition into the database.
@@ -320,6 +505,9 @@ impl Database {
     /// * `obj_type` - The object's type (e.g., "building", "organization", "item")
     /// * `properties` - Optional JSON string containing object properties
     ///
+    /// Carried items can declare `"weight"` (kg per unit) and `"bulk"` (how
+    /// awkward one unit is to carry) in their properties; both feed encumbrance.
+    ///
     /// # Returns
     ///
     /// Returns the ID of the newly inserted object.
@@ -341,7 +529,7 @@ impl Database {
         name: &str,
         obj_type: &str,
         properties: Option<&str>,
-    ) -> Result<i64> {
+    ) -> DbResult<i64> {
         self.conn.execute(
             "INSERT INTO objects (name, type, properties) VALUES (?1, ?2, ?3)",
             (name, obj_type, properties),
@@ -357,22 +545,33 @@ impl Database {
     ///
     /// # Returns
     ///
-    /// Returns `Some((id, name, type, properties))` if found, or `None` if not found.
-    pub fn get_object(
-        &self,
-        object_id: i64,
-    ) -> Result<Option<(i64, String, String, Option<String>)>> {
-        let mut stmt = self
+    /// Returns `Some(ObjectRecord)` if found, or `None` if not found.
+    pub fn get_object(&self, object_id: i64) -> DbResult<Option<ObjectRecord>> {
+        Ok(self
             .conn
-            .prepare("SELECT id, name, type, properties FROM objects WHERE id = ?1")?;
-
-        let mut rows = stmt.query([object_id])?;
+            .query_row(
+                "SELECT id, name, type, properties FROM objects WHERE id = ?1",
+                [object_id],
+                ObjectRecord::from_row,
+            )
+            .optional()?)
+    }
 
-        if let Some(row) = rows.next()? {
-            Ok(Some((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
-        } else {
-            Ok(None)
-        }
+    /// Finds object definitions by exact name, lowest ID first.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The object name (e.g., "Sword")
+    ///
+    /// # Returns
+    ///
+    /// Returns every object with that name (names are not required to be unique).
+    pub fn find_objects_by_name(&self, name: &str) -> DbResult<Vec<ObjectRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, type, properties FROM objects WHERE name = ?1 ORDER BY id",
+        )?;
+        let rows = stmt.query_map([name], ObjectRecord::from_row)?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
     }
 
     /// Updates an object's properties.
@@ -385,13 +584,57 @@ impl Database {
     /// # Returns
     ///
     /// Returns the number of rows updated (should be 1 if successful, 0 if object not found).
-    pub fn update_object(&self, object_id: i64, properties: &str) -> Result<usize> {
+    pub fn update_object(&self, object_id: i64, properties: &str) -> DbResult<usize> {
         Ok(self.conn.execute(
             "UPDATE objects SET properties = ?1 WHERE id = ?2",
             (properties, object_id),
         )?)
     }
 
+    /// Overwrites an object definition's name, type and properties.
+    ///
+    /// # Arguments
+    ///
+    /// * `object_id` - The object's ID
+    /// * `name` - The new name
+    /// * `obj_type` - The new type
+    /// * `properties` - New JSON string containing object properties, or `None` to clear them
+    ///
+    /// # Returns
+    ///
+    /// Returns `DbError::NotFound` if the object doesn't exist.
+    pub fn replace_object(
+        &self,
+        object_id: i64,
+        name: &str,
+        obj_type: &str,
+        properties: Option<&str>,
+    ) -> DbResult<()> {
+        let updated = self.conn.execute(
+            "UPDATE objects SET name = ?1, type = ?2, properties = ?3 WHERE id = ?4",
+            (name, obj_type, properties, object_id),
+        )?;
+        if updated == 0 {
+            return Err(DbError::not_found(format!("object {}", object_id)));
+        }
+        Ok(())
+    }
+
+    /// Lists object definitions ordered by name, optionally only those of one type.
+    ///
+    /// # Arguments
+    ///
+    /// * `obj_type` - Only return objects of this type (e.g., "weapon"), if given
+    pub fn list_objects(&self, obj_type: Option<&str>) -> DbResult<Vec<ObjectRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, type, properties FROM objects
+             WHERE ?1 IS NULL OR type = ?1
+             ORDER BY name, id",
+        )?;
+        let rows = stmt.query_map([obj_type], ObjectRecord::from_row)?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
     /// Deletes an object definition from the database.
     ///
     /// # Arguments
@@ -401,16 +644,39 @@ impl Database {
     /// # Returns
     ///
     /// Returns the number of rows deleted (should be 1 if successful, 0 if object not found).
-    pub fn delete_object(&self, object_id: i64) -> Result<usize> {
+    pub fn delete_object(&self, object_id: i64) -> DbResult<usize> {
         Ok(self
             .conn
             .execute("DELETE FROM objects WHERE id = ?1", [object_id])?)
     }
 
+    /// Lists the games whose inventories or item instances use an object definition.
+    ///
+    /// # Arguments
+    ///
+    /// * `object_id` - The object's ID
+    ///
+    /// # Returns
+    ///
+    /// Returns the game names in alphabetical order (empty if nothing uses the object).
+    pub fn object_games(&self, object_id: i64) -> DbResult<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT game FROM character_objects WHERE object_id = ?1
+             UNION
+             SELECT game FROM item_instances WHERE object_id = ?1
+             ORDER BY game",
+        )?;
+        let rows = stmt.query_map([object_id], |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
     // ==================== CHARACTER OBJECT (OWNERSHIP) METHODS ====================
 
     /// Adds an object to a character's inventory/associations.
     ///
+    /// Inventory stacks: if the character already holds this object, the
+    /// quantity is added to the existing row instead of creating a new one.
+    ///
     /// # Arguments
     ///
     /// * `game` - The game context
@@ -420,7 +686,8 @@ impl Database {
     ///
     /// # Returns
     ///
-    /// Returns the ID of the newly created association.
+    /// Returns the ID of the stack's association row, or `DbError::ForeignKeyViolation`
+    /// if the character or object doesn't exist.
     ///
     /// # Examples
     ///
@@ -438,12 +705,16 @@ impl Database {
         character_name: &str,
         object_id: i64,
         quantity: i32,
-    ) -> Result<i64> {
-        self.conn.execute(
-            "INSERT INTO character_objects (game, character_name, object_id, quantity) VALUES (?1, ?2, ?3, ?4)",
+    ) -> DbResult<i64> {
+        Ok(self.conn.query_row(
+            "INSERT INTO character_objects (game, character_name, object_id, quantity)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (game, character_name, object_id)
+             DO UPDATE SET quantity = COALESCE(quantity, 1) + excluded.quantity
+             RETURNING id",
             (game, character_name, object_id, quantity),
-        )?;
-        Ok(self.conn.last_insert_rowid())
+            |row| row.get(0),
+        )?)
     }
 
     /// Removes an object from a character's inventory/associations.
@@ -462,7 +733,7 @@ impl Database {
         game: &str,
         character_name: &str,
         object_id: i64,
-    ) -> Result<usize> {
+    ) -> DbResult<usize> {
         Ok(self.conn.execute(
             "DELETE FROM character_objects WHERE game = ?1 AND character_name = ?2 AND object_id = ?3",
             (game, character_name, object_id),
@@ -487,7 +758,7 @@ impl Database {
         character_name: &str,
         object_id: i64,
         quantity: i32,
-    ) -> Result<usize> {
+    ) -> DbResult<usize> {
         Ok(self.conn.execute(
             "UPDATE character_objects SET quantity = ?1 WHERE game = ?2 AND character_name = ?3 AND object_id = ?4",
             (quantity, game, character_name, object_id),
@@ -503,12 +774,12 @@ impl Database {
     ///
     /// # Returns
     ///
-    /// Returns a vector of tuples containing (object_id, object_name, object_type, quantity, properties).
+    /// Returns one `InventoryEntry` per owned object.
     pub fn get_character_objects(
         &self,
         game: &str,
         character_name: &str,
-    ) -> Result<Vec<(i64, String, String, i32, Option<String>)>> {
+    ) -> DbResult<Vec<InventoryEntry>> {
         let mut stmt = self.conn.prepare(
             "SELECT o.id, o.name, o.type, co.quantity, o.properties
              FROM character_objects co
@@ -516,15 +787,7 @@ impl Database {
              WHERE co.game = ?1 AND co.character_name = ?2",
         )?;
 
-        let rows = stmt.query_map((game, character_name), |row| {
-            Ok((
-                row.get(0)?,
-                row.get(1)?,
-                row.get(2)?,
-                row.get(3)?,
-                row.get(4)?,
-            ))
-        })?;
+        let rows = stmt.query_map((game, character_name), InventoryEntry::from_row)?;
 
         let mut objects = Vec::new();
         for row in rows {
@@ -535,6 +798,73 @@ impl Database {
     }
 }
 
+/// An open transaction level created by `Database::transaction`.
+///
+/// The outermost level is a `BEGIN IMMEDIATE` transaction, which takes the
+/// write lock up front so a read-then-write operation can't be invalidated by
+/// another connection writing in between (readers are unaffected under WAL).
+/// Nested levels are savepoints. Dropping the guard without calling `commit`
+/// rolls back everything done since it was created, which also covers panics.
+struct Savepoint<'a> {
+    db: &'a Database,
+    name: String,
+    outermost: bool,
+    finished: bool,
+}
+
+impl<'a> Savepoint<'a> {
+    fn begin(db: &'a Database) -> DbResult<Self> {
+        let depth = db.tx_depth.get();
+        let name = format!("ttdigirpg_tx_{}", depth);
+        let outermost = depth == 0;
+        if outermost {
+            db.conn.execute_batch("BEGIN IMMEDIATE")?;
+        } else {
+            db.conn.execute_batch(&format!("SAVEPOINT {}", name))?;
+        }
+        db.tx_depth.set(depth + 1);
+
+        Ok(Savepoint {
+            db,
+            name,
+            outermost,
+            finished: false,
+        })
+    }
+
+    /// Commits the savepoint. If that fails (e.g. `SQLITE_BUSY`), the guard
+    /// stays live and rolls the transaction back when dropped.
+    fn commit(mut self) -> DbResult<()> {
+        if self.outermost {
+            self.db.conn.execute_batch("COMMIT")?;
+        } else {
+            self.db
+                .conn
+                .execute_batch(&format!("RELEASE {}", self.name))?;
+        }
+        self.finished = true;
+        self.db.tx_depth.set(self.db.tx_depth.get() - 1);
+        Ok(())
+    }
+}
+
+impl Drop for Savepoint<'_> {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+        self.db.tx_depth.set(self.db.tx_depth.get() - 1);
+        let rollback = if self.outermost {
+            "ROLLBACK".to_string()
+        } else {
+            format!("ROLLBACK TO {0}; RELEASE {0}", self.name)
+        };
+        if let Err(err) = self.db.conn.execute_batch(&rollback) {
+            eprintln!("Failed to roll back transaction {}: {}", self.name, err);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -559,6 +889,92 @@ mod tests {
         assert!(result.is_ok(), "Should be able to insert into newly created database");
     }
 
+    /// Builds a unique path in the system temp directory for file-backed tests.
+    fn temp_db_path(label: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("ttdigirpg_{}_{}.db", label, Uuid::new_v4()))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn test_open_existing_empty_file_creates_tables() {
+        // An empty file used to be treated as "existing" and got no tables
+        let path = temp_db_path("empty");
+        std::fs::File::create(&path).unwrap();
+
+        let db = Database::new(&path).expect("Should open empty file");
+        let result = db.insert_character("Test Character", "Test Game", None);
+        std::fs::remove_file(&path).ok();
+
+        assert!(result.is_ok(), "Empty file should be migrated to the full schema");
+    }
+
+    #[test]
+    fn test_open_upgrades_fixture_from_every_past_version() {
+        // Version 0 covers both a blank file and a pre-versioning save (below)
+        for version in 0..migrations::latest_version() {
+            let path = temp_db_path(&format!("v{}", version));
+            {
+                let mut conn = Connection::open(&path).unwrap();
+                migrations::migrate_to(&mut conn, version).unwrap();
+                if version >= 1 {
+                    conn.execute_batch(
+                        "INSERT INTO characters (uuid, name, game, data)
+                             VALUES ('fixture-uuid', 'Alice', 'Fixture', '{\"level\": 3}');
+                         INSERT INTO objects (name, type, properties) VALUES ('Sword', 'weapon', NULL);
+                         INSERT INTO character_objects (game, character_name, object_id, quantity)
+                             VALUES ('Fixture', 'Alice', 1, 2);",
+                    )
+                    .unwrap();
+                }
+            }
+
+            let db = Database::new(&path).expect("Upgrade should succeed");
+            let upgraded_version = migrations::current_version(&db.conn).unwrap();
+
+            if version >= 1 {
+                let alice = db.get_character("Alice", "Fixture").unwrap()
+                    .expect("Fixture data should survive the upgrade");
+                assert_eq!(alice.uuid, "fixture-uuid");
+                let inventory = db.get_character_objects("Fixture", "Alice").unwrap();
+                assert_eq!(inventory.len(), 1);
+                assert_eq!(inventory[0].quantity, 2);
+            }
+            db.insert_character("Bob", "Fixture", None)
+                .expect("Upgraded schema should accept new rows");
+
+            drop(db);
+            std::fs::remove_file(&path).ok();
+            assert_eq!(upgraded_version, migrations::latest_version(), "from version {}", version);
+        }
+    }
+
+    #[test]
+    fn test_open_upgrades_pre_versioning_save() {
+        // Saves made before migrations existed have the tables but user_version 0
+        let path = temp_db_path("legacy");
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(migrations::MIGRATIONS[0].sql).unwrap();
+            conn.execute(
+                "INSERT INTO characters (uuid, name, game, data) VALUES ('legacy', 'Old', 'Save', NULL)",
+                [],
+            )
+            .unwrap();
+            assert_eq!(migrations::current_version(&conn).unwrap(), 0);
+        }
+
+        let db = Database::new(&path).expect("Legacy save should open");
+        let old = db.get_character("Old", "Save").unwrap();
+        let version = migrations::current_version(&db.conn).unwrap();
+        drop(db);
+        std::fs::remove_file(&path).ok();
+
+        assert!(old.is_some(), "Legacy data should be kept");
+        assert_eq!(version, migrations::latest_version());
+    }
+
     #[test]
     fn test_name_combiner_basic() {
         // Test basic string concatenation
@@ -615,7 +1031,10 @@ mod tests {
 
         // Try to insert duplicate (same name + game) - should fail
         let result = db.insert_character("Alice", "Knives Out", None);
-        assert!(result.is_err(), "Duplicate character should fail due to UNIQUE constraint");
+        assert!(
+            matches!(result, Err(DbError::Duplicate(_))),
+            "Duplicate character should fail due to UNIQUE constraint"
+        );
     }
 
     #[test]
@@ -644,12 +1063,12 @@ mod tests {
 
         assert!(result.is_some(), "Character should be found");
 
-        let (uuid, name, game, data) = result.unwrap();
-        assert!(!uuid.is_empty(), "UUID should not be empty");
-        assert!(Uuid::parse_str(&uuid).is_ok(), "Should have a valid UUID");
-        assert_eq!(name, "Alice");
-        assert_eq!(game, "Knives Out");
-        assert_eq!(data, Some(json_data.to_string()));
+        let record = result.unwrap();
+        assert!(!record.uuid.is_empty(), "UUID should not be empty");
+        assert!(Uuid::parse_str(&record.uuid).is_ok(), "Should have a valid UUID");
+        assert_eq!(record.name, "Alice");
+        assert_eq!(record.game, "Knives Out");
+        assert_eq!(record.data, Some(json_data.to_string()));
     }
 
     #[test]
@@ -680,8 +1099,7 @@ mod tests {
         // Verify the update
         let result = db.get_character("Alice", "Knives Out")
             .expect("Failed to get character");
-        let (_, _, _, data) = result.unwrap();
-        assert_eq!(data, Some(updated_data.to_string()));
+        assert_eq!(result.unwrap().data, Some(updated_data.to_string()));
     }
 
     #[test]
@@ -723,6 +1141,126 @@ mod tests {
         assert_eq!(rows_affected, 0, "Deleting non-existent character should affect 0 rows");
     }
 
+    // ==================== CHARACTER UUID TESTS ====================
+
+    #[test]
+    fn test_get_update_delete_by_uuid() {
+        let db = setup_test_db();
+        let uuid = db.insert_character("Alice", "Knives Out", None).unwrap();
+
+        let alice = db.get_character_by_uuid(&uuid).unwrap().expect("Should find by UUID");
+        assert_eq!(alice.name, "Alice");
+
+        assert_eq!(db.update_character_by_uuid(&uuid, r#"{"level": 2}"#).unwrap(), 1);
+        let alice = db.get_character("Alice", "Knives Out").unwrap().unwrap();
+        assert_eq!(alice.data, Some(r#"{"level": 2}"#.to_string()));
+
+        assert_eq!(db.delete_character_by_uuid(&uuid).unwrap(), 1);
+        assert!(db.get_character_by_uuid(&uuid).unwrap().is_none());
+        assert_eq!(db.delete_character_by_uuid(&uuid).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_rename_character_keeps_uuid_and_inventory() {
+        let db = setup_test_db();
+        let uuid = db.insert_character("Alice", "Test Game", None).unwrap();
+        let sword_id = db.insert_object("Sword", "weapon", None).unwrap();
+        db.add_object_to_character("Test Game", "Alice", sword_id, 1).unwrap();
+
+        db.rename_character(&uuid, "Alicia").expect("Rename should succeed");
+
+        assert!(db.get_character("Alice", "Test Game").unwrap().is_none());
+        let alicia = db.get_character("Alicia", "Test Game").unwrap().unwrap();
+        assert_eq!(alicia.uuid, uuid);
+        let objects = db.get_character_objects("Test Game", "Alicia").unwrap();
+        assert_eq!(objects.len(), 1, "Inventory should follow the rename");
+    }
+
+    #[test]
+    fn test_rename_character_errors() {
+        let db = setup_test_db();
+        let uuid = db.insert_character("Alice", "Test Game", None).unwrap();
+        db.insert_character("Bob", "Test Game", None).unwrap();
+
+        assert!(matches!(
+            db.rename_character(&uuid, "Bob"),
+            Err(DbError::Duplicate(_))
+        ));
+        assert!(matches!(
+            db.rename_character("missing-uuid", "Carol"),
+            Err(DbError::NotFound(_))
+        ));
+    }
+
+    // ==================== LISTING TESTS ====================
+
+    #[test]
+    fn test_list_characters_paginates_and_sorts() {
+        let db = setup_test_db();
+        for name in ["Carol", "Alice", "Eve", "Bob", "Dave"] {
+            db.insert_character(name, "Game", None).unwrap();
+        }
+        db.insert_character("Zed", "Other Game", None).unwrap();
+
+        let first = db
+            .list_characters("Game", CharacterSort::Name, &PageRequest::new(2, 0))
+            .unwrap();
+        let names: Vec<_> = first.items.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["Alice", "Bob"]);
+        assert_eq!(first.total, 5);
+
+        let last = db
+            .list_characters("Game", CharacterSort::Name, &PageRequest::new(2, 4))
+            .unwrap();
+        let names: Vec<_> = last.items.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["Eve"]);
+
+        let descending = PageRequest {
+            descending: true,
+            ..PageRequest::new(1, 0)
+        };
+        let reversed = db
+            .list_characters("Game", CharacterSort::Name, &descending)
+            .unwrap();
+        assert_eq!(reversed.items[0].name, "Eve");
+    }
+
+    #[test]
+    fn test_list_characters_by_creation_time() {
+        let db = setup_test_db();
+        db.insert_character("Zoe", "Game", None).unwrap();
+        db.insert_character("Adam", "Game", None).unwrap();
+        db.conn
+            .execute(
+                "UPDATE characters SET created_at = '2020-01-01 00:00:00' WHERE name = 'Zoe'",
+                [],
+            )
+            .unwrap();
+
+        let page = db
+            .list_characters("Game", CharacterSort::CreatedAt, &PageRequest::default())
+            .unwrap();
+        assert_eq!(page.items[0].name, "Zoe", "Oldest character should come first");
+    }
+
+    #[test]
+    fn test_list_games() {
+        let db = setup_test_db();
+        db.insert_character("Alice", "Vampire", None).unwrap();
+        db.insert_character("Bob", "Vampire", None).unwrap();
+        db.insert_character("Carol", "Mage", None).unwrap();
+
+        let games = db.list_games(&PageRequest::default()).unwrap();
+        assert_eq!(games.total, 2);
+        assert_eq!(
+            games.items,
+            vec![
+                GameSummary { game: "Mage".to_string(), character_count: 1 },
+                GameSummary { game: "Vampire".to_string(), character_count: 2 },
+            ]
+        );
+    }
+
     // ==================== OBJECT METHOD TESTS ====================
 
     #[test]
@@ -747,11 +1285,11 @@ mod tests {
             .expect("Query failed");
 
         assert!(result.is_some());
-        let (id, name, obj_type, properties) = result.unwrap();
-        assert_eq!(id, inserted_id);
-        assert_eq!(name, "Sword");
-        assert_eq!(obj_type, "weapon");
-        assert_eq!(properties, Some(props.to_string()));
+        let object = result.unwrap();
+        assert_eq!(object.id, inserted_id);
+        assert_eq!(object.name, "Sword");
+        assert_eq!(object.obj_type, "weapon");
+        assert_eq!(object.properties, Some(props.to_string()));
     }
 
     #[test]
@@ -778,8 +1316,41 @@ mod tests {
         assert_eq!(rows_affected, 1);
 
         let result = db.get_object(id).expect("Query failed");
-        let (_, _, _, properties) = result.unwrap();
-        assert_eq!(properties, Some(new_props.to_string()));
+        assert_eq!(result.unwrap().properties, Some(new_props.to_string()));
+    }
+
+    #[test]
+    fn test_replace_object() {
+        let db = setup_test_db();
+
+        let id = db.insert_object("Sword", "weapon", Some(r#"{"damage": 10}"#))
+            .expect("Failed to insert object");
+
+        db.replace_object(id, "Rusty Sword", "junk", None)
+            .expect("Failed to replace object");
+
+        let result = db.get_object(id).expect("Query failed").unwrap();
+        assert_eq!(result.name, "Rusty Sword");
+        assert_eq!(result.obj_type, "junk");
+        assert_eq!(result.properties, None);
+
+        let missing = db.replace_object(999, "Ghost", "item", None);
+        assert!(matches!(missing, Err(DbError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_list_objects_filters_by_type() {
+        let db = setup_test_db();
+
+        db.insert_object("Sword", "weapon", None).unwrap();
+        db.insert_object("Axe", "weapon", None).unwrap();
+        db.insert_object("Potion", "consumable", None).unwrap();
+
+        let weapons = db.list_objects(Some("weapon")).expect("Query failed");
+        let names: Vec<_> = weapons.iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(names, vec!["Axe", "Sword"]);
+
+        assert_eq!(db.list_objects(None).expect("Query failed").len(), 3);
     }
 
     #[test]
@@ -815,6 +1386,57 @@ mod tests {
         assert!(association_id > 0);
     }
 
+    #[test]
+    fn test_add_object_twice_stacks() {
+        let db = setup_test_db();
+        db.insert_character("Alice", "Test Game", None).unwrap();
+        let arrow_id = db.insert_object("Arrow", "ammunition", None).unwrap();
+
+        let first = db.add_object_to_character("Test Game", "Alice", arrow_id, 10).unwrap();
+        let second = db.add_object_to_character("Test Game", "Alice", arrow_id, 10).unwrap();
+
+        assert_eq!(first, second, "Both adds should land on the same stack");
+        let objects = db.get_character_objects("Test Game", "Alice").unwrap();
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0].quantity, 20);
+    }
+
+    #[test]
+    fn test_open_merges_duplicate_stacks_from_old_saves() {
+        let path = temp_db_path("stacks");
+        {
+            let mut conn = Connection::open(&path).unwrap();
+            migrations::migrate_to(&mut conn, 3).unwrap();
+            conn.execute_batch(
+                "INSERT INTO characters (uuid, name, game) VALUES ('u1', 'Alice', 'Game');
+                 INSERT INTO objects (name, type) VALUES ('Arrow', 'ammunition');
+                 INSERT INTO character_objects (game, character_name, object_id, quantity)
+                     VALUES ('Game', 'Alice', 1, 10), ('Game', 'Alice', 1, 10), ('Game', 'Alice', 1, 5);",
+            )
+            .unwrap();
+        }
+
+        let db = Database::new(&path).unwrap();
+        let objects = db.get_character_objects("Game", "Alice").unwrap();
+        drop(db);
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(objects.len(), 1, "Duplicate rows should be folded into one stack");
+        assert_eq!(objects[0].quantity, 25);
+    }
+
+    #[test]
+    fn test_add_object_to_missing_character_is_foreign_key_violation() {
+        let db = setup_test_db();
+        let sword_id = db.insert_object("Sword", "weapon", None).unwrap();
+
+        let result = db.add_object_to_character("Test Game", "Nobody", sword_id, 1);
+        assert!(
+            matches!(result, Err(DbError::ForeignKeyViolation(_))),
+            "Unknown character should be reported as a foreign key violation"
+        );
+    }
+
     #[test]
     fn test_get_character_objects() {
         let db = setup_test_db();
@@ -835,19 +1457,19 @@ mod tests {
         assert_eq!(objects.len(), 2, "Character should have 2 objects");
 
         // Verify first object (Sword)
-        let (id, name, obj_type, quantity, properties) = &objects[0];
-        assert_eq!(*id, sword_id);
-        assert_eq!(name, "Sword");
-        assert_eq!(obj_type, "weapon");
-        assert_eq!(*quantity, 1);
-        assert_eq!(properties, &Some(r#"{"damage": 10}"#.to_string()));
+        let sword = &objects[0];
+        assert_eq!(sword.object_id, sword_id);
+        assert_eq!(sword.name, "Sword");
+        assert_eq!(sword.obj_type, "weapon");
+        assert_eq!(sword.quantity, 1);
+        assert_eq!(sword.properties, Some(r#"{"damage": 10}"#.to_string()));
 
         // Verify second object (Shield)
-        let (id, name, obj_type, quantity, _) = &objects[1];
-        assert_eq!(*id, shield_id);
-        assert_eq!(name, "Shield");
-        assert_eq!(obj_type, "armor");
-        assert_eq!(*quantity, 2);
+        let shield = &objects[1];
+        assert_eq!(shield.object_id, shield_id);
+        assert_eq!(shield.name, "Shield");
+        assert_eq!(shield.obj_type, "armor");
+        assert_eq!(shield.quantity, 2);
     }
 
     #[test]
@@ -879,8 +1501,7 @@ mod tests {
 
         // Verify
         let objects = db.get_character_objects("Test Game", "Alice").unwrap();
-        let (_, _, _, quantity, _) = &objects[0];
-        assert_eq!(*quantity, 10);
+        assert_eq!(objects[0].quantity, 10);
     }
 
     #[test]
@@ -903,6 +1524,147 @@ mod tests {
         assert_eq!(objects.len(), 0, "Character should have no objects after removal");
     }
 
+    // ==================== TRANSACTION TESTS ====================
+
+    #[test]
+    fn test_transaction_commits_on_success() {
+        let db = setup_test_db();
+
+        let sword_id = db
+            .transaction(|db| -> DbResult<i64> {
+                db.insert_character("Alice", "Test Game", None)?;
+                let sword_id = db.insert_object("Sword", "weapon", None)?;
+                db.add_object_to_character("Test Game", "Alice", sword_id, 1)?;
+                Ok(sword_id)
+            })
+            .expect("Transaction should commit");
+
+        let objects = db.get_character_objects("Test Game", "Alice").unwrap();
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0].object_id, sword_id);
+    }
+
+    #[test]
+    fn test_transaction_rolls_back_on_mid_operation_error() {
+        let db = setup_test_db();
+        db.insert_character("Alice", "Test Game", None).unwrap();
+        let potion_id = db.insert_object("Potion", "consumable", None).unwrap();
+        db.add_object_to_character("Test Game", "Alice", potion_id, 5).unwrap();
+
+        // Spend the potions, then fail on the second step (unknown recipient)
+        let result = db.transaction(|db| -> DbResult<()> {
+            db.update_object_quantity("Test Game", "Alice", potion_id, 0)?;
+            db.add_object_to_character("Test Game", "Nobody", potion_id, 5)?;
+            Ok(())
+        });
+
+        assert!(matches!(result, Err(DbError::ForeignKeyViolation(_))));
+        let objects = db.get_character_objects("Test Game", "Alice").unwrap();
+        assert_eq!(objects[0].quantity, 5, "First step should have been rolled back");
+    }
+
+    #[test]
+    fn test_transaction_rolls_back_on_custom_error() {
+        #[derive(Debug)]
+        enum GameError {
+            NotEnoughXp,
+            #[allow(dead_code)]
+            Db(DbError),
+        }
+        impl From<DbError> for GameError {
+            fn from(err: DbError) -> Self {
+                GameError::Db(err)
+            }
+        }
+
+        let db = setup_test_db();
+        db.insert_character("Alice", "Test Game", Some(r#"{"xp": 3}"#)).unwrap();
+
+        let result = db.transaction(|db| {
+            db.update_character("Alice", "Test Game", r#"{"xp": -2}"#)?;
+            Err::<(), _>(GameError::NotEnoughXp)
+        });
+
+        assert!(matches!(result, Err(GameError::NotEnoughXp)));
+        let alice = db.get_character("Alice", "Test Game").unwrap().unwrap();
+        assert_eq!(alice.data, Some(r#"{"xp": 3}"#.to_string()));
+    }
+
+    #[test]
+    fn test_transaction_rolls_back_on_panic() {
+        let db = setup_test_db();
+
+        let outcome = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            db.transaction(|db| -> DbResult<()> {
+                db.insert_character("Doomed", "Test Game", None)?;
+                panic!("simulated crash mid-operation");
+            })
+        }));
+
+        assert!(outcome.is_err());
+        assert!(db.get_character("Doomed", "Test Game").unwrap().is_none());
+        // The connection is usable again afterwards
+        db.insert_character("Survivor", "Test Game", None).unwrap();
+    }
+
+    #[test]
+    fn test_nested_transaction_failure_only_undoes_inner_work() {
+        let db = setup_test_db();
+
+        db.transaction(|db| -> DbResult<()> {
+            db.insert_character("Outer", "Test Game", None)?;
+            let inner = db.transaction(|db| -> DbResult<()> {
+                db.insert_character("Inner", "Test Game", None)?;
+                Err(DbError::not_found("forced failure"))
+            });
+            assert!(inner.is_err());
+            Ok(())
+        })
+        .unwrap();
+
+        assert!(db.get_character("Outer", "Test Game").unwrap().is_some());
+        assert!(db.get_character("Inner", "Test Game").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_nested_transaction_error_propagated_rolls_back_everything() {
+        let db = setup_test_db();
+
+        let result = db.transaction(|db| -> DbResult<()> {
+            db.insert_character("Outer", "Test Game", None)?;
+            db.transaction(|db| -> DbResult<()> {
+                db.insert_character("Inner", "Test Game", None)?;
+                db.insert_character("Inner", "Test Game", None)?;
+                Ok(())
+            })
+        });
+
+        assert!(matches!(result, Err(DbError::Duplicate(_))));
+        assert!(db.get_character("Outer", "Test Game").unwrap().is_none());
+        assert!(db.get_character("Inner", "Test Game").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_failed_commit_rolls_back_and_resets_depth() {
+        let db = setup_test_db();
+        let sword_id = db.insert_object("Sword", "weapon", None).unwrap();
+
+        // A deferred foreign key violation only surfaces at COMMIT, which
+        // fails and leaves the transaction open
+        let result = db.transaction(|db| -> DbResult<()> {
+            db.conn.execute_batch("PRAGMA defer_foreign_keys = ON")?;
+            db.add_object_to_character("Test Game", "Nobody", sword_id, 1)?;
+            Ok(())
+        });
+
+        assert!(result.is_err());
+        assert!(db.conn.is_autocommit(), "The guard should roll back");
+        assert_eq!(db.tx_depth.get(), 0);
+        db.transaction(|db| db.insert_character("Alice", "Test Game", None))
+            .unwrap();
+        assert!(db.get_character("Alice", "Test Game").unwrap().is_some());
+    }
+
     // ==================== INTEGRATION TESTS ====================
 
     #[test]
@@ -918,7 +1680,7 @@ mod tests {
         let character = db.get_character("Hero", "Epic Quest")
             .expect("Failed to get")
             .expect("Character should exist");
-        assert_eq!(character.0, id);
+        assert_eq!(character.uuid, id);
 
         // Update
         db.update_character("Hero", "Epic Quest", r#"{"level": 50}"#)
@@ -928,7 +1690,7 @@ mod tests {
         let updated = db.get_character("Hero", "Epic Quest")
             .unwrap()
             .unwrap();
-        assert_eq!(updated.3, Some(r#"{"level": 50}"#.to_string()));
+        assert_eq!(updated.data, Some(r#"{"level": 50}"#.to_string()));
 
         // Delete
         db.delete_character("Hero", "Epic Quest")
@@ -967,9 +1729,9 @@ mod tests {
         // Verify potion quantity
         let updated_inventory = db.get_character_objects("Dungeon Crawler", "Adventurer").unwrap();
         let potion_entry = updated_inventory.iter()
-            .find(|(id, _, _, _, _)| *id == potion_id)
+            .find(|entry| entry.object_id == potion_id)
             .expect("Potion should exist");
-        assert_eq!(potion_entry.3, 3);
+        assert_eq!(potion_entry.quantity, 3);
 
         // Sell sword (remove from inventory)
         db.remove_object_from_character("Dungeon Crawler", "Adventurer", sword_id).unwrap();
@@ -4,9 +4,21 @@
 //! interactions/transactions between different game elements. It's not about
 //! traditional currency, but rather a system for associating and tracking
 //! any valued resources and their relationships.
+//!
+//! Quantities of a resource held by characters, factions and locations, and
+//! every change to them, are recorded in the double-entry ledger
+//! (`entities::ledger`).
 
-use uuid::Uuid;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::{OptionalExtension, Row};
 use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use super::database::Database;
+use super::error::{DbError, DbResult};
+use super::records::{Page, PageRequest};
 
 /// Represents a tracked resource or element in the game's resource management system.
 ///
@@ -21,7 +33,7 @@ use serde::{Deserialize, Serialize};
 /// * `id` - Unique identifier for this entity
 /// * `name` - The name/label of this resource
 /// * `value` - The quantitative value associated with this resource
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct EconomicEntity {
     /// Unique identifier for this economic entity
     pub id: EntityId,
@@ -31,8 +43,25 @@ pub struct EconomicEntity {
     pub value: i32,
 }
 
-// impl _EconomicEntity {
-// }
+impl EconomicEntity {
+    /// Creates an entity with a fresh ID.
+    pub fn new(name: impl Into<String>, value: i32) -> Self {
+        EconomicEntity {
+            id: EntityId::new(),
+            name: name.into(),
+            value,
+        }
+    }
+
+    /// Builds an entity from a row selected as `id, name, value`.
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(EconomicEntity {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            value: row.get(2)?,
+        })
+    }
+}
 
 /// A unique identifier for economic entities using UUIDs.
 ///
@@ -47,9 +76,13 @@ pub struct EconomicEntity {
 ///
 /// let id1 = EntityId::new();
 /// let id2 = EntityId::new();
-/// // id1 and id2 will have different unique values
+/// assert_ne!(id1, id2);
+///
+/// let parsed: EntityId = id1.to_string().parse().unwrap();
+/// assert_eq!(parsed, id1);
 /// ```
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
 pub struct EntityId(Uuid);
 
 impl EntityId {
@@ -63,7 +96,264 @@ impl EntityId {
     pub fn new() -> Self {
         EntityId(Uuid::new_v4())
     }
+
+    /// The underlying UUID.
+    pub fn as_uuid(&self) -> &Uuid {
+        &self.0
+    }
+}
+
+impl Default for EntityId {
+    /// A new random identifier, the same as `EntityId::new()`.
+    fn default() -> Self {
+        EntityId::new()
+    }
+}
+
+impl From<Uuid> for EntityId {
+    fn from(uuid: Uuid) -> Self {
+        EntityId(uuid)
+    }
+}
+
+impl fmt::Display for EntityId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.hyphenated().fmt(f)
+    }
+}
+
+impl FromStr for EntityId {
+    type Err = uuid::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Uuid::parse_str(s).map(EntityId)
+    }
+}
+
+impl ToSql for EntityId {
+    /// Stored as hyphenated text, matching the `characters.uuid` column.
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.to_string()))
+    }
+}
+
+impl FromSql for EntityId {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|err| FromSqlError::Other(Box::new(err)))
+    }
+}
+
+impl Database {
+    // ==================== ECONOMIC ENTITY METHODS ====================
+
+    /// Stores a new economic entity under its own ID.
+    ///
+    /// # Returns
+    ///
+    /// Returns `DbError::Duplicate` if an entity with the same ID already exists.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ttdigirpg::entities::database::Database;
+    /// use ttdigirpg::entities::economy::EconomicEntity;
+    ///
+    /// let db = Database::new(":memory:").unwrap();
+    /// let grain = EconomicEntity::new("Grain", 4);
+    /// db.insert_economic_entity(&grain).unwrap();
+    ///
+    /// assert_eq!(db.get_economic_entity(grain.id).unwrap(), Some(grain));
+    /// ```
+    pub fn insert_economic_entity(&self, entity: &EconomicEntity) -> DbResult<()> {
+        self.conn.execute(
+            "INSERT INTO economic_entities (id, name, value, updated_at)
+             VALUES (?1, ?2, ?3, datetime('now'))",
+            (entity.id, &entity.name, entity.value),
+        )?;
+        Ok(())
+    }
+
+    /// Retrieves an economic entity by ID.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(EconomicEntity)` if found, or `None` if not found.
+    pub fn get_economic_entity(&self, id: EntityId) -> DbResult<Option<EconomicEntity>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT id, name, value FROM economic_entities WHERE id = ?1",
+                [id],
+                EconomicEntity::from_row,
+            )
+            .optional()?)
+    }
+
+    /// Overwrites the stored name and value of an existing entity.
+    ///
+    /// # Returns
+    ///
+    /// Returns `DbError::NotFound` if no entity has this ID.
+    pub fn update_economic_entity(&self, entity: &EconomicEntity) -> DbResult<()> {
+        let updated = self.conn.execute(
+            "UPDATE economic_entities SET name = ?1, value = ?2, updated_at = datetime('now')
+             WHERE id = ?3",
+            (&entity.name, entity.value, entity.id),
+        )?;
+        if updated == 0 {
+            return Err(DbError::not_found(format!("economic entity {}", entity.id)));
+        }
+        Ok(())
+    }
+
+    /// Sets just the value of an entity (e.g. a new market price).
+    ///
+    /// # Returns
+    ///
+    /// Returns `DbError::NotFound` if no entity has this ID.
+    pub fn set_economic_entity_value(&self, id: EntityId, value: i32) -> DbResult<()> {
+        let updated = self.conn.execute(
+            "UPDATE economic_entities SET value = ?1, updated_at = datetime('now') WHERE id = ?2",
+            (value, id),
+        )?;
+        if updated == 0 {
+            return Err(DbError::not_found(format!("economic entity {}", id)));
+        }
+        Ok(())
+    }
+
+    /// Deletes an economic entity.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of rows deleted (1 if successful, 0 if not found).
+    pub fn delete_economic_entity(&self, id: EntityId) -> DbResult<usize> {
+        Ok(self
+            .conn
+            .execute("DELETE FROM economic_entities WHERE id = ?1", [id])?)
+    }
+
+    /// Lists one page of economic entities ordered by name.
+    pub fn list_economic_entities(&self, page: &PageRequest) -> DbResult<Page<EconomicEntity>> {
+        self.page_economic_entities(None, page)
+    }
+
+    /// Lists one page of economic entities whose name contains `query`
+    /// (case-insensitive), ordered by name.
+    pub fn search_economic_entities(
+        &self,
+        query: &str,
+        page: &PageRequest,
+    ) -> DbResult<Page<EconomicEntity>> {
+        let escaped = query
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        self.page_economic_entities(Some(format!("%{}%", escaped)), page)
+    }
+
+    fn page_economic_entities(
+        &self,
+        pattern: Option<String>,
+        page: &PageRequest,
+    ) -> DbResult<Page<EconomicEntity>> {
+        const FILTER: &str = "?1 IS NULL OR name LIKE ?1 ESCAPE '\\'";
+
+        let sql = format!(
+            "SELECT id, name, value FROM economic_entities
+             WHERE {filter}
+             ORDER BY name {dir}, id {dir}
+             LIMIT ?2 OFFSET ?3",
+            filter = FILTER,
+            dir = page.direction()
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(
+            (&pattern, page.effective_limit(), page.offset),
+            EconomicEntity::from_row,
+        )?;
+        let items = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let total = self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM economic_entities WHERE {}", FILTER),
+            [&pattern],
+            |row| row.get(0),
+        )?;
+
+        Ok(Page {
+            items,
+            total,
+            limit: page.effective_limit(),
+            offset: page.offset,
+        })
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entity_id_text_round_trip() {
+        let id = EntityId::new();
+        let text = id.to_string();
+
+        assert_eq!(text.len(), 36);
+        assert_eq!(text.parse::<EntityId>().unwrap(), id);
+        assert!("not-a-uuid".parse::<EntityId>().is_err());
+        assert_eq!(serde_json::to_value(id).unwrap(), serde_json::json!(text));
+    }
+
+    #[test]
+    fn test_economic_entity_crud() {
+        let db = Database::new(":memory:").unwrap();
+        let mut salt = EconomicEntity::new("Salt", 3);
+        db.insert_economic_entity(&salt).unwrap();
+        assert!(matches!(
+            db.insert_economic_entity(&salt),
+            Err(DbError::Duplicate(_))
+        ));
+
+        salt.value = 5;
+        db.update_economic_entity(&salt).unwrap();
+        assert_eq!(db.get_economic_entity(salt.id).unwrap().unwrap().value, 5);
+
+        db.set_economic_entity_value(salt.id, 8).unwrap();
+        assert_eq!(db.get_economic_entity(salt.id).unwrap().unwrap().value, 8);
+
+        assert_eq!(db.delete_economic_entity(salt.id).unwrap(), 1);
+        assert_eq!(db.get_economic_entity(salt.id).unwrap(), None);
+        assert!(matches!(
+            db.update_economic_entity(&salt),
+            Err(DbError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_list_and_search() {
+        let db = Database::new(":memory:").unwrap();
+        for (name, value) in [("Silk", 40), ("Salt", 3), ("Iron Ore", 6), ("100% Wool", 9)] {
+            db.insert_economic_entity(&EconomicEntity::new(name, value))
+                .unwrap();
+        }
+
+        let page = db.list_economic_entities(&PageRequest::new(2, 1)).unwrap();
+        assert_eq!(page.total, 4);
+        let names: Vec<_> = page.items.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["Iron Ore", "Salt"]);
 
+        let found = db
+            .search_economic_entities("s", &PageRequest::default())
+            .unwrap();
+        assert_eq!(found.total, 2);
 
+        let literal = db
+            .search_economic_entities("0%", &PageRequest::default())
+            .unwrap();
+        assert_eq!(literal.items.len(), 1);
+        assert_eq!(literal.items[0].name, "100% Wool");
+    }
+}
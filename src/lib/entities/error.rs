@@ -0,0 +1,157 @@
+//! Error type for the database layer.
+//!
+//! `DbError` turns SQLite constraint failures into semantic variants so
+//! callers (and the API layer) can tell "not found", "duplicate" and
+//! "foreign key violation" apart without inspecting SQLite error codes.
+
+use rusqlite::ffi;
+use std::fmt;
+
+/// Errors returned by `Database` methods.
+#[derive(Debug)]
+pub enum DbError {
+    /// The requested row does not exist
+    NotFound(String),
+    /// A UNIQUE or PRIMARY KEY constraint rejected the write
+    /// (e.g. a character name already used in this game)
+    Duplicate(String),
+    /// A FOREIGN KEY constraint rejected the write
+    /// (e.g. giving an object to a character that doesn't exist)
+    ForeignKeyViolation(String),
+    /// A game rule refused the operation (e.g. transferring more items than owned)
+    InvalidOperation(String),
+    /// No connection could be used (e.g. a pooled worker panicked)
+    Unavailable(String),
+    /// Any other SQLite failure
+    Sqlite(rusqlite::Error),
+}
+
+/// Shorthand for results produced by the database layer.
+pub type DbResult<T> = std::result::Result<T, DbError>;
+
+impl DbError {
+    /// Creates a `NotFound` error describing the missing row.
+    pub fn not_found(what: impl Into<String>) -> Self {
+        DbError::NotFound(what.into())
+    }
+
+    /// Creates an `InvalidOperation` error explaining which rule was broken.
+    pub fn invalid(reason: impl Into<String>) -> Self {
+        DbError::InvalidOperation(reason.into())
+    }
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::NotFound(what) => write!(f, "Not found: {}", what),
+            DbError::Duplicate(msg) => write!(f, "Duplicate entry: {}", msg),
+            DbError::ForeignKeyViolation(msg) => write!(f, "Invalid reference: {}", msg),
+            DbError::InvalidOperation(msg) => write!(f, "Invalid operation: {}", msg),
+            DbError::Unavailable(msg) => write!(f, "Database unavailable: {}", msg),
+            DbError::Sqlite(err) => write!(f, "Database error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for DbError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DbError::Sqlite(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for DbError {
+    /// Maps constraint failures to semantic variants; everything else is wrapped.
+    fn from(err: rusqlite::Error) -> Self {
+        match &err {
+            rusqlite::Error::QueryReturnedNoRows => {
+                DbError::NotFound("query returned no rows".to_string())
+            }
+            rusqlite::Error::SqliteFailure(code, msg) => {
+                let detail = msg.clone().unwrap_or_else(|| code.to_string());
+                match code.extended_code {
+                    ffi::SQLITE_CONSTRAINT_UNIQUE | ffi::SQLITE_CONSTRAINT_PRIMARYKEY => {
+                        DbError::Duplicate(detail)
+                    }
+                    ffi::SQLITE_CONSTRAINT_FOREIGNKEY => DbError::ForeignKeyViolation(detail),
+                    _ => DbError::Sqlite(err),
+                }
+            }
+            _ => DbError::Sqlite(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+
+    fn setup_conn() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "PRAGMA foreign_keys = ON;
+             CREATE TABLE parent (id INTEGER PRIMARY KEY, name TEXT UNIQUE);
+             CREATE TABLE child (parent_id INTEGER REFERENCES parent(id));
+             INSERT INTO parent (id, name) VALUES (1, 'a');",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_unique_violation_maps_to_duplicate() {
+        let conn = setup_conn();
+        let err = conn
+            .execute("INSERT INTO parent (id, name) VALUES (2, 'a')", [])
+            .unwrap_err();
+
+        assert!(matches!(DbError::from(err), DbError::Duplicate(_)));
+    }
+
+    #[test]
+    fn test_primary_key_violation_maps_to_duplicate() {
+        let conn = setup_conn();
+        let err = conn
+            .execute("INSERT INTO parent (id, name) VALUES (1, 'b')", [])
+            .unwrap_err();
+
+        assert!(matches!(DbError::from(err), DbError::Duplicate(_)));
+    }
+
+    #[test]
+    fn test_foreign_key_violation_maps() {
+        let conn = setup_conn();
+        let err = conn
+            .execute("INSERT INTO child (parent_id) VALUES (99)", [])
+            .unwrap_err();
+
+        assert!(matches!(
+            DbError::from(err),
+            DbError::ForeignKeyViolation(_)
+        ));
+    }
+
+    #[test]
+    fn test_no_rows_maps_to_not_found() {
+        let conn = setup_conn();
+        let err = conn
+            .query_row("SELECT id FROM parent WHERE id = 42", [], |row| {
+                row.get::<_, i64>(0)
+            })
+            .unwrap_err();
+
+        assert!(matches!(DbError::from(err), DbError::NotFound(_)));
+    }
+
+    #[test]
+    fn test_other_errors_are_wrapped() {
+        let conn = setup_conn();
+        let err = conn.execute("SELECT * FROM missing_table", []).unwrap_err();
+
+        assert!(matches!(DbError::from(err), DbError::Sqlite(_)));
+    }
+}
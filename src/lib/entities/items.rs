@@ -0,0 +1,522 @@
+//! Item instances: individual copies of an object template.
+//!
+//! Rows in `objects` are templates ("Sword"). An item instance is one
+//! particular sword, with its own durability, charges, name and notes. Any
+//! per-copy field left unset falls back to the template, so editing a template
+//! changes every copy that hasn't been individually modified while leaving
+//! damaged, enchanted or renamed copies alone.
+
+use rusqlite::{OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::database::Database;
+use super::error::{DbError, DbResult};
+
+/// Columns selected by every instance query, in `RawInstance::from_row` order.
+const INSTANCE_COLUMNS: &str = "i.id, i.object_id, i.game, i.character_name, i.custom_name,
+     i.durability, i.charges, i.notes, i.overrides, o.name, o.type, o.properties,
+     i.container_id, i.location";
+
+/// An item instance with its template already applied.
+///
+/// `name`, `durability`, `charges` and `properties` are the effective values:
+/// per-copy state where it has been set, template values everywhere else.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItemInstance {
+    /// Auto-incremented instance ID
+    pub id: i64,
+    /// ID of the template this is a copy of
+    pub object_id: i64,
+    /// The game this instance belongs to
+    pub game: String,
+    /// Name of the owning character, if a character holds it
+    pub character_name: Option<String>,
+    /// ID of the container instance it is stored in, if any
+    pub container_id: Option<i64>,
+    /// Location it has been left at (e.g. a hideout), if any
+    pub location: Option<String>,
+    /// Custom name if set, otherwise the template's name
+    pub name: String,
+    /// The template's type
+    #[serde(rename = "type")]
+    pub obj_type: String,
+    /// Current durability (per-copy, or the template's `durability` property)
+    pub durability: Option<i64>,
+    /// Remaining charges (per-copy, or the template's `charges` property)
+    pub charges: Option<i64>,
+    /// Free-form notes about this copy
+    pub notes: Option<String>,
+    /// Template properties with this copy's overrides applied
+    pub properties: Value,
+    /// Names of the fields this copy has modified
+    pub modified: Vec<String>,
+}
+
+impl ItemInstance {
+    /// Weight of this copy alone in kilograms (its `weight` property, or 0).
+    pub fn weight(&self) -> f64 {
+        self.number("weight").unwrap_or(0.0)
+    }
+
+    /// Kilograms of contents this copy can hold, or `None` if it isn't a container.
+    ///
+    /// Any object whose effective properties include `"capacity"` is a container.
+    pub fn capacity(&self) -> Option<f64> {
+        self.number("capacity")
+    }
+
+    /// Maximum number of items directly inside this container, if limited.
+    pub fn slots(&self) -> Option<u64> {
+        self.properties.get("slots").and_then(Value::as_u64)
+    }
+
+    fn number(&self, key: &str) -> Option<f64> {
+        self.properties
+            .get(key)
+            .and_then(Value::as_f64)
+            .map(|value| value.max(0.0))
+    }
+}
+
+/// An instance row joined with its template, before resolution.
+struct RawInstance {
+    id: i64,
+    object_id: i64,
+    game: String,
+    character_name: Option<String>,
+    container_id: Option<i64>,
+    location: Option<String>,
+    custom_name: Option<String>,
+    durability: Option<i64>,
+    charges: Option<i64>,
+    notes: Option<String>,
+    overrides: Option<String>,
+    template_name: String,
+    template_type: String,
+    template_properties: Option<String>,
+}
+
+impl RawInstance {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(RawInstance {
+            id: row.get(0)?,
+            object_id: row.get(1)?,
+            game: row.get(2)?,
+            character_name: row.get(3)?,
+            custom_name: row.get(4)?,
+            durability: row.get(5)?,
+            charges: row.get(6)?,
+            notes: row.get(7)?,
+            overrides: row.get(8)?,
+            template_name: row.get(9)?,
+            template_type: row.get(10)?,
+            template_properties: row.get(11)?,
+            container_id: row.get(12)?,
+            location: row.get(13)?,
+        })
+    }
+
+    /// Layers the per-copy state over the template.
+    fn resolve(self) -> ItemInstance {
+        let mut properties = parse_object(self.template_properties.as_deref());
+        let overrides = parse_object(self.overrides.as_deref());
+
+        let mut modified = Vec::new();
+        if self.custom_name.is_some() {
+            modified.push("name".to_string());
+        }
+        if self.durability.is_some() {
+            modified.push("durability".to_string());
+        }
+        if self.charges.is_some() {
+            modified.push("charges".to_string());
+        }
+        if self.notes.is_some() {
+            modified.push("notes".to_string());
+        }
+        for (key, value) in overrides {
+            modified.push(key.clone());
+            properties.insert(key, value);
+        }
+
+        let durability = self
+            .durability
+            .or_else(|| properties.get("durability").and_then(Value::as_i64));
+        let charges = self
+            .charges
+            .or_else(|| properties.get("charges").and_then(Value::as_i64));
+
+        ItemInstance {
+            id: self.id,
+            object_id: self.object_id,
+            game: self.game,
+            character_name: self.character_name,
+            container_id: self.container_id,
+            location: self.location,
+            name: self.custom_name.unwrap_or(self.template_name),
+            obj_type: self.template_type,
+            durability,
+            charges,
+            notes: self.notes,
+            properties: Value::Object(properties),
+            modified,
+        }
+    }
+}
+
+/// Parses a JSON object column, treating NULL or non-object JSON as empty.
+fn parse_object(json: Option<&str>) -> Map<String, Value> {
+    match json.map(serde_json::from_str::<Value>) {
+        Some(Ok(Value::Object(map))) => map,
+        _ => Map::new(),
+    }
+}
+
+impl Database {
+    // ==================== ITEM INSTANCE METHODS ====================
+
+    /// Creates a new, unmodified copy of an object template.
+    ///
+    /// # Arguments
+    ///
+    /// * `object_id` - The template to copy
+    /// * `game` - The game the instance belongs to
+    /// * `character_name` - The owning character, or `None` for an unowned item
+    ///
+    /// # Returns
+    ///
+    /// Returns the ID of the new instance, or `DbError::ForeignKeyViolation` if
+    /// the template or character doesn't exist.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ttdigirpg::entities::database::Database;
+    ///
+    /// let db = Database::new(":memory:").unwrap();
+    /// db.insert_character("Alice", "Knives Out", None).unwrap();
+    /// let sword = db.insert_object("Sword", "weapon", Some(r#"{"durability": 100}"#)).unwrap();
+    ///
+    /// let copy = db.create_item_instance(sword, "Knives Out", Some("Alice")).unwrap();
+    /// db.set_instance_durability(copy, Some(40)).unwrap();
+    ///
+    /// let item = db.get_item_instance(copy).unwrap().unwrap();
+    /// assert_eq!(item.durability, Some(40));
+    /// ```
+    pub fn create_item_instance(
+        &self,
+        object_id: i64,
+        game: &str,
+        character_name: Option<&str>,
+    ) -> DbResult<i64> {
+        self.conn.execute(
+            "INSERT INTO item_instances (object_id, game, character_name) VALUES (?1, ?2, ?3)",
+            (object_id, game, character_name),
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Retrieves an instance with its template applied.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(ItemInstance)` if found, or `None` if not found.
+    pub fn get_item_instance(&self, instance_id: i64) -> DbResult<Option<ItemInstance>> {
+        let sql = format!(
+            "SELECT {} FROM item_instances i JOIN objects o ON i.object_id = o.id WHERE i.id = ?1",
+            INSTANCE_COLUMNS
+        );
+        let raw = self
+            .conn
+            .query_row(&sql, [instance_id], RawInstance::from_row)
+            .optional()?;
+        Ok(raw.map(RawInstance::resolve))
+    }
+
+    /// Lists every instance a character holds directly, with templates applied.
+    ///
+    /// Items packed inside a held container are not included; see
+    /// `get_carried_instances` for everything the character is carrying.
+    pub fn get_character_instances(
+        &self,
+        game: &str,
+        character_name: &str,
+    ) -> DbResult<Vec<ItemInstance>> {
+        self.query_instances(
+            "WHERE i.game = ?1 AND i.character_name = ?2",
+            (game, character_name),
+        )
+    }
+
+    /// Runs an instance query with the given clause after the template join.
+    ///
+    /// The clause (typically a `WHERE`) refers to the instance as `i` and uses
+    /// `?N` placeholders bound from `params`. Results are ordered by ID.
+    pub(crate) fn query_instances<P: rusqlite::Params>(
+        &self,
+        clause: &str,
+        params: P,
+    ) -> DbResult<Vec<ItemInstance>> {
+        let sql = format!(
+            "SELECT {} FROM item_instances i JOIN objects o ON i.object_id = o.id {} ORDER BY i.id",
+            INSTANCE_COLUMNS, clause
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(params, RawInstance::from_row)?;
+
+        let mut items = Vec::new();
+        for row in rows {
+            items.push(row?.resolve());
+        }
+        Ok(items)
+    }
+
+    /// Gives this copy its own name, or reverts to the template's with `None`.
+    pub fn set_instance_custom_name(&self, instance_id: i64, name: Option<&str>) -> DbResult<()> {
+        self.update_instance_column(instance_id, "custom_name", name)
+    }
+
+    /// Sets this copy's durability, or reverts to the template's with `None`.
+    pub fn set_instance_durability(
+        &self,
+        instance_id: i64,
+        durability: Option<i64>,
+    ) -> DbResult<()> {
+        self.update_instance_column(instance_id, "durability", durability)
+    }
+
+    /// Sets this copy's charges, or reverts to the template's with `None`.
+    pub fn set_instance_charges(&self, instance_id: i64, charges: Option<i64>) -> DbResult<()> {
+        self.update_instance_column(instance_id, "charges", charges)
+    }
+
+    /// Sets free-form notes on this copy, or clears them with `None`.
+    pub fn set_instance_notes(&self, instance_id: i64, notes: Option<&str>) -> DbResult<()> {
+        self.update_instance_column(instance_id, "notes", notes)
+    }
+
+    /// Overrides one template property on this copy (e.g. an enchantment bonus).
+    ///
+    /// Passing `None` removes the override so the template's value shows through again.
+    pub fn set_instance_property(
+        &self,
+        instance_id: i64,
+        key: &str,
+        value: Option<Value>,
+    ) -> DbResult<()> {
+        self.transaction(|db| {
+            let current: Option<String> = db
+                .conn
+                .query_row(
+                    "SELECT overrides FROM item_instances WHERE id = ?1",
+                    [instance_id],
+                    |row| row.get(0),
+                )
+                .optional()?
+                .ok_or_else(|| DbError::not_found(format!("item instance {}", instance_id)))?;
+
+            let mut overrides = parse_object(current.as_deref());
+            match value {
+                Some(value) => overrides.insert(key.to_string(), value),
+                None => overrides.remove(key),
+            };
+            let stored = if overrides.is_empty() {
+                None
+            } else {
+                Some(Value::Object(overrides).to_string())
+            };
+
+            db.update_instance_column(instance_id, "overrides", stored)
+        })
+    }
+
+    /// Deletes an item instance.
+    ///
+    /// If the instance is a container, its contents are not lost: they move to
+    /// wherever the container was (its holder, location or parent container).
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of rows deleted (1 if successful, 0 if not found).
+    pub fn delete_item_instance(&self, instance_id: i64) -> DbResult<usize> {
+        self.transaction(|db| {
+            db.conn.execute(
+                "UPDATE item_instances
+                 SET (character_name, container_id, location) = (
+                     SELECT character_name, container_id, location
+                     FROM item_instances WHERE id = ?1
+                 )
+                 WHERE container_id = ?1",
+                [instance_id],
+            )?;
+            Ok(db
+                .conn
+                .execute("DELETE FROM item_instances WHERE id = ?1", [instance_id])?)
+        })
+    }
+
+    /// Writes a single per-copy column, reporting a missing instance as `NotFound`.
+    fn update_instance_column<V: rusqlite::ToSql>(
+        &self,
+        instance_id: i64,
+        column: &str,
+        value: V,
+    ) -> DbResult<()> {
+        let sql = format!("UPDATE item_instances SET {} = ?1 WHERE id = ?2", column);
+        let updated = self.conn.execute(&sql, (value, instance_id))?;
+        if updated == 0 {
+            return Err(DbError::not_found(format!("item instance {}", instance_id)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn setup_sword() -> (Database, i64) {
+        let db = Database::new(":memory:").unwrap();
+        db.insert_character("Alice", "Game", None).unwrap();
+        db.insert_character("Bob", "Game", None).unwrap();
+        let sword = db
+            .insert_object(
+                "Sword",
+                "weapon",
+                Some(r#"{"damage": 10, "durability": 100}"#),
+            )
+            .unwrap();
+        (db, sword)
+    }
+
+    #[test]
+    fn test_new_instance_reads_template() {
+        let (db, sword) = setup_sword();
+        let id = db
+            .create_item_instance(sword, "Game", Some("Alice"))
+            .unwrap();
+
+        let item = db.get_item_instance(id).unwrap().unwrap();
+        assert_eq!(item.name, "Sword");
+        assert_eq!(item.obj_type, "weapon");
+        assert_eq!(item.durability, Some(100));
+        assert_eq!(item.charges, None);
+        assert_eq!(item.properties["damage"], 10);
+        assert!(item.modified.is_empty());
+    }
+
+    #[test]
+    fn test_two_copies_hold_independent_state() {
+        let (db, sword) = setup_sword();
+        let alices = db
+            .create_item_instance(sword, "Game", Some("Alice"))
+            .unwrap();
+        let bobs = db.create_item_instance(sword, "Game", Some("Bob")).unwrap();
+
+        db.set_instance_durability(alices, Some(35)).unwrap();
+        db.set_instance_custom_name(bobs, Some("Widowmaker"))
+            .unwrap();
+        db.set_instance_property(bobs, "enchantment", Some(json!("fire")))
+            .unwrap();
+
+        let alices = db.get_item_instance(alices).unwrap().unwrap();
+        let bobs = db.get_item_instance(bobs).unwrap().unwrap();
+        assert_eq!(alices.durability, Some(35));
+        assert_eq!(alices.name, "Sword");
+        assert_eq!(bobs.durability, Some(100));
+        assert_eq!(bobs.name, "Widowmaker");
+        assert_eq!(bobs.properties["enchantment"], "fire");
+        assert!(alices.properties.get("enchantment").is_none());
+    }
+
+    #[test]
+    fn test_template_edit_only_reaches_unmodified_fields() {
+        let (db, sword) = setup_sword();
+        let damaged = db
+            .create_item_instance(sword, "Game", Some("Alice"))
+            .unwrap();
+        let pristine = db.create_item_instance(sword, "Game", Some("Bob")).unwrap();
+        db.set_instance_durability(damaged, Some(40)).unwrap();
+        db.set_instance_property(damaged, "damage", Some(json!(14)))
+            .unwrap();
+
+        db.update_object(sword, r#"{"damage": 12, "durability": 120}"#)
+            .unwrap();
+
+        let damaged = db.get_item_instance(damaged).unwrap().unwrap();
+        let pristine = db.get_item_instance(pristine).unwrap().unwrap();
+        assert_eq!(damaged.durability, Some(40), "Per-copy durability is kept");
+        assert_eq!(
+            damaged.properties["damage"], 14,
+            "Per-copy override is kept"
+        );
+        assert_eq!(pristine.durability, Some(120));
+        assert_eq!(pristine.properties["damage"], 12);
+        assert_eq!(damaged.modified, vec!["durability", "damage"]);
+    }
+
+    #[test]
+    fn test_clearing_a_field_reverts_to_template() {
+        let (db, sword) = setup_sword();
+        let id = db
+            .create_item_instance(sword, "Game", Some("Alice"))
+            .unwrap();
+        db.set_instance_durability(id, Some(1)).unwrap();
+        db.set_instance_property(id, "damage", Some(json!(1)))
+            .unwrap();
+
+        db.set_instance_durability(id, None).unwrap();
+        db.set_instance_property(id, "damage", None).unwrap();
+
+        let item = db.get_item_instance(id).unwrap().unwrap();
+        assert_eq!(item.durability, Some(100));
+        assert_eq!(item.properties["damage"], 10);
+        assert!(item.modified.is_empty());
+    }
+
+    #[test]
+    fn test_character_instances_and_cascade() {
+        let (db, sword) = setup_sword();
+        db.create_item_instance(sword, "Game", Some("Alice"))
+            .unwrap();
+        db.create_item_instance(sword, "Game", Some("Alice"))
+            .unwrap();
+        db.create_item_instance(sword, "Game", Some("Bob")).unwrap();
+
+        assert_eq!(
+            db.get_character_instances("Game", "Alice").unwrap().len(),
+            2
+        );
+
+        db.delete_character("Alice", "Game").unwrap();
+        assert!(db
+            .get_character_instances("Game", "Alice")
+            .unwrap()
+            .is_empty());
+        assert_eq!(db.get_character_instances("Game", "Bob").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_missing_references_and_instances() {
+        let (db, sword) = setup_sword();
+
+        assert!(matches!(
+            db.create_item_instance(sword, "Game", Some("Nobody")),
+            Err(DbError::ForeignKeyViolation(_))
+        ));
+        assert!(matches!(
+            db.create_item_instance(999, "Game", None),
+            Err(DbError::ForeignKeyViolation(_))
+        ));
+        assert!(matches!(
+            db.set_instance_notes(999, Some("lost")),
+            Err(DbError::NotFound(_))
+        ));
+        assert!(matches!(
+            db.set_instance_property(999, "damage", None),
+            Err(DbError::NotFound(_))
+        ));
+    }
+}
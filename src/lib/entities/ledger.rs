@@ -0,0 +1,610 @@
+//! Double-entry ledger for tracked resources.
+//!
+//! Resources such as cash, influence or favours live in accounts, one per
+//! owner and resource. Every change is a transaction made of postings that
+//! add to or take from accounts, and the postings for each resource must sum
+//! to zero: whatever one account gains another loses. Resources entering or
+//! leaving play come from or go to a `World` account, so the journal always
+//! balances and any balance can be rebuilt at any point in time.
+//!
+//! Journal rows are never updated or deleted. Corrections are new
+//! transactions that reverse the mistake.
+
+use rusqlite::{OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::database::{unix_now, Database};
+use super::error::{DbError, DbResult};
+
+/// Who holds a ledger account.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "id", rename_all = "lowercase")]
+pub enum AccountOwner {
+    /// A character, by UUID
+    Character(String),
+    /// A faction, by name
+    Faction(String),
+    /// A location, by name
+    Location(String),
+    /// The game world: where new resources come from and spent ones go
+    World,
+}
+
+impl AccountOwner {
+    /// The `(owner_kind, owner_id)` pair stored for this owner.
+    fn columns(&self) -> (&'static str, &str) {
+        match self {
+            AccountOwner::Character(uuid) => ("character", uuid),
+            AccountOwner::Faction(name) => ("faction", name),
+            AccountOwner::Location(name) => ("location", name),
+            AccountOwner::World => ("world", ""),
+        }
+    }
+
+    fn from_columns(kind: &str, id: String) -> Option<Self> {
+        match kind {
+            "character" => Some(AccountOwner::Character(id)),
+            "faction" => Some(AccountOwner::Faction(id)),
+            "location" => Some(AccountOwner::Location(id)),
+            "world" => Some(AccountOwner::World),
+            _ => None,
+        }
+    }
+}
+
+/// A row of the `ledger_accounts` table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Account {
+    /// Auto-incremented account ID
+    pub id: i64,
+    /// The game the account belongs to
+    pub game: String,
+    /// Who holds the account
+    pub owner: AccountOwner,
+    /// What the account holds (e.g. "cash", "influence")
+    pub resource: String,
+    /// When the account was opened (Unix seconds)
+    pub created_at: i64,
+}
+
+impl Account {
+    /// Builds an account from a row selected as
+    /// `id, game, owner_kind, owner_id, resource, created_at`.
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let kind: String = row.get(2)?;
+        let owner = AccountOwner::from_columns(&kind, row.get(3)?).ok_or_else(|| {
+            rusqlite::Error::FromSqlConversionFailure(
+                2,
+                rusqlite::types::Type::Text,
+                format!("Unknown account owner kind: {}", kind).into(),
+            )
+        })?;
+
+        Ok(Account {
+            id: row.get(0)?,
+            game: row.get(1)?,
+            owner,
+            resource: row.get(4)?,
+            created_at: row.get(5)?,
+        })
+    }
+}
+
+/// One line of a transaction: an amount added to (positive) or taken from
+/// (negative) an account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Posting {
+    /// The account affected
+    pub account_id: i64,
+    /// Signed change to the account's balance
+    pub amount: i64,
+}
+
+impl Posting {
+    /// Creates a posting of `amount` to `account_id`.
+    pub fn new(account_id: i64, amount: i64) -> Self {
+        Posting { account_id, amount }
+    }
+}
+
+/// A recorded transaction with its postings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerTransaction {
+    /// Auto-incremented transaction ID
+    pub id: i64,
+    /// The game the transaction belongs to
+    pub game: String,
+    /// What happened (e.g. "Bribed the harbour master")
+    pub description: String,
+    /// When it happened (Unix seconds); balances "as of" a time use this
+    pub occurred_at: i64,
+    /// When it was written to the journal (Unix seconds)
+    pub recorded_at: i64,
+    /// The balanced postings
+    pub postings: Vec<Posting>,
+}
+
+/// One line of an account statement.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatementLine {
+    /// The transaction the posting belongs to
+    pub transaction_id: i64,
+    /// When the transaction happened (Unix seconds)
+    pub occurred_at: i64,
+    /// The transaction's description
+    pub description: String,
+    /// Signed change to this account
+    pub amount: i64,
+    /// The account's balance after this line
+    pub balance: i64,
+}
+
+impl Database {
+    // ==================== LEDGER METHODS ====================
+
+    /// Returns the ID of an owner's account for a resource, opening it on first use.
+    ///
+    /// Character owners must exist in the game.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ttdigirpg::entities::database::Database;
+    /// use ttdigirpg::entities::ledger::{AccountOwner, Posting};
+    ///
+    /// let db = Database::new(":memory:").unwrap();
+    /// let alice = db.insert_character("Alice", "Game", None).unwrap();
+    ///
+    /// let world = db.open_account("Game", &AccountOwner::World, "cash").unwrap();
+    /// let wallet = db.open_account("Game", &AccountOwner::Character(alice), "cash").unwrap();
+    /// db.record_transaction("Game", "Starting funds", 0, &[
+    ///     Posting::new(world, -100),
+    ///     Posting::new(wallet, 100),
+    /// ]).unwrap();
+    ///
+    /// assert_eq!(db.account_balance(wallet, None).unwrap(), 100);
+    /// ```
+    pub fn open_account(&self, game: &str, owner: &AccountOwner, resource: &str) -> DbResult<i64> {
+        if let AccountOwner::Character(uuid) = owner {
+            let character = self
+                .get_character_by_uuid(uuid)?
+                .ok_or_else(|| DbError::not_found(format!("character {}", uuid)))?;
+            if character.game != game {
+                return Err(DbError::invalid(format!(
+                    "{} is not in game {}",
+                    character.name, game
+                )));
+            }
+        }
+
+        let (kind, owner_id) = owner.columns();
+        self.conn.execute(
+            "INSERT OR IGNORE INTO ledger_accounts (game, owner_kind, owner_id, resource, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            (game, kind, owner_id, resource, unix_now()),
+        )?;
+        Ok(self.conn.query_row(
+            "SELECT id FROM ledger_accounts
+             WHERE game = ?1 AND owner_kind = ?2 AND owner_id = ?3 AND resource = ?4",
+            (game, kind, owner_id, resource),
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Retrieves an account by ID.
+    pub fn get_account(&self, account_id: i64) -> DbResult<Option<Account>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT id, game, owner_kind, owner_id, resource, created_at
+                 FROM ledger_accounts WHERE id = ?1",
+                [account_id],
+                Account::from_row,
+            )
+            .optional()?)
+    }
+
+    /// Lists the accounts in a game, optionally only those of one owner.
+    pub fn list_accounts(
+        &self,
+        game: &str,
+        owner: Option<&AccountOwner>,
+    ) -> DbResult<Vec<Account>> {
+        let (kind, owner_id) = match owner.map(AccountOwner::columns) {
+            Some((kind, id)) => (Some(kind), Some(id)),
+            None => (None, None),
+        };
+        let mut stmt = self.conn.prepare(
+            "SELECT id, game, owner_kind, owner_id, resource, created_at
+             FROM ledger_accounts
+             WHERE game = ?1 AND (?2 IS NULL OR (owner_kind = ?2 AND owner_id = ?3))
+             ORDER BY id",
+        )?;
+        let rows = stmt.query_map((game, kind, owner_id), Account::from_row)?;
+
+        let mut accounts = Vec::new();
+        for row in rows {
+            accounts.push(row?);
+        }
+        Ok(accounts)
+    }
+
+    /// Writes a balanced transaction to the journal.
+    ///
+    /// # Arguments
+    ///
+    /// * `game` - The game every posted account must belong to
+    /// * `description` - What happened
+    /// * `occurred_at` - When it happened (Unix seconds); may be in the past
+    /// * `postings` - At least two non-zero postings
+    ///
+    /// # Returns
+    ///
+    /// Returns the transaction ID, or `DbError::InvalidOperation` if the
+    /// postings don't sum to zero for every resource, an account is in
+    /// another game, or a posting is zero. Balances may go negative (a
+    /// favour owed is a negative favour balance).
+    pub fn record_transaction(
+        &self,
+        game: &str,
+        description: &str,
+        occurred_at: i64,
+        postings: &[Posting],
+    ) -> DbResult<i64> {
+        if postings.len() < 2 {
+            return Err(DbError::invalid(
+                "a transaction needs at least two postings",
+            ));
+        }
+        if postings.iter().any(|posting| posting.amount == 0) {
+            return Err(DbError::invalid("postings must not be zero"));
+        }
+
+        self.transaction(|db| {
+            let mut totals: HashMap<String, i64> = HashMap::new();
+            for posting in postings {
+                let account = db.get_account(posting.account_id)?.ok_or_else(|| {
+                    DbError::not_found(format!("ledger account {}", posting.account_id))
+                })?;
+                if account.game != game {
+                    return Err(DbError::invalid(format!(
+                        "ledger account {} belongs to game {}",
+                        account.id, account.game
+                    )));
+                }
+                let total = totals.entry(account.resource).or_default();
+                *total = total
+                    .checked_add(posting.amount)
+                    .ok_or_else(|| DbError::invalid("posting amounts overflow"))?;
+            }
+            let mut unbalanced: Vec<_> = totals
+                .into_iter()
+                .filter(|(_, total)| *total != 0)
+                .map(|(resource, total)| format!("{} off by {}", resource, total))
+                .collect();
+            if !unbalanced.is_empty() {
+                unbalanced.sort();
+                return Err(DbError::invalid(format!(
+                    "postings do not balance: {}",
+                    unbalanced.join(", ")
+                )));
+            }
+
+            let transaction_id: i64 = db.conn.query_row(
+                "INSERT INTO ledger_transactions (game, description, occurred_at, recorded_at)
+                 VALUES (?1, ?2, ?3, ?4)
+                 RETURNING id",
+                (game, description, occurred_at, unix_now()),
+                |row| row.get(0),
+            )?;
+            for posting in postings {
+                db.conn.execute(
+                    "INSERT INTO ledger_postings (transaction_id, account_id, amount)
+                     VALUES (?1, ?2, ?3)",
+                    (transaction_id, posting.account_id, posting.amount),
+                )?;
+            }
+            Ok(transaction_id)
+        })
+    }
+
+    /// Retrieves a transaction and its postings.
+    pub fn get_ledger_transaction(
+        &self,
+        transaction_id: i64,
+    ) -> DbResult<Option<LedgerTransaction>> {
+        let header = self
+            .conn
+            .query_row(
+                "SELECT id, game, description, occurred_at, recorded_at
+                 FROM ledger_transactions WHERE id = ?1",
+                [transaction_id],
+                |row| {
+                    Ok(LedgerTransaction {
+                        id: row.get(0)?,
+                        game: row.get(1)?,
+                        description: row.get(2)?,
+                        occurred_at: row.get(3)?,
+                        recorded_at: row.get(4)?,
+                        postings: Vec::new(),
+                    })
+                },
+            )
+            .optional()?;
+
+        let Some(mut transaction) = header else {
+            return Ok(None);
+        };
+
+        let mut stmt = self.conn.prepare(
+            "SELECT account_id, amount FROM ledger_postings WHERE transaction_id = ?1 ORDER BY id",
+        )?;
+        let rows = stmt.query_map([transaction_id], |row| {
+            Ok(Posting::new(row.get(0)?, row.get(1)?))
+        })?;
+        for row in rows {
+            transaction.postings.push(row?);
+        }
+
+        Ok(Some(transaction))
+    }
+
+    /// An account's balance, either now or as of a point in time.
+    ///
+    /// # Arguments
+    ///
+    /// * `account_id` - The account
+    /// * `as_of` - Only count transactions that occurred at or before this
+    ///   time (Unix seconds); `None` counts everything
+    pub fn account_balance(&self, account_id: i64, as_of: Option<i64>) -> DbResult<i64> {
+        if self.get_account(account_id)?.is_none() {
+            return Err(DbError::not_found(format!("ledger account {}", account_id)));
+        }
+
+        Ok(self.conn.query_row(
+            "SELECT COALESCE(SUM(p.amount), 0)
+             FROM ledger_postings p JOIN ledger_transactions t ON p.transaction_id = t.id
+             WHERE p.account_id = ?1 AND (?2 IS NULL OR t.occurred_at <= ?2)",
+            (account_id, as_of),
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Every posting to an account in the order it happened, with running balances.
+    pub fn account_statement(&self, account_id: i64) -> DbResult<Vec<StatementLine>> {
+        if self.get_account(account_id)?.is_none() {
+            return Err(DbError::not_found(format!("ledger account {}", account_id)));
+        }
+
+        let mut stmt = self.conn.prepare(
+            "SELECT t.id, t.occurred_at, t.description, p.amount
+             FROM ledger_postings p JOIN ledger_transactions t ON p.transaction_id = t.id
+             WHERE p.account_id = ?1
+             ORDER BY t.occurred_at, t.id, p.id",
+        )?;
+        let rows = stmt.query_map([account_id], |row| {
+            Ok(StatementLine {
+                transaction_id: row.get(0)?,
+                occurred_at: row.get(1)?,
+                description: row.get(2)?,
+                amount: row.get(3)?,
+                balance: 0,
+            })
+        })?;
+
+        let mut balance = 0;
+        let mut lines = Vec::new();
+        for row in rows {
+            let mut line = row?;
+            balance += line.amount;
+            line.balance = balance;
+            lines.push(line);
+        }
+        Ok(lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_ledger() -> (Database, i64, i64, i64) {
+        let db = Database::new(":memory:").unwrap();
+        let alice = db.insert_character("Alice", "Game", None).unwrap();
+        let world = db
+            .open_account("Game", &AccountOwner::World, "cash")
+            .unwrap();
+        let wallet = db
+            .open_account("Game", &AccountOwner::Character(alice), "cash")
+            .unwrap();
+        let guild = db
+            .open_account(
+                "Game",
+                &AccountOwner::Faction("Thieves' Guild".into()),
+                "cash",
+            )
+            .unwrap();
+        (db, world, wallet, guild)
+    }
+
+    #[test]
+    fn test_open_account_is_idempotent() {
+        let (db, world, _, _) = setup_ledger();
+        assert_eq!(
+            db.open_account("Game", &AccountOwner::World, "cash")
+                .unwrap(),
+            world
+        );
+        let influence = db
+            .open_account("Game", &AccountOwner::World, "influence")
+            .unwrap();
+        assert_ne!(influence, world);
+        assert_eq!(
+            db.list_accounts("Game", Some(&AccountOwner::World))
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(db.list_accounts("Game", None).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_character_accounts_need_a_character() {
+        let db = Database::new(":memory:").unwrap();
+        let err = db
+            .open_account("Game", &AccountOwner::Character("missing".into()), "cash")
+            .unwrap_err();
+        assert!(matches!(err, DbError::NotFound(_)));
+    }
+
+    #[test]
+    fn test_unbalanced_transactions_are_refused() {
+        let (db, world, wallet, _) = setup_ledger();
+        let err = db
+            .record_transaction(
+                "Game",
+                "Free money",
+                0,
+                &[Posting::new(world, -50), Posting::new(wallet, 60)],
+            )
+            .unwrap_err();
+
+        assert!(matches!(err, DbError::InvalidOperation(_)));
+        assert_eq!(db.account_balance(wallet, None).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_each_resource_balances_separately() {
+        let (db, world, wallet, guild) = setup_ledger();
+        let favours = db
+            .open_account(
+                "Game",
+                &AccountOwner::Faction("Thieves' Guild".into()),
+                "favours",
+            )
+            .unwrap();
+        let owed = db
+            .open_account("Game", &AccountOwner::World, "favours")
+            .unwrap();
+
+        // Cash moves one way, a favour the other: each resource nets to zero
+        db.record_transaction(
+            "Game",
+            "Starting funds",
+            0,
+            &[Posting::new(world, -100), Posting::new(wallet, 100)],
+        )
+        .unwrap();
+        db.record_transaction(
+            "Game",
+            "Paid the guild for a favour",
+            10,
+            &[
+                Posting::new(wallet, -40),
+                Posting::new(guild, 40),
+                Posting::new(favours, -1),
+                Posting::new(owed, 1),
+            ],
+        )
+        .unwrap();
+
+        // Mixing resources in one total is refused
+        let err = db
+            .record_transaction(
+                "Game",
+                "Swap",
+                20,
+                &[Posting::new(wallet, -1), Posting::new(favours, 1)],
+            )
+            .unwrap_err();
+        assert!(matches!(err, DbError::InvalidOperation(_)));
+
+        assert_eq!(db.account_balance(wallet, None).unwrap(), 60);
+        assert_eq!(db.account_balance(favours, None).unwrap(), -1);
+    }
+
+    #[test]
+    fn test_balance_as_of_a_point_in_time() {
+        let (db, world, wallet, guild) = setup_ledger();
+        db.record_transaction(
+            "Game",
+            "Wages",
+            100,
+            &[Posting::new(world, -50), Posting::new(wallet, 50)],
+        )
+        .unwrap();
+        db.record_transaction(
+            "Game",
+            "Dues",
+            200,
+            &[Posting::new(wallet, -20), Posting::new(guild, 20)],
+        )
+        .unwrap();
+        // Recorded late, but it happened first
+        db.record_transaction(
+            "Game",
+            "Found a purse",
+            50,
+            &[Posting::new(world, -5), Posting::new(wallet, 5)],
+        )
+        .unwrap();
+
+        assert_eq!(db.account_balance(wallet, Some(49)).unwrap(), 0);
+        assert_eq!(db.account_balance(wallet, Some(150)).unwrap(), 55);
+        assert_eq!(db.account_balance(wallet, None).unwrap(), 35);
+
+        let statement = db.account_statement(wallet).unwrap();
+        let balances: Vec<i64> = statement.iter().map(|line| line.balance).collect();
+        assert_eq!(balances, vec![5, 55, 35]);
+        assert_eq!(statement[0].description, "Found a purse");
+    }
+
+    #[test]
+    fn test_transaction_round_trip() {
+        let (db, world, wallet, _) = setup_ledger();
+        let id = db
+            .record_transaction(
+                "Game",
+                "Reward",
+                7,
+                &[Posting::new(world, -10), Posting::new(wallet, 10)],
+            )
+            .unwrap();
+
+        let transaction = db.get_ledger_transaction(id).unwrap().unwrap();
+        assert_eq!(transaction.description, "Reward");
+        assert_eq!(transaction.occurred_at, 7);
+        assert_eq!(
+            transaction.postings,
+            vec![Posting::new(world, -10), Posting::new(wallet, 10)]
+        );
+    }
+
+    #[test]
+    fn test_accounts_must_share_the_game() {
+        let (db, world, _, _) = setup_ledger();
+        let elsewhere = db
+            .open_account("Other", &AccountOwner::Location("Docks".into()), "cash")
+            .unwrap();
+
+        let err = db
+            .record_transaction(
+                "Game",
+                "Smuggling",
+                0,
+                &[Posting::new(world, -1), Posting::new(elsewhere, 1)],
+            )
+            .unwrap_err();
+        assert!(matches!(err, DbError::InvalidOperation(_)));
+    }
+
+    #[test]
+    fn test_owner_serializes_with_kind_tag() {
+        let owner = AccountOwner::Location("Docks".into());
+        assert_eq!(
+            serde_json::to_value(&owner).unwrap(),
+            serde_json::json!({"kind": "location", "id": "Docks"})
+        );
+    }
+}
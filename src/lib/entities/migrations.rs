@@ -0,0 +1,423 @@
+//! Schema versioning for the game database.
+//!
+//! The schema version is stored in SQLite's `PRAGMA user_version`. Every
+//! change to the schema is appended to `MIGRATIONS` as a new step; steps are
+//! never edited once released. On open, `Database` applies every step newer
+//! than the file's version, each inside its own transaction, so saves from
+//! any earlier version are upgraded in place.
+
+use rusqlite::{Connection, Result};
+
+/// A single ordered schema change.
+pub struct Migration {
+    /// Schema version after this step has been applied
+    pub version: u32,
+    /// Short human-readable summary of the change
+    pub description: &'static str,
+    /// SQL executed as a batch to perform the change
+    pub sql: &'static str,
+}
+
+/// Every migration in application order. Versions must be consecutive from 1.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema: characters, objects, character_objects",
+        // IF NOT EXISTS lets databases created before versioning existed (which
+        // report version 0 but already have these tables) pass through unchanged.
+        sql: "
+            CREATE TABLE IF NOT EXISTS characters (
+                uuid TEXT NOT NULL UNIQUE,
+                name TEXT NOT NULL,
+                game TEXT NOT NULL,
+                data TEXT,
+                PRIMARY KEY (name, game)
+            );
+
+            CREATE TABLE IF NOT EXISTS objects (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                type TEXT NOT NULL,
+                properties TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS character_objects (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                game TEXT NOT NULL,
+                character_name TEXT NOT NULL,
+                object_id INTEGER NOT NULL,
+                quantity INTEGER DEFAULT 1,
+                FOREIGN KEY (object_id) REFERENCES objects(id) ON DELETE CASCADE,
+                FOREIGN KEY (character_name, game) REFERENCES characters(name, game) ON DELETE CASCADE
+            );
+        ",
+    },
+    Migration {
+        version: 2,
+        description: "character creation time; inventory follows character renames",
+        // SQLite can't alter a foreign key in place, so character_objects is
+        // rebuilt with ON UPDATE CASCADE on its (name, game) reference.
+        sql: "
+            ALTER TABLE characters ADD COLUMN created_at TEXT;
+            UPDATE characters SET created_at = datetime('now') WHERE created_at IS NULL;
+
+            CREATE TABLE character_objects_v2 (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                game TEXT NOT NULL,
+                character_name TEXT NOT NULL,
+                object_id INTEGER NOT NULL,
+                quantity INTEGER DEFAULT 1,
+                FOREIGN KEY (object_id) REFERENCES objects(id) ON DELETE CASCADE,
+                FOREIGN KEY (character_name, game) REFERENCES characters(name, game)
+                    ON DELETE CASCADE ON UPDATE CASCADE
+            );
+            INSERT INTO character_objects_v2 (id, game, character_name, object_id, quantity)
+                SELECT id, game, character_name, object_id, quantity FROM character_objects;
+            DROP TABLE character_objects;
+            ALTER TABLE character_objects_v2 RENAME TO character_objects;
+        ",
+    },
+    Migration {
+        version: 3,
+        description: "item instances with per-copy state",
+        // NULL in a per-copy column means "unmodified": the value is read
+        // from the template, so template edits still reach it.
+        sql: "
+            CREATE TABLE item_instances (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                object_id INTEGER NOT NULL,
+                game TEXT NOT NULL,
+                character_name TEXT,
+                custom_name TEXT,
+                durability INTEGER,
+                charges INTEGER,
+                notes TEXT,
+                overrides TEXT,
+                FOREIGN KEY (object_id) REFERENCES objects(id) ON DELETE CASCADE,
+                FOREIGN KEY (character_name, game) REFERENCES characters(name, game)
+                    ON DELETE CASCADE ON UPDATE CASCADE
+            );
+            CREATE INDEX idx_item_instances_owner ON item_instances (game, character_name);
+        ",
+    },
+    Migration {
+        version: 4,
+        description: "one stacked inventory row per character and object",
+        // Earlier versions inserted a new row on every add; fold those into the
+        // oldest row of each stack before enforcing uniqueness.
+        sql: "
+            UPDATE character_objects
+            SET quantity = (
+                SELECT SUM(COALESCE(dup.quantity, 1)) FROM character_objects dup
+                WHERE dup.game = character_objects.game
+                  AND dup.character_name = character_objects.character_name
+                  AND dup.object_id = character_objects.object_id
+            )
+            WHERE id IN (
+                SELECT MIN(id) FROM character_objects GROUP BY game, character_name, object_id
+            );
+            DELETE FROM character_objects
+            WHERE id NOT IN (
+                SELECT MIN(id) FROM character_objects GROUP BY game, character_name, object_id
+            );
+            CREATE UNIQUE INDEX idx_character_objects_stack
+                ON character_objects (game, character_name, object_id);
+        ",
+    },
+    Migration {
+        version: 5,
+        description: "two-party trade offers",
+        sql: "
+            CREATE TABLE trade_offers (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                game TEXT NOT NULL,
+                from_uuid TEXT NOT NULL,
+                to_uuid TEXT NOT NULL,
+                offered TEXT NOT NULL,
+                requested TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'pending',
+                created_at INTEGER NOT NULL,
+                expires_at INTEGER,
+                resolved_at INTEGER,
+                FOREIGN KEY (from_uuid) REFERENCES characters(uuid) ON DELETE CASCADE,
+                FOREIGN KEY (to_uuid) REFERENCES characters(uuid) ON DELETE CASCADE
+            );
+            CREATE INDEX idx_trade_offers_game ON trade_offers (game, status);
+        ",
+    },
+    Migration {
+        version: 6,
+        description: "item instances inside containers or left at locations",
+        sql: "
+            ALTER TABLE item_instances
+                ADD COLUMN container_id INTEGER
+                REFERENCES item_instances(id) ON DELETE SET NULL;
+            ALTER TABLE item_instances ADD COLUMN location TEXT;
+            CREATE INDEX idx_item_instances_container ON item_instances (container_id);
+            CREATE INDEX idx_item_instances_location ON item_instances (game, location);
+        ",
+    },
+    Migration {
+        version: 7,
+        description: "double-entry resource ledger",
+        sql: "
+            CREATE TABLE ledger_accounts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                game TEXT NOT NULL,
+                owner_kind TEXT NOT NULL,
+                owner_id TEXT NOT NULL,
+                resource TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                UNIQUE (game, owner_kind, owner_id, resource)
+            );
+            CREATE TABLE ledger_transactions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                game TEXT NOT NULL,
+                description TEXT NOT NULL,
+                occurred_at INTEGER NOT NULL,
+                recorded_at INTEGER NOT NULL
+            );
+            CREATE TABLE ledger_postings (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                transaction_id INTEGER NOT NULL REFERENCES ledger_transactions(id),
+                account_id INTEGER NOT NULL REFERENCES ledger_accounts(id),
+                amount INTEGER NOT NULL
+            );
+            CREATE INDEX idx_ledger_postings_account ON ledger_postings (account_id);
+            CREATE INDEX idx_ledger_postings_transaction ON ledger_postings (transaction_id);
+        ",
+    },
+    Migration {
+        version: 8,
+        description: "economic entities",
+        sql: "
+            CREATE TABLE economic_entities (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                value INTEGER NOT NULL,
+                updated_at TEXT
+            );
+            CREATE INDEX idx_economic_entities_name ON economic_entities (name);
+        ",
+    },
+    Migration {
+        version: 9,
+        description: "server-side roll log",
+        sql: "
+            CREATE TABLE rolls (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                game TEXT NOT NULL,
+                character_uuid TEXT NOT NULL,
+                seed INTEGER NOT NULL,
+                breakdown TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+            CREATE INDEX idx_rolls_game ON rolls (game, created_at);
+        ",
+    },
+    Migration {
+        version: 10,
+        description: "hashed per-game API tokens with roles",
+        sql: "
+            CREATE TABLE api_tokens (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                game TEXT NOT NULL,
+                token_hash TEXT NOT NULL UNIQUE,
+                role TEXT NOT NULL CHECK (role IN ('gm', 'player', 'observer')),
+                character_uuid TEXT,
+                label TEXT,
+                created_at INTEGER NOT NULL,
+                revoked_at INTEGER,
+                FOREIGN KEY (character_uuid) REFERENCES characters(uuid) ON DELETE CASCADE,
+                CHECK ((role = 'player') = (character_uuid IS NOT NULL))
+            );
+            CREATE INDEX idx_api_tokens_game ON api_tokens (game);
+        ",
+    },
+    Migration {
+        version: 11,
+        description: "sessions and scenes; rolls, damage and XP tagged with them",
+        sql: "
+            CREATE TABLE sessions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                game TEXT NOT NULL,
+                title TEXT,
+                seed INTEGER NOT NULL,
+                started_at INTEGER NOT NULL,
+                ended_at INTEGER
+            );
+            CREATE INDEX idx_sessions_game ON sessions (game, started_at);
+            CREATE UNIQUE INDEX idx_sessions_open ON sessions (game) WHERE ended_at IS NULL;
+
+            CREATE TABLE scenes (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id INTEGER NOT NULL,
+                title TEXT,
+                seed INTEGER NOT NULL,
+                started_at INTEGER NOT NULL,
+                ended_at INTEGER,
+                FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+            );
+            CREATE UNIQUE INDEX idx_scenes_open ON scenes (session_id) WHERE ended_at IS NULL;
+
+            CREATE TABLE session_participants (
+                session_id INTEGER NOT NULL,
+                character_uuid TEXT NOT NULL,
+                joined_at INTEGER NOT NULL,
+                PRIMARY KEY (session_id, character_uuid),
+                FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE,
+                FOREIGN KEY (character_uuid) REFERENCES characters(uuid) ON DELETE CASCADE
+            );
+            CREATE TABLE scene_participants (
+                scene_id INTEGER NOT NULL,
+                character_uuid TEXT NOT NULL,
+                joined_at INTEGER NOT NULL,
+                PRIMARY KEY (scene_id, character_uuid),
+                FOREIGN KEY (scene_id) REFERENCES scenes(id) ON DELETE CASCADE,
+                FOREIGN KEY (character_uuid) REFERENCES characters(uuid) ON DELETE CASCADE
+            );
+
+            ALTER TABLE rolls ADD COLUMN session_id INTEGER
+                REFERENCES sessions(id) ON DELETE SET NULL;
+            ALTER TABLE rolls ADD COLUMN scene_id INTEGER
+                REFERENCES scenes(id) ON DELETE SET NULL;
+            CREATE INDEX idx_rolls_session ON rolls (session_id);
+
+            CREATE TABLE character_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                game TEXT NOT NULL,
+                character_uuid TEXT NOT NULL,
+                kind TEXT NOT NULL CHECK (kind IN ('damage', 'xp')),
+                amount INTEGER NOT NULL,
+                note TEXT,
+                session_id INTEGER,
+                scene_id INTEGER,
+                created_at INTEGER NOT NULL,
+                FOREIGN KEY (character_uuid) REFERENCES characters(uuid) ON DELETE CASCADE,
+                FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE SET NULL,
+                FOREIGN KEY (scene_id) REFERENCES scenes(id) ON DELETE SET NULL
+            );
+            CREATE INDEX idx_character_log_session ON character_log (session_id);
+        ",
+    },
+    Migration {
+        version: 12,
+        description: "character log outlives deleted characters",
+        // Like rolls, log entries keep a deleted character's UUID so session
+        // recaps still show its damage and XP; the table is rebuilt without
+        // the cascading character reference.
+        sql: "
+            CREATE TABLE character_log_v2 (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                game TEXT NOT NULL,
+                character_uuid TEXT NOT NULL,
+                kind TEXT NOT NULL CHECK (kind IN ('damage', 'xp')),
+                amount INTEGER NOT NULL,
+                note TEXT,
+                session_id INTEGER,
+                scene_id INTEGER,
+                created_at INTEGER NOT NULL,
+                FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE SET NULL,
+                FOREIGN KEY (scene_id) REFERENCES scenes(id) ON DELETE SET NULL
+            );
+            INSERT INTO character_log_v2
+                SELECT id, game, character_uuid, kind, amount, note, session_id, scene_id, created_at
+                FROM character_log;
+            DROP TABLE character_log;
+            ALTER TABLE character_log_v2 RENAME TO character_log;
+            CREATE INDEX idx_character_log_session ON character_log (session_id);
+        ",
+    },
+];
+
+/// The schema version a fully migrated database reports.
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+/// Reads the schema version stored in the database file.
+pub fn current_version(conn: &Connection) -> Result<u32> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+/// Applies every pending migration and returns the resulting schema version.
+///
+/// # Arguments
+///
+/// * `conn` - The connection to upgrade
+///
+/// # Returns
+///
+/// Returns the schema version after migrating, or the first error raised by a
+/// step. A failing step is rolled back and leaves the version unchanged.
+pub fn migrate(conn: &mut Connection) -> Result<u32> {
+    migrate_to(conn, latest_version())
+}
+
+/// Applies pending migrations up to and including `target`.
+///
+/// Used by `migrate` and by tests that need a database frozen at an older
+/// schema version.
+pub fn migrate_to(conn: &mut Connection, target: u32) -> Result<u32> {
+    let start = current_version(conn)?;
+    let mut version = start;
+
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|m| m.version > start && m.version <= target)
+    {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration.sql)?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+
+        eprintln!(
+            "Applied migration {}: {}",
+            migration.version, migration.description
+        );
+        version = migration.version;
+    }
+
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations_are_consecutive() {
+        for (index, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(
+                migration.version as usize,
+                index + 1,
+                "Migration versions must start at 1 and have no gaps"
+            );
+        }
+    }
+
+    #[test]
+    fn test_migrate_fresh_database_reaches_latest() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        assert_eq!(current_version(&conn).unwrap(), 0);
+        assert_eq!(migrate(&mut conn).unwrap(), latest_version());
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+    }
+
+    #[test]
+    fn test_migrate_is_idempotent() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+
+        assert_eq!(migrate(&mut conn).unwrap(), latest_version());
+    }
+
+    #[test]
+    fn test_migrate_to_stops_at_target() {
+        let mut conn = Connection::open_in_memory().unwrap();
+
+        assert_eq!(migrate_to(&mut conn, 1).unwrap(), 1);
+        assert_eq!(current_version(&conn).unwrap(), 1);
+    }
+}
@@ -1,4 +1,15 @@
 /// Entities module - contains all game entity structs and their implementations
 pub mod character;
+pub mod containers;
 pub mod database;
 pub mod economy;
+pub mod error;
+pub mod items;
+pub mod ledger;
+pub mod migrations;
+pub mod pool;
+pub mod records;
+pub mod rolls;
+pub mod sessions;
+pub mod tokens;
+pub mod trade;
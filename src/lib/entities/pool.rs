@@ -0,0 +1,324 @@
+//! Shared, thread-safe access to the game database.
+//!
+//! `Database` wraps a single `rusqlite::Connection`, which can't be shared
+//! between threads. `DbPool` is a cloneable handle over a small set of
+//! connections to the same file: async code hands it a closure, the closure
+//! runs on tokio's blocking thread pool with a connection checked out, and the
+//! result is awaited. Connections are opened in WAL mode, so reads on one
+//! connection don't wait for a write on another.
+
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Semaphore;
+
+use super::database::Database;
+use super::error::{DbError, DbResult};
+
+/// Default number of connections for file-backed pools.
+pub const DEFAULT_POOL_SIZE: usize = 4;
+
+/// Cloneable handle to a pool of `Database` connections.
+///
+/// # Examples
+///
+/// ```
+/// use ttdigirpg::entities::error::DbError;
+/// use ttdigirpg::entities::pool::DbPool;
+///
+/// # tokio::runtime::Runtime::new().unwrap().block_on(async {
+/// let pool = DbPool::in_memory().unwrap();
+/// pool.run(|db| db.insert_character("Alice", "Knives Out", None)).await.unwrap();
+///
+/// let alice = pool
+///     .run(|db| db.get_character("Alice", "Knives Out"))
+///     .await
+///     .unwrap();
+/// assert!(alice.is_some());
+/// # });
+/// ```
+#[derive(Clone)]
+pub struct DbPool {
+    inner: Arc<PoolInner>,
+}
+
+struct PoolInner {
+    /// Path every connection is opened against
+    path: String,
+    /// Connections not currently in use
+    idle: Mutex<Vec<Database>>,
+    /// Limits how many connections exist at once
+    permits: Arc<Semaphore>,
+}
+
+impl DbPool {
+    /// Creates a pool for the database file at `db_path`.
+    ///
+    /// One connection is opened immediately so the schema is migrated (and any
+    /// error reported) before the pool is handed out.
+    ///
+    /// # Arguments
+    ///
+    /// * `db_path` - The file path of the database
+    /// * `max_size` - Maximum number of simultaneously open connections (at least 1)
+    pub fn open(db_path: &str, max_size: usize) -> DbResult<Self> {
+        let first = Database::open(db_path)?;
+        Ok(DbPool {
+            inner: Arc::new(PoolInner {
+                path: db_path.to_string(),
+                idle: Mutex::new(vec![first]),
+                permits: Arc::new(Semaphore::new(max_size.max(1))),
+            }),
+        })
+    }
+
+    /// Creates a pool over a single in-memory database.
+    ///
+    /// Every SQLite `:memory:` connection is a separate database, so the pool
+    /// is limited to the one connection that holds the data.
+    pub fn in_memory() -> DbResult<Self> {
+        Self::open(":memory:", 1)
+    }
+
+    /// Runs `operation` with a pooled connection and returns its result.
+    ///
+    /// The closure runs on a blocking thread, so it may freely use any
+    /// synchronous `Database` method, including `transaction`.
+    ///
+    /// # Returns
+    ///
+    /// Returns whatever the closure returned. Failing to open a connection or a
+    /// panicking closure is reported as a `DbError`. A connection whose closure
+    /// panicked goes back to the pool with any open transaction rolled back.
+    pub async fn run<T, E, F>(&self, operation: F) -> Result<T, E>
+    where
+        F: FnOnce(&Database) -> Result<T, E> + Send + 'static,
+        T: Send + 'static,
+        E: From<DbError> + Send + 'static,
+    {
+        let permit = Arc::clone(&self.inner.permits)
+            .acquire_owned()
+            .await
+            .map_err(|_| E::from(DbError::Unavailable("connection pool closed".to_string())))?;
+        let inner = Arc::clone(&self.inner);
+
+        let joined = tokio::task::spawn_blocking(move || {
+            let db = match inner.checkout() {
+                Ok(db) => db,
+                Err(err) => return Err(E::from(err)),
+            };
+            let result = operation(&db);
+            drop(db);
+            drop(permit);
+            result
+        })
+        .await;
+
+        match joined {
+            Ok(result) => result,
+            Err(err) => Err(E::from(DbError::Unavailable(format!(
+                "database task failed: {}",
+                err
+            )))),
+        }
+    }
+}
+
+impl PoolInner {
+    /// Takes an idle connection, or opens a new one if none is idle.
+    fn checkout(&self) -> DbResult<Checkout<'_>> {
+        let idle = self.idle.lock().unwrap_or_else(|e| e.into_inner()).pop();
+        let db = match idle {
+            Some(db) => db,
+            None => Database::open(&self.path)?,
+        };
+        Ok(Checkout {
+            pool: self,
+            db: Some(db),
+        })
+    }
+
+    /// Returns a connection to the idle list.
+    fn checkin(&self, db: Database) {
+        self.idle.lock().unwrap_or_else(|e| e.into_inner()).push(db);
+    }
+}
+
+/// A checked-out connection, returned to the idle list when dropped.
+///
+/// Dropping also happens while a panicking closure unwinds, which matters for
+/// in-memory pools: their only connection holds the data, and losing it would
+/// leave every later call with an empty database.
+struct Checkout<'a> {
+    pool: &'a PoolInner,
+    db: Option<Database>,
+}
+
+impl Deref for Checkout<'_> {
+    type Target = Database;
+
+    fn deref(&self) -> &Database {
+        self.db.as_ref().expect("connection already returned")
+    }
+}
+
+impl Drop for Checkout<'_> {
+    fn drop(&mut self) {
+        let Some(db) = self.db.take() else {
+            return;
+        };
+        // `transaction` rolls back its own savepoints while unwinding; this
+        // catches a transaction the closure began by hand
+        if std::thread::panicking() && !db.conn.is_autocommit() {
+            if let Err(err) = db.conn.execute_batch("ROLLBACK") {
+                eprintln!("Failed to roll back after a panic: {}", err);
+            }
+        }
+        self.pool.checkin(db);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn temp_db_path() -> String {
+        std::env::temp_dir()
+            .join(format!("ttdigirpg_pool_{}.db", Uuid::new_v4()))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn remove_db_files(path: &str) {
+        for suffix in ["", "-wal", "-shm"] {
+            std::fs::remove_file(format!("{}{}", path, suffix)).ok();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_pool_keeps_data_between_calls() {
+        let pool = DbPool::in_memory().unwrap();
+        pool.run(|db| db.insert_character("Alice", "Game", None))
+            .await
+            .unwrap();
+
+        let alice = pool
+            .run(|db| db.get_character("Alice", "Game"))
+            .await
+            .unwrap();
+        assert!(alice.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_handle_clones_share_the_database() {
+        let pool = DbPool::in_memory().unwrap();
+        let clone = pool.clone();
+
+        clone
+            .run(|db| db.insert_character("Bob", "Game", None))
+            .await
+            .unwrap();
+        let bob = pool
+            .run(|db| db.get_character("Bob", "Game"))
+            .await
+            .unwrap();
+        assert!(bob.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_errors_pass_through() {
+        let pool = DbPool::in_memory().unwrap();
+        pool.run(|db| db.insert_character("Alice", "Game", None))
+            .await
+            .unwrap();
+
+        let result = pool
+            .run(|db| db.insert_character("Alice", "Game", None))
+            .await;
+        assert!(matches!(result, Err(DbError::Duplicate(_))));
+    }
+
+    #[tokio::test]
+    async fn test_panicking_operation_reports_unavailable() {
+        let pool = DbPool::in_memory().unwrap();
+        pool.run(|db| db.insert_character("Alice", "Game", None))
+            .await
+            .unwrap();
+
+        let result: DbResult<()> = pool.run(|_| panic!("boom")).await;
+        assert!(matches!(result, Err(DbError::Unavailable(_))));
+
+        // Panics inside transactions, ours or hand-rolled, are rolled back
+        let result: DbResult<()> = pool
+            .run(|db| {
+                db.transaction(|db| {
+                    db.insert_character("Bob", "Game", None)?;
+                    panic!("boom")
+                })
+            })
+            .await;
+        assert!(matches!(result, Err(DbError::Unavailable(_))));
+        let result: DbResult<()> = pool
+            .run(|db| {
+                db.conn.execute_batch("BEGIN")?;
+                db.insert_character("Carol", "Game", None)?;
+                panic!("boom")
+            })
+            .await;
+        assert!(matches!(result, Err(DbError::Unavailable(_))));
+
+        // The in-memory database survived, and still takes transactions
+        let names = pool
+            .run(|db| {
+                db.transaction(|db| db.insert_character("Dave", "Game", None))?;
+                ["Alice", "Bob", "Carol", "Dave"]
+                    .into_iter()
+                    .map(|name| Ok(db.get_character(name, "Game")?.is_some()))
+                    .collect::<DbResult<Vec<_>>>()
+            })
+            .await
+            .unwrap();
+        assert_eq!(names, vec![true, false, false, true]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_access_to_file_database() {
+        let path = temp_db_path();
+        let pool = DbPool::open(&path, DEFAULT_POOL_SIZE).unwrap();
+
+        let mut tasks = Vec::new();
+        for i in 0..16 {
+            let pool = pool.clone();
+            tasks.push(tokio::spawn(async move {
+                pool.run(move |db| db.insert_character(&format!("PC {}", i), "Game", None))
+                    .await
+                    .unwrap();
+                pool.run(move |db| db.get_character(&format!("PC {}", i), "Game"))
+                    .await
+                    .unwrap()
+                    .is_some()
+            }));
+        }
+        for task in tasks {
+            assert!(
+                task.await.unwrap(),
+                "Each writer should read back its own row"
+            );
+        }
+
+        drop(pool);
+        remove_db_files(&path);
+    }
+
+    #[tokio::test]
+    async fn test_file_database_uses_wal() {
+        let path = temp_db_path();
+        let pool = DbPool::open(&path, 2).unwrap();
+
+        let mode: String = pool.run(|db| db.journal_mode()).await.unwrap();
+
+        drop(pool);
+        remove_db_files(&path);
+        assert_eq!(mode, "wal");
+    }
+}
@@ -0,0 +1,256 @@
+//! Typed rows returned by `Database` queries.
+//!
+//! Each struct mirrors the columns of a query so callers access fields by
+//! name instead of tuple position. All records derive serde so the API layer
+//! and tests can serialize them directly.
+
+use rusqlite::{Result, Row};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use serde_json::{Map, Value};
+
+use super::character::Character;
+
+/// A row of the `characters` table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CharacterRecord {
+    /// Stable unique identifier assigned on insert
+    pub uuid: String,
+    /// The character's name (unique within a game)
+    pub name: String,
+    /// The game this character belongs to
+    pub game: String,
+    /// Optional JSON string containing character data
+    pub data: Option<String>,
+}
+
+impl CharacterRecord {
+    /// Builds a record from a row selected as `uuid, name, game, data`.
+    pub(crate) fn from_row(row: &Row<'_>) -> Result<Self> {
+        Ok(CharacterRecord {
+            uuid: row.get(0)?,
+            name: row.get(1)?,
+            game: row.get(2)?,
+            data: row.get(3)?,
+        })
+    }
+
+    /// Decodes the character sheet stored in `data`.
+    ///
+    /// Stats missing from the stored JSON (or all of them, if `data` is NULL or
+    /// not a sheet) default to 1. The name always comes from the `name` column.
+    pub fn sheet(&self) -> Character {
+        let mut sheet = self
+            .data
+            .as_deref()
+            .and_then(|json| serde_json::from_str::<Character>(json).ok())
+            .unwrap_or_default();
+        sheet.name = self.name.clone();
+        sheet
+    }
+}
+
+/// Merges a sheet's stats into a character's stored `data` JSON, keeping any
+/// other keys (such as an imported Foundry document).
+///
+/// The name is left out because the `name` column is authoritative.
+pub fn merge_sheet_data(existing: Option<&str>, sheet: &Character) -> String {
+    let mut data = match existing.map(serde_json::from_str::<Value>) {
+        Some(Ok(Value::Object(map))) => map,
+        _ => Map::new(),
+    };
+    if let Ok(Value::Object(stats)) = serde_json::to_value(sheet) {
+        data.extend(stats);
+    }
+    data.remove("name");
+    Value::Object(data).to_string()
+}
+
+/// A row of the `objects` table (an object definition).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObjectRecord {
+    /// Auto-incremented object ID
+    pub id: i64,
+    /// The object's name (e.g., "Sword")
+    pub name: String,
+    /// The object's type (e.g., "weapon", "building")
+    #[serde(rename = "type")]
+    pub obj_type: String,
+    /// Optional JSON string containing object properties
+    pub properties: Option<String>,
+}
+
+impl ObjectRecord {
+    /// Builds a record from a row selected as `id, name, type, properties`.
+    pub(crate) fn from_row(row: &Row<'_>) -> Result<Self> {
+        Ok(ObjectRecord {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            obj_type: row.get(2)?,
+            properties: row.get(3)?,
+        })
+    }
+}
+
+/// One line of a character's inventory: an object joined with its owned quantity.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InventoryEntry {
+    /// ID of the owned object definition
+    pub object_id: i64,
+    /// The object's name
+    pub name: String,
+    /// The object's type
+    #[serde(rename = "type")]
+    pub obj_type: String,
+    /// How many of this object the character holds
+    pub quantity: i32,
+    /// Optional JSON string containing object properties
+    pub properties: Option<String>,
+}
+
+impl InventoryEntry {
+    /// Builds an entry from a row selected as `id, name, type, quantity, properties`.
+    pub(crate) fn from_row(row: &Row<'_>) -> Result<Self> {
+        Ok(InventoryEntry {
+            object_id: row.get(0)?,
+            name: row.get(1)?,
+            obj_type: row.get(2)?,
+            quantity: row.get(3)?,
+            properties: row.get(4)?,
+        })
+    }
+}
+
+/// One game that has at least one character, as listed by `Database::list_games`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameSummary {
+    /// The game's name
+    pub game: String,
+    /// How many characters belong to it
+    pub character_count: i64,
+}
+
+/// Which column `Database::list_characters` orders by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CharacterSort {
+    /// Alphabetical by character name (default)
+    #[default]
+    Name,
+    /// Oldest characters first
+    CreatedAt,
+}
+
+/// Pagination and ordering parameters for list queries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PageRequest {
+    /// Maximum number of items to return
+    pub limit: u32,
+    /// Number of items to skip before the first returned item
+    pub offset: u32,
+    /// Reverse the sort order
+    pub descending: bool,
+}
+
+impl PageRequest {
+    /// Largest page size a single query will return.
+    pub const MAX_LIMIT: u32 = 500;
+
+    /// Creates a request for `limit` items starting after `offset` items.
+    pub fn new(limit: u32, offset: u32) -> Self {
+        PageRequest {
+            limit,
+            offset,
+            descending: false,
+        }
+    }
+
+    /// The limit actually applied, capped at `MAX_LIMIT`.
+    pub fn effective_limit(&self) -> u32 {
+        self.limit.min(Self::MAX_LIMIT)
+    }
+
+    /// `ASC` or `DESC` for use in an ORDER BY clause.
+    pub(crate) fn direction(&self) -> &'static str {
+        if self.descending {
+            "DESC"
+        } else {
+            "ASC"
+        }
+    }
+}
+
+impl Default for PageRequest {
+    fn default() -> Self {
+        PageRequest::new(50, 0)
+    }
+}
+
+/// One page of a list query along with the total number of matching items.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Page<T> {
+    /// The items on this page
+    pub items: Vec<T>,
+    /// Total number of items across all pages
+    pub total: i64,
+    /// The limit that was applied
+    pub limit: u32,
+    /// The offset that was applied
+    pub offset: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_object_record_serializes_type_field() {
+        let record = ObjectRecord {
+            id: 1,
+            name: "Sword".to_string(),
+            obj_type: "weapon".to_string(),
+            properties: None,
+        };
+
+        let value = serde_json::to_value(&record).unwrap();
+        assert_eq!(
+            value,
+            json!({"id": 1, "name": "Sword", "type": "weapon", "properties": null})
+        );
+    }
+
+    #[test]
+    fn test_page_request_caps_limit() {
+        assert_eq!(PageRequest::new(10, 0).effective_limit(), 10);
+        assert_eq!(
+            PageRequest::new(10_000, 0).effective_limit(),
+            PageRequest::MAX_LIMIT
+        );
+    }
+
+    #[test]
+    fn test_page_request_fills_missing_fields() {
+        let request: PageRequest = serde_json::from_str(r#"{"offset": 20}"#).unwrap();
+
+        assert_eq!(request.offset, 20);
+        assert_eq!(request.limit, PageRequest::default().limit);
+        assert!(!request.descending);
+    }
+
+    #[test]
+    fn test_inventory_entry_round_trip() {
+        let entry = InventoryEntry {
+            object_id: 3,
+            name: "Potion".to_string(),
+            obj_type: "consumable".to_string(),
+            quantity: 5,
+            properties: Some(r#"{"heal": 50}"#.to_string()),
+        };
+
+        let text = serde_json::to_string(&entry).unwrap();
+        let back: InventoryEntry = serde_json::from_str(&text).unwrap();
+        assert_eq!(back, entry);
+    }
+}
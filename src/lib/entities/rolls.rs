@@ -0,0 +1,187 @@
+//! Server-side log of dice rolls.
+//!
+//! Rolls made through the API are stored with the seed that produced them, so
+//! anyone can later fetch a roll by ID and check it was not made up or
+//! re-rolled on the client. Each roll is tagged with the session and scene
+//! open in its game at the time.
+
+use rusqlite::{OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::database::{unix_now, Database};
+use super::error::DbResult;
+
+/// A row of the `rolls` table.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RollRecord {
+    /// Auto-incremented roll ID
+    pub id: i64,
+    /// The game the roll was made in
+    pub game: String,
+    /// UUID of the rolling character
+    pub character_uuid: String,
+    /// Seed of the RNG the dice were drawn from
+    pub seed: u64,
+    /// Everything about the roll: pool, modifiers, faces and outcome
+    pub breakdown: Value,
+    /// The session open when the roll was made, if any
+    pub session_id: Option<i64>,
+    /// The scene open when the roll was made, if any
+    pub scene_id: Option<i64>,
+    /// When the roll was made (Unix seconds)
+    pub created_at: i64,
+}
+
+impl RollRecord {
+    /// Builds a record from a row selected as `ROLL_COLUMNS`.
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let seed: i64 = row.get(3)?;
+        let breakdown: String = row.get(4)?;
+        Ok(RollRecord {
+            id: row.get(0)?,
+            game: row.get(1)?,
+            character_uuid: row.get(2)?,
+            seed: seed as u64,
+            breakdown: serde_json::from_str(&breakdown).map_err(|err| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    4,
+                    rusqlite::types::Type::Text,
+                    err.into(),
+                )
+            })?,
+            created_at: row.get(5)?,
+            session_id: row.get(6)?,
+            scene_id: row.get(7)?,
+        })
+    }
+}
+
+const ROLL_COLUMNS: &str =
+    "id, game, character_uuid, seed, breakdown, created_at, session_id, scene_id";
+
+impl Database {
+    // ==================== ROLL LOG METHODS ====================
+
+    /// Stores a roll and returns the stored record.
+    ///
+    /// The roll is tagged with the game's open session and scene, which the
+    /// character joins.
+    ///
+    /// # Arguments
+    ///
+    /// * `game` - The game the roll was made in
+    /// * `character_uuid` - The rolling character
+    /// * `seed` - Seed of the RNG the dice were drawn from
+    /// * `breakdown` - JSON description of the roll
+    pub fn record_roll(
+        &self,
+        game: &str,
+        character_uuid: &str,
+        seed: u64,
+        breakdown: &Value,
+    ) -> DbResult<RollRecord> {
+        self.transaction(|db| {
+            let context = db.play_context(game)?;
+            db.join_play_context(game, context, character_uuid)?;
+            let id: i64 = db.conn.query_row(
+                "INSERT INTO rolls
+                     (game, character_uuid, seed, breakdown, created_at, session_id, scene_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                 RETURNING id",
+                (
+                    game,
+                    character_uuid,
+                    seed as i64,
+                    breakdown.to_string(),
+                    unix_now(),
+                    context.session_id,
+                    context.scene_id,
+                ),
+                |row| row.get(0),
+            )?;
+
+            Ok(db.conn.query_row(
+                &format!("SELECT {} FROM rolls WHERE id = ?1", ROLL_COLUMNS),
+                [id],
+                RollRecord::from_row,
+            )?)
+        })
+    }
+
+    /// Retrieves a stored roll by ID.
+    pub fn get_roll(&self, roll_id: i64) -> DbResult<Option<RollRecord>> {
+        Ok(self
+            .conn
+            .query_row(
+                &format!("SELECT {} FROM rolls WHERE id = ?1", ROLL_COLUMNS),
+                [roll_id],
+                RollRecord::from_row,
+            )
+            .optional()?)
+    }
+
+    /// Lists the most recent rolls in a game, newest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `game` - The game to list
+    /// * `character_uuid` - Only rolls by this character, if given
+    /// * `limit` - Maximum number of rolls to return
+    pub fn list_rolls(
+        &self,
+        game: &str,
+        character_uuid: Option<&str>,
+        limit: u32,
+    ) -> DbResult<Vec<RollRecord>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM rolls
+             WHERE game = ?1 AND (?2 IS NULL OR character_uuid = ?2)
+             ORDER BY id DESC
+             LIMIT ?3",
+            ROLL_COLUMNS
+        ))?;
+        let rows = stmt.query_map((game, character_uuid, limit), RollRecord::from_row)?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// Lists the rolls made during a session, oldest first.
+    pub fn list_session_rolls(&self, session_id: i64) -> DbResult<Vec<RollRecord>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM rolls WHERE session_id = ?1 ORDER BY id",
+            ROLL_COLUMNS
+        ))?;
+        let rows = stmt.query_map([session_id], RollRecord::from_row)?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_rolls_round_trip_and_list_newest_first() {
+        let db = Database::new(":memory:").unwrap();
+        let big_seed = u64::MAX - 1;
+
+        let first = db
+            .record_roll("Game", "alice", big_seed, &json!({"faces": [3, 9]}))
+            .unwrap();
+        db.record_roll("Game", "bob", 7, &json!({"faces": [1]}))
+            .unwrap();
+        db.record_roll("Other", "alice", 8, &json!({})).unwrap();
+
+        let fetched = db.get_roll(first.id).unwrap().unwrap();
+        assert_eq!(fetched, first);
+        assert_eq!(fetched.seed, big_seed);
+        assert_eq!(fetched.breakdown["faces"], json!([3, 9]));
+
+        let game_rolls = db.list_rolls("Game", None, 10).unwrap();
+        let seeds: Vec<u64> = game_rolls.iter().map(|roll| roll.seed).collect();
+        assert_eq!(seeds, vec![7, big_seed]);
+        assert_eq!(db.list_rolls("Game", Some("alice"), 10).unwrap().len(), 1);
+        assert_eq!(db.list_rolls("Game", None, 1).unwrap().len(), 1);
+    }
+}
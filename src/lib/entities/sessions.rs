@@ -0,0 +1,687 @@
+//! Play sessions and the scenes within them.
+//!
+//! A session is one night's play in a game; a scene is a stretch of a session
+//! in one place or situation. Each game has at most one open session, and each
+//! session at most one open scene. Rolls, damage and XP awards recorded while
+//! they are open are tagged with them (see `Database::play_context`), and the
+//! characters involved are added to their participants, which is what session
+//! recaps and scene-scoped effects are built from.
+//!
+//! Every session and scene has an RNG seed for randomness that should be
+//! reproducible within it, such as random tables. Unless one is given, a
+//! session's seed is random and a scene's is derived from its session's seed
+//! and its position in the session.
+
+use std::fmt;
+use std::str::FromStr;
+
+use rand::Rng;
+use rusqlite::{OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use super::database::{unix_now, Database};
+use super::error::{DbError, DbResult};
+use crate::systems::dice::MAX_SEED;
+
+/// One night's play in a game.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Session {
+    /// Auto-incremented session ID
+    pub id: i64,
+    /// The game being played
+    pub game: String,
+    /// Optional name, e.g. "The heist, part 2"
+    pub title: Option<String>,
+    /// Seed for randomness that should be reproducible within the session
+    pub seed: u64,
+    /// When the session started (Unix seconds)
+    pub started_at: i64,
+    /// When the session ended, or `None` while it is being played
+    pub ended_at: Option<i64>,
+    /// UUIDs of the characters who took part, in the order they joined
+    pub participants: Vec<String>,
+}
+
+/// A scene within a session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Scene {
+    /// Auto-incremented scene ID
+    pub id: i64,
+    /// The session the scene is part of
+    pub session_id: i64,
+    /// Optional name, e.g. "Rooftop chase"
+    pub title: Option<String>,
+    /// Seed for randomness that should be reproducible within the scene
+    pub seed: u64,
+    /// When the scene started (Unix seconds)
+    pub started_at: i64,
+    /// When the scene ended, or `None` while it is being played
+    pub ended_at: Option<i64>,
+    /// UUIDs of the characters who took part, in the order they joined
+    pub participants: Vec<String>,
+}
+
+/// The open session and scene of a game, which new activity is tagged with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PlayContext {
+    /// The open session, if any
+    pub session_id: Option<i64>,
+    /// The open scene of that session, if any
+    pub scene_id: Option<i64>,
+}
+
+/// What a `character_log` entry records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LogKind {
+    /// Damage taken (negative amounts heal)
+    Damage,
+    /// Experience points awarded (negative amounts take them away)
+    Xp,
+}
+
+impl LogKind {
+    /// The value stored in the `kind` column.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogKind::Damage => "damage",
+            LogKind::Xp => "xp",
+        }
+    }
+}
+
+impl fmt::Display for LogKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for LogKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "damage" => Ok(LogKind::Damage),
+            "xp" => Ok(LogKind::Xp),
+            other => Err(format!("Unknown log kind: {}", other)),
+        }
+    }
+}
+
+/// A row of the `character_log` table: damage or XP for one character.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct LogEntry {
+    /// Auto-incremented entry ID
+    pub id: i64,
+    /// The game the character belongs to
+    pub game: String,
+    /// UUID of the character
+    pub character_uuid: String,
+    /// Damage or XP
+    pub kind: LogKind,
+    /// Points of damage or XP
+    pub amount: i32,
+    /// Where the damage came from or what the XP was for
+    pub note: Option<String>,
+    /// The session open when the entry was made, if any
+    pub session_id: Option<i64>,
+    /// The scene open when the entry was made, if any
+    pub scene_id: Option<i64>,
+    /// When the entry was made (Unix seconds)
+    pub created_at: i64,
+}
+
+impl LogEntry {
+    /// Builds an entry from a row selected as `LOG_COLUMNS`.
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let kind: String = row.get(3)?;
+        Ok(LogEntry {
+            id: row.get(0)?,
+            game: row.get(1)?,
+            character_uuid: row.get(2)?,
+            kind: kind.parse().map_err(|msg: String| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    3,
+                    rusqlite::types::Type::Text,
+                    msg.into(),
+                )
+            })?,
+            amount: row.get(4)?,
+            note: row.get(5)?,
+            session_id: row.get(6)?,
+            scene_id: row.get(7)?,
+            created_at: row.get(8)?,
+        })
+    }
+}
+
+const SESSION_COLUMNS: &str = "id, game, title, seed, started_at, ended_at";
+const SCENE_COLUMNS: &str = "id, session_id, title, seed, started_at, ended_at";
+const LOG_COLUMNS: &str =
+    "id, game, character_uuid, kind, amount, note, session_id, scene_id, created_at";
+
+/// Seed of the `number`th scene (from 1) of a session, mixed with SplitMix64
+/// so neighbouring scenes get unrelated seeds.
+fn scene_seed(session_seed: u64, number: i64) -> u64 {
+    let mut z = session_seed.wrapping_add((number as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (z ^ (z >> 31)) % MAX_SEED
+}
+
+impl Database {
+    // ==================== SESSION METHODS ====================
+
+    /// Starts a session of a game.
+    ///
+    /// # Arguments
+    ///
+    /// * `game` - The game being played
+    /// * `title` - Optional name for the session
+    /// * `seed` - The session's RNG seed; random if `None`
+    /// * `participants` - UUIDs of the characters taking part from the start
+    ///
+    /// # Returns
+    ///
+    /// Returns the new session. Returns `DbError::InvalidOperation` if the game
+    /// already has an open session or a participant isn't in the game.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ttdigirpg::entities::database::Database;
+    ///
+    /// let db = Database::new(":memory:").unwrap();
+    /// let mara = db.insert_character("Mara", "Noir", None).unwrap();
+    ///
+    /// let session = db.start_session("Noir", Some("Night one"), None, &[mara.clone()]).unwrap();
+    /// assert_eq!(session.participants, vec![mara]);
+    /// assert!(db.start_session("Noir", None, None, &[]).is_err());
+    ///
+    /// db.end_session(session.id).unwrap();
+    /// assert!(db.current_session("Noir").unwrap().is_none());
+    /// ```
+    pub fn start_session(
+        &self,
+        game: &str,
+        title: Option<&str>,
+        seed: Option<u64>,
+        participants: &[String],
+    ) -> DbResult<Session> {
+        self.transaction(|db| {
+            if let Some(open) = db.current_session(game)? {
+                return Err(DbError::invalid(format!(
+                    "session {} of {} is still open",
+                    open.id, game
+                )));
+            }
+            let seed = seed.unwrap_or_else(|| rand::thread_rng().gen_range(0..MAX_SEED));
+            let id: i64 = db.conn.query_row(
+                "INSERT INTO sessions (game, title, seed, started_at)
+                 VALUES (?1, ?2, ?3, ?4)
+                 RETURNING id",
+                (game, title, seed as i64, unix_now()),
+                |row| row.get(0),
+            )?;
+            for uuid in participants {
+                db.join_session(game, id, None, uuid)?;
+            }
+            db.get_session(id)?
+                .ok_or_else(|| DbError::not_found(format!("session {}", id)))
+        })
+    }
+
+    /// Ends a session, and its open scene if it has one.
+    ///
+    /// # Returns
+    ///
+    /// Returns the ended session, or `DbError::NotFound` if no open session
+    /// has this ID.
+    pub fn end_session(&self, session_id: i64) -> DbResult<Session> {
+        self.transaction(|db| {
+            let now = unix_now();
+            let ended = db.conn.execute(
+                "UPDATE sessions SET ended_at = ?1 WHERE id = ?2 AND ended_at IS NULL",
+                (now, session_id),
+            )?;
+            if ended == 0 {
+                return Err(DbError::not_found(format!("open session {}", session_id)));
+            }
+            db.conn.execute(
+                "UPDATE scenes SET ended_at = ?1 WHERE session_id = ?2 AND ended_at IS NULL",
+                (now, session_id),
+            )?;
+            db.get_session(session_id)?
+                .ok_or_else(|| DbError::not_found(format!("session {}", session_id)))
+        })
+    }
+
+    /// Retrieves a session by ID, with its participants.
+    pub fn get_session(&self, session_id: i64) -> DbResult<Option<Session>> {
+        let session = self
+            .conn
+            .query_row(
+                &format!("SELECT {} FROM sessions WHERE id = ?1", SESSION_COLUMNS),
+                [session_id],
+                session_from_row,
+            )
+            .optional()?;
+        session
+            .map(|session| self.with_session_participants(session))
+            .transpose()
+    }
+
+    /// The game's open session, if there is one.
+    pub fn current_session(&self, game: &str) -> DbResult<Option<Session>> {
+        let session = self
+            .conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM sessions WHERE game = ?1 AND ended_at IS NULL",
+                    SESSION_COLUMNS
+                ),
+                [game],
+                session_from_row,
+            )
+            .optional()?;
+        session
+            .map(|session| self.with_session_participants(session))
+            .transpose()
+    }
+
+    /// Lists a game's sessions, newest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `game` - The game to list
+    /// * `limit` - Maximum number of sessions to return
+    pub fn list_sessions(&self, game: &str, limit: u32) -> DbResult<Vec<Session>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM sessions WHERE game = ?1 ORDER BY id DESC LIMIT ?2",
+            SESSION_COLUMNS
+        ))?;
+        let sessions = stmt
+            .query_map((game, limit), session_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        sessions
+            .into_iter()
+            .map(|session| self.with_session_participants(session))
+            .collect()
+    }
+
+    fn with_session_participants(&self, mut session: Session) -> DbResult<Session> {
+        let mut stmt = self.conn.prepare(
+            "SELECT character_uuid FROM session_participants
+             WHERE session_id = ?1 ORDER BY joined_at, rowid",
+        )?;
+        let rows = stmt.query_map([session.id], |row| row.get(0))?;
+        session.participants = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(session)
+    }
+
+    // ==================== SCENE METHODS ====================
+
+    /// Starts a scene in an open session.
+    ///
+    /// # Arguments
+    ///
+    /// * `session_id` - The session the scene is part of
+    /// * `title` - Optional name for the scene
+    /// * `seed` - The scene's RNG seed; derived from the session's if `None`
+    /// * `participants` - UUIDs of the characters in the scene from the start;
+    ///   they join the session too
+    ///
+    /// # Returns
+    ///
+    /// Returns the new scene. Returns `DbError::NotFound` if no open session
+    /// has this ID, or `DbError::InvalidOperation` if the session already has
+    /// an open scene or a participant isn't in the game.
+    pub fn start_scene(
+        &self,
+        session_id: i64,
+        title: Option<&str>,
+        seed: Option<u64>,
+        participants: &[String],
+    ) -> DbResult<Scene> {
+        self.transaction(|db| {
+            let session = db
+                .get_session(session_id)?
+                .filter(|session| session.ended_at.is_none())
+                .ok_or_else(|| DbError::not_found(format!("open session {}", session_id)))?;
+            let scenes = db.list_scenes(session_id)?;
+            if let Some(open) = scenes.iter().find(|scene| scene.ended_at.is_none()) {
+                return Err(DbError::invalid(format!(
+                    "scene {} of session {} is still open",
+                    open.id, session_id
+                )));
+            }
+
+            let number = scenes.len() as i64 + 1;
+            let seed = seed.unwrap_or_else(|| scene_seed(session.seed, number));
+            let id: i64 = db.conn.query_row(
+                "INSERT INTO scenes (session_id, title, seed, started_at)
+                 VALUES (?1, ?2, ?3, ?4)
+                 RETURNING id",
+                (session_id, title, seed as i64, unix_now()),
+                |row| row.get(0),
+            )?;
+            for uuid in participants {
+                db.join_session(&session.game, session_id, Some(id), uuid)?;
+            }
+            db.get_scene(id)?
+                .ok_or_else(|| DbError::not_found(format!("scene {}", id)))
+        })
+    }
+
+    /// Ends a scene.
+    ///
+    /// # Returns
+    ///
+    /// Returns the ended scene, or `DbError::NotFound` if no open scene has
+    /// this ID.
+    pub fn end_scene(&self, scene_id: i64) -> DbResult<Scene> {
+        let ended = self.conn.execute(
+            "UPDATE scenes SET ended_at = ?1 WHERE id = ?2 AND ended_at IS NULL",
+            (unix_now(), scene_id),
+        )?;
+        if ended == 0 {
+            return Err(DbError::not_found(format!("open scene {}", scene_id)));
+        }
+        self.get_scene(scene_id)?
+            .ok_or_else(|| DbError::not_found(format!("scene {}", scene_id)))
+    }
+
+    /// Retrieves a scene by ID, with its participants.
+    pub fn get_scene(&self, scene_id: i64) -> DbResult<Option<Scene>> {
+        let scene = self
+            .conn
+            .query_row(
+                &format!("SELECT {} FROM scenes WHERE id = ?1", SCENE_COLUMNS),
+                [scene_id],
+                scene_from_row,
+            )
+            .optional()?;
+        scene
+            .map(|scene| self.with_scene_participants(scene))
+            .transpose()
+    }
+
+    /// Lists a session's scenes in the order they were played.
+    pub fn list_scenes(&self, session_id: i64) -> DbResult<Vec<Scene>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM scenes WHERE session_id = ?1 ORDER BY id",
+            SCENE_COLUMNS
+        ))?;
+        let scenes = stmt
+            .query_map([session_id], scene_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        scenes
+            .into_iter()
+            .map(|scene| self.with_scene_participants(scene))
+            .collect()
+    }
+
+    fn with_scene_participants(&self, mut scene: Scene) -> DbResult<Scene> {
+        let mut stmt = self.conn.prepare(
+            "SELECT character_uuid FROM scene_participants
+             WHERE scene_id = ?1 ORDER BY joined_at, rowid",
+        )?;
+        let rows = stmt.query_map([scene.id], |row| row.get(0))?;
+        scene.participants = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(scene)
+    }
+
+    // ==================== PLAY CONTEXT METHODS ====================
+
+    /// The open session and scene of a game, which new activity is tagged with.
+    pub fn play_context(&self, game: &str) -> DbResult<PlayContext> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT sessions.id, scenes.id FROM sessions
+                 LEFT JOIN scenes ON scenes.session_id = sessions.id AND scenes.ended_at IS NULL
+                 WHERE sessions.game = ?1 AND sessions.ended_at IS NULL",
+                [game],
+                |row| {
+                    Ok(PlayContext {
+                        session_id: Some(row.get(0)?),
+                        scene_id: row.get(1)?,
+                    })
+                },
+            )
+            .optional()?
+            .unwrap_or_default())
+    }
+
+    /// Adds a character of the game to the context's session and scene.
+    ///
+    /// Characters already taking part are left as they are.
+    pub fn join_play_context(
+        &self,
+        game: &str,
+        context: PlayContext,
+        character_uuid: &str,
+    ) -> DbResult<()> {
+        match context.session_id {
+            Some(session_id) => {
+                self.join_session(game, session_id, context.scene_id, character_uuid)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn join_session(
+        &self,
+        game: &str,
+        session_id: i64,
+        scene_id: Option<i64>,
+        character_uuid: &str,
+    ) -> DbResult<()> {
+        let in_game = self
+            .get_character_by_uuid(character_uuid)?
+            .is_some_and(|character| character.game == game);
+        if !in_game {
+            return Err(DbError::invalid(format!(
+                "character {} is not in {}",
+                character_uuid, game
+            )));
+        }
+
+        let now = unix_now();
+        self.conn.execute(
+            "INSERT OR IGNORE INTO session_participants (session_id, character_uuid, joined_at)
+             VALUES (?1, ?2, ?3)",
+            (session_id, character_uuid, now),
+        )?;
+        if let Some(scene_id) = scene_id {
+            self.conn.execute(
+                "INSERT OR IGNORE INTO scene_participants (scene_id, character_uuid, joined_at)
+                 VALUES (?1, ?2, ?3)",
+                (scene_id, character_uuid, now),
+            )?;
+        }
+        Ok(())
+    }
+
+    // ==================== CHARACTER LOG METHODS ====================
+
+    /// Records damage or XP for a character, tagged with the game's open
+    /// session and scene, which the character joins.
+    ///
+    /// # Arguments
+    ///
+    /// * `character_uuid` - The character taking damage or earning XP
+    /// * `kind` - Damage or XP
+    /// * `amount` - Points of damage or XP
+    /// * `note` - Where the damage came from or what the XP was for
+    ///
+    /// # Returns
+    ///
+    /// Returns the stored entry, or `DbError::NotFound` if the character
+    /// doesn't exist.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ttdigirpg::entities::database::Database;
+    /// use ttdigirpg::entities::sessions::LogKind;
+    ///
+    /// let db = Database::new(":memory:").unwrap();
+    /// let mara = db.insert_character("Mara", "Noir", None).unwrap();
+    /// let session = db.start_session("Noir", None, None, &[]).unwrap();
+    ///
+    /// let award = db.log_character(&mara, LogKind::Xp, 3, Some("Solved the case")).unwrap();
+    /// assert_eq!(award.session_id, Some(session.id));
+    /// assert_eq!(db.get_session(session.id).unwrap().unwrap().participants, vec![mara]);
+    /// ```
+    pub fn log_character(
+        &self,
+        character_uuid: &str,
+        kind: LogKind,
+        amount: i32,
+        note: Option<&str>,
+    ) -> DbResult<LogEntry> {
+        self.transaction(|db| {
+            let character = db
+                .get_character_by_uuid(character_uuid)?
+                .ok_or_else(|| DbError::not_found(format!("character {}", character_uuid)))?;
+            let context = db.play_context(&character.game)?;
+            db.join_play_context(&character.game, context, character_uuid)?;
+
+            let id: i64 = db.conn.query_row(
+                "INSERT INTO character_log
+                     (game, character_uuid, kind, amount, note, session_id, scene_id, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                 RETURNING id",
+                (
+                    &character.game,
+                    character_uuid,
+                    kind.as_str(),
+                    amount,
+                    note,
+                    context.session_id,
+                    context.scene_id,
+                    unix_now(),
+                ),
+                |row| row.get(0),
+            )?;
+            Ok(db.conn.query_row(
+                &format!("SELECT {} FROM character_log WHERE id = ?1", LOG_COLUMNS),
+                [id],
+                LogEntry::from_row,
+            )?)
+        })
+    }
+
+    /// Lists the damage and XP recorded during a session, oldest first.
+    pub fn session_log(&self, session_id: i64) -> DbResult<Vec<LogEntry>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM character_log WHERE session_id = ?1 ORDER BY id",
+            LOG_COLUMNS
+        ))?;
+        let rows = stmt.query_map([session_id], LogEntry::from_row)?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+}
+
+/// Builds a session without participants from a row selected as `SESSION_COLUMNS`.
+fn session_from_row(row: &Row<'_>) -> rusqlite::Result<Session> {
+    let seed: i64 = row.get(3)?;
+    Ok(Session {
+        id: row.get(0)?,
+        game: row.get(1)?,
+        title: row.get(2)?,
+        seed: seed as u64,
+        started_at: row.get(4)?,
+        ended_at: row.get(5)?,
+        participants: Vec::new(),
+    })
+}
+
+/// Builds a scene without participants from a row selected as `SCENE_COLUMNS`.
+fn scene_from_row(row: &Row<'_>) -> rusqlite::Result<Scene> {
+    let seed: i64 = row.get(3)?;
+    Ok(Scene {
+        id: row.get(0)?,
+        session_id: row.get(1)?,
+        title: row.get(2)?,
+        seed: seed as u64,
+        started_at: row.get(4)?,
+        ended_at: row.get(5)?,
+        participants: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scenes_belong_to_an_open_session() {
+        let db = Database::new(":memory:").unwrap();
+        let mara = db.insert_character("Mara", "Noir", None).unwrap();
+        let vic = db.insert_character("Vic", "Noir", None).unwrap();
+        let stranger = db.insert_character("Stranger", "Other", None).unwrap();
+
+        let session = db
+            .start_session("Noir", None, Some(42), std::slice::from_ref(&mara))
+            .unwrap();
+        let first = db
+            .start_scene(session.id, Some("Docks"), None, std::slice::from_ref(&vic))
+            .unwrap();
+        assert_eq!(first.seed, scene_seed(42, 1));
+        assert!(first.seed < MAX_SEED);
+        assert!(matches!(
+            db.start_scene(session.id, None, None, &[]),
+            Err(DbError::InvalidOperation(_))
+        ));
+
+        db.end_scene(first.id).unwrap();
+        let second = db.start_scene(session.id, None, None, &[]).unwrap();
+        assert_ne!(second.seed, first.seed);
+
+        // Scene participants join the session; outsiders can't join at all
+        let session = db.get_session(session.id).unwrap().unwrap();
+        assert_eq!(session.participants, vec![mara, vic]);
+        assert!(db.start_scene(session.id, None, None, &[stranger]).is_err());
+
+        // Ending the session ends its open scene
+        db.end_session(session.id).unwrap();
+        assert!(db.get_scene(second.id).unwrap().unwrap().ended_at.is_some());
+        assert!(matches!(
+            db.start_scene(session.id, None, None, &[]),
+            Err(DbError::NotFound(_))
+        ));
+        assert_eq!(db.play_context("Noir").unwrap(), PlayContext::default());
+    }
+
+    #[test]
+    fn test_log_entries_are_tagged_with_the_open_scene() {
+        let db = Database::new(":memory:").unwrap();
+        let mara = db.insert_character("Mara", "Noir", None).unwrap();
+
+        let untagged = db.log_character(&mara, LogKind::Damage, 2, None).unwrap();
+        assert_eq!((untagged.session_id, untagged.scene_id), (None, None));
+
+        let session = db.start_session("Noir", None, None, &[]).unwrap();
+        let scene = db.start_scene(session.id, None, None, &[]).unwrap();
+        let hit = db
+            .log_character(&mara, LogKind::Damage, 3, Some("Knife"))
+            .unwrap();
+        assert_eq!(hit.session_id, Some(session.id));
+        assert_eq!(hit.scene_id, Some(scene.id));
+        assert_eq!(
+            db.get_scene(scene.id).unwrap().unwrap().participants,
+            vec![mara.clone()]
+        );
+
+        db.end_scene(scene.id).unwrap();
+        let award = db.log_character(&mara, LogKind::Xp, 5, None).unwrap();
+        assert_eq!((award.session_id, award.scene_id), (Some(session.id), None));
+
+        let log = db.session_log(session.id).unwrap();
+        assert_eq!(log, vec![hit, award]);
+        assert!(db.log_character("nobody", LogKind::Xp, 1, None).is_err());
+    }
+}
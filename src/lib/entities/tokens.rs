@@ -0,0 +1,316 @@
+//! Per-game API tokens.
+//!
+//! A token grants one role in one game. Only a SHA-256 hash of each token is
+//! stored, so the secret is shown once when it is issued and can't be
+//! recovered from the database afterwards.
+
+use std::fmt;
+use std::str::FromStr;
+
+use rand::RngCore;
+use rusqlite::{OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use sha2::{Digest, Sha256};
+
+use super::database::{unix_now, Database};
+use super::error::{DbError, DbResult};
+
+/// Prefix of every issued token, so leaked tokens are easy to recognise.
+pub const TOKEN_PREFIX: &str = "ttd_";
+
+/// What a token holder may do in its game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Can read and change everything in the game
+    Gm,
+    /// Can read the game and change only their own character
+    Player,
+    /// Can read the game but change nothing
+    Observer,
+}
+
+impl Role {
+    /// The value stored in the `role` column.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Gm => "gm",
+            Role::Player => "player",
+            Role::Observer => "observer",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gm" => Ok(Role::Gm),
+            "player" => Ok(Role::Player),
+            "observer" => Ok(Role::Observer),
+            other => Err(format!("Unknown role: {}", other)),
+        }
+    }
+}
+
+/// A row of the `api_tokens` table (never includes the secret).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ApiToken {
+    /// Auto-incremented token ID
+    pub id: i64,
+    /// The game the token grants access to
+    pub game: String,
+    /// What the holder may do
+    pub role: Role,
+    /// UUID of the character a player token controls (`None` for other roles)
+    pub character_uuid: Option<String>,
+    /// Free-text note, e.g. who the token was given to
+    pub label: Option<String>,
+    /// When the token was issued (Unix seconds)
+    pub created_at: i64,
+    /// When the token was revoked, if it has been
+    pub revoked_at: Option<i64>,
+}
+
+impl ApiToken {
+    /// Builds a token from a row selected as
+    /// `id, game, role, character_uuid, label, created_at, revoked_at`.
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let role: String = row.get(2)?;
+        Ok(ApiToken {
+            id: row.get(0)?,
+            game: row.get(1)?,
+            role: role.parse().map_err(|msg: String| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    2,
+                    rusqlite::types::Type::Text,
+                    msg.into(),
+                )
+            })?,
+            character_uuid: row.get(3)?,
+            label: row.get(4)?,
+            created_at: row.get(5)?,
+            revoked_at: row.get(6)?,
+        })
+    }
+}
+
+/// Hex-encoded SHA-256 of a token secret, as stored in `token_hash`.
+fn hash_token(secret: &str) -> String {
+    to_hex(&Sha256::digest(secret.as_bytes()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+const TOKEN_COLUMNS: &str = "id, game, role, character_uuid, label, created_at, revoked_at";
+
+impl Database {
+    // ==================== API TOKEN METHODS ====================
+
+    /// Issues a new token for a game.
+    ///
+    /// # Arguments
+    ///
+    /// * `game` - The game the token grants access to
+    /// * `role` - What the holder may do
+    /// * `character_uuid` - The character a player controls; required for
+    ///   players and not allowed for other roles
+    /// * `label` - Optional note about who holds the token
+    ///
+    /// # Returns
+    ///
+    /// Returns the stored token and its secret, which is not stored and can't
+    /// be retrieved again. Returns `DbError::InvalidOperation` if the character
+    /// rule above is broken or the character is in another game.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ttdigirpg::entities::database::Database;
+    /// use ttdigirpg::entities::tokens::Role;
+    ///
+    /// let db = Database::new(":memory:").unwrap();
+    /// let (token, secret) = db.issue_token("Game", Role::Gm, None, Some("Alex")).unwrap();
+    ///
+    /// let holder = db.authenticate_token(&secret).unwrap().unwrap();
+    /// assert_eq!(holder.id, token.id);
+    /// assert_eq!(holder.role, Role::Gm);
+    /// ```
+    pub fn issue_token(
+        &self,
+        game: &str,
+        role: Role,
+        character_uuid: Option<&str>,
+        label: Option<&str>,
+    ) -> DbResult<(ApiToken, String)> {
+        match (role, character_uuid) {
+            (Role::Player, None) => {
+                return Err(DbError::invalid("a player token needs a character"))
+            }
+            (Role::Player, Some(uuid)) => {
+                let in_game = self
+                    .get_character_by_uuid(uuid)?
+                    .is_some_and(|character| character.game == game);
+                if !in_game {
+                    return Err(DbError::invalid(format!(
+                        "character {} is not in {}",
+                        uuid, game
+                    )));
+                }
+            }
+            (_, Some(_)) => {
+                return Err(DbError::invalid(format!(
+                    "only player tokens control a character, not {} tokens",
+                    role
+                )))
+            }
+            (_, None) => {}
+        }
+
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let secret = format!("{}{}", TOKEN_PREFIX, to_hex(&bytes));
+
+        let id: i64 = self.conn.query_row(
+            "INSERT INTO api_tokens (game, token_hash, role, character_uuid, label, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             RETURNING id",
+            (
+                game,
+                hash_token(&secret),
+                role.as_str(),
+                character_uuid,
+                label,
+                unix_now(),
+            ),
+            |row| row.get(0),
+        )?;
+
+        let token = self
+            .get_token(id)?
+            .ok_or_else(|| DbError::not_found(format!("token {}", id)))?;
+        Ok((token, secret))
+    }
+
+    /// Looks up the unrevoked token with this secret.
+    pub fn authenticate_token(&self, secret: &str) -> DbResult<Option<ApiToken>> {
+        Ok(self
+            .conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM api_tokens WHERE token_hash = ?1 AND revoked_at IS NULL",
+                    TOKEN_COLUMNS
+                ),
+                [hash_token(secret)],
+                ApiToken::from_row,
+            )
+            .optional()?)
+    }
+
+    /// Retrieves a token by ID, revoked or not.
+    pub fn get_token(&self, token_id: i64) -> DbResult<Option<ApiToken>> {
+        Ok(self
+            .conn
+            .query_row(
+                &format!("SELECT {} FROM api_tokens WHERE id = ?1", TOKEN_COLUMNS),
+                [token_id],
+                ApiToken::from_row,
+            )
+            .optional()?)
+    }
+
+    /// Lists a game's tokens, oldest first, including revoked ones.
+    pub fn list_tokens(&self, game: &str) -> DbResult<Vec<ApiToken>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM api_tokens WHERE game = ?1 ORDER BY id",
+            TOKEN_COLUMNS
+        ))?;
+        let rows = stmt.query_map([game], ApiToken::from_row)?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// Revokes a token so it no longer authenticates.
+    ///
+    /// # Returns
+    ///
+    /// Returns `DbError::NotFound` if no unrevoked token has this ID.
+    pub fn revoke_token(&self, token_id: i64) -> DbResult<()> {
+        let updated = self.conn.execute(
+            "UPDATE api_tokens SET revoked_at = ?1 WHERE id = ?2 AND revoked_at IS NULL",
+            (unix_now(), token_id),
+        )?;
+        if updated == 0 {
+            return Err(DbError::not_found(format!("token {}", token_id)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_the_hash_is_stored() {
+        let db = Database::new(":memory:").unwrap();
+        let (_, secret) = db.issue_token("Game", Role::Observer, None, None).unwrap();
+        assert!(secret.starts_with(TOKEN_PREFIX));
+
+        let stored: String = db
+            .conn
+            .query_row("SELECT token_hash FROM api_tokens", [], |row| row.get(0))
+            .unwrap();
+        assert_ne!(stored, secret);
+        assert_eq!(stored, hash_token(&secret));
+        assert!(db.authenticate_token("ttd_guess").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_player_tokens_need_a_character_in_the_game() {
+        let db = Database::new(":memory:").unwrap();
+        let alice = db.insert_character("Alice", "Game", None).unwrap();
+        let bob = db.insert_character("Bob", "Other", None).unwrap();
+
+        for (role, character) in [
+            (Role::Player, None),
+            (Role::Player, Some(bob.as_str())),
+            (Role::Gm, Some(alice.as_str())),
+        ] {
+            let err = db.issue_token("Game", role, character, None).unwrap_err();
+            assert!(matches!(err, DbError::InvalidOperation(_)));
+        }
+
+        let (token, _) = db
+            .issue_token("Game", Role::Player, Some(&alice), None)
+            .unwrap();
+        assert_eq!(token.character_uuid.as_deref(), Some(alice.as_str()));
+
+        // Deleting the character deletes its player tokens
+        db.delete_character_by_uuid(&alice).unwrap();
+        assert!(db.get_token(token.id).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_revoked_tokens_stop_working() {
+        let db = Database::new(":memory:").unwrap();
+        let (token, secret) = db.issue_token("Game", Role::Gm, None, None).unwrap();
+
+        db.revoke_token(token.id).unwrap();
+        assert!(db.authenticate_token(&secret).unwrap().is_none());
+        assert!(db.list_tokens("Game").unwrap()[0].revoked_at.is_some());
+        assert!(matches!(
+            db.revoke_token(token.id).unwrap_err(),
+            DbError::NotFound(_)
+        ));
+    }
+}
@@ -0,0 +1,687 @@
+//! Moving objects between characters.
+//!
+//! `transfer_object` moves part or all of a stack from one character to
+//! another in a single transaction, so an item can never vanish between the
+//! remove and the add. Trade offers build on it: one character proposes to
+//! swap some of their objects for some of another character's, and accepting
+//! the offer performs every transfer atomically or none of them.
+
+use rusqlite::{OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use std::fmt;
+use std::str::FromStr;
+
+use super::database::Database;
+use super::error::{DbError, DbResult};
+use super::records::CharacterRecord;
+
+/// Columns selected by every trade offer query, in `TradeOffer::from_row` order.
+const TRADE_COLUMNS: &str = "id, game, from_uuid, to_uuid, offered, requested, status,
+     created_at, expires_at, resolved_at";
+
+/// A quantity of one object that is part of a trade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct TradeItem {
+    /// ID of the object definition
+    pub object_id: i64,
+    /// How many of it change hands
+    pub quantity: i32,
+}
+
+/// Where a trade offer is in its lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TradeStatus {
+    /// Waiting for the recipient to answer
+    Pending,
+    /// The recipient accepted and the items were exchanged
+    Accepted,
+    /// The recipient turned the offer down
+    Rejected,
+    /// Nobody answered before the offer's deadline
+    Expired,
+}
+
+impl TradeStatus {
+    /// The value stored in the `status` column.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TradeStatus::Pending => "pending",
+            TradeStatus::Accepted => "accepted",
+            TradeStatus::Rejected => "rejected",
+            TradeStatus::Expired => "expired",
+        }
+    }
+}
+
+impl fmt::Display for TradeStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for TradeStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(TradeStatus::Pending),
+            "accepted" => Ok(TradeStatus::Accepted),
+            "rejected" => Ok(TradeStatus::Rejected),
+            "expired" => Ok(TradeStatus::Expired),
+            other => Err(format!("Unknown trade status: {}", other)),
+        }
+    }
+}
+
+/// A row of the `trade_offers` table.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct TradeOffer {
+    /// Auto-incremented offer ID
+    pub id: i64,
+    /// The game both characters belong to
+    pub game: String,
+    /// UUID of the character making the offer
+    pub from_uuid: String,
+    /// UUID of the character the offer is made to
+    pub to_uuid: String,
+    /// What the proposer gives
+    pub offered: Vec<TradeItem>,
+    /// What the proposer wants in return
+    pub requested: Vec<TradeItem>,
+    /// Current status
+    pub status: TradeStatus,
+    /// When the offer was made (Unix seconds)
+    pub created_at: i64,
+    /// When the offer stops being acceptable (Unix seconds), if ever
+    pub expires_at: Option<i64>,
+    /// When the offer was accepted, rejected or expired (Unix seconds)
+    pub resolved_at: Option<i64>,
+}
+
+impl TradeOffer {
+    /// Builds an offer from a row selected as `TRADE_COLUMNS`.
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        let status: String = row.get(6)?;
+        Ok(TradeOffer {
+            id: row.get(0)?,
+            game: row.get(1)?,
+            from_uuid: row.get(2)?,
+            to_uuid: row.get(3)?,
+            offered: parse_items(row, 4)?,
+            requested: parse_items(row, 5)?,
+            status: status.parse().map_err(|msg: String| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    6,
+                    rusqlite::types::Type::Text,
+                    msg.into(),
+                )
+            })?,
+            created_at: row.get(7)?,
+            expires_at: row.get(8)?,
+            resolved_at: row.get(9)?,
+        })
+    }
+}
+
+/// Decodes a JSON list of trade items stored in column `idx`.
+fn parse_items(row: &Row<'_>, idx: usize) -> rusqlite::Result<Vec<TradeItem>> {
+    let json: String = row.get(idx)?;
+    serde_json::from_str(&json).map_err(|err| {
+        rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, err.into())
+    })
+}
+
+impl Database {
+    // ==================== TRANSFER METHODS ====================
+
+    /// Moves `quantity` of an object from one character's stack to another's.
+    ///
+    /// Both characters must exist and belong to the same game, and the sender
+    /// must hold at least `quantity`. A stack emptied by the transfer is
+    /// removed; the recipient's stack is created or topped up. Either the
+    /// whole transfer happens or nothing changes.
+    ///
+    /// # Arguments
+    ///
+    /// * `from_uuid` - UUID of the character giving the object
+    /// * `to_uuid` - UUID of the character receiving it
+    /// * `object_id` - The ID of the object to move
+    /// * `quantity` - How many to move (must be positive)
+    ///
+    /// # Returns
+    ///
+    /// Returns `DbError::NotFound` if either character doesn't exist, or
+    /// `DbError::InvalidOperation` if the transfer breaks a rule (different
+    /// games, same character, non-positive quantity, not enough owned).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ttdigirpg::entities::database::Database;
+    ///
+    /// let db = Database::new(":memory:").unwrap();
+    /// let alice = db.insert_character("Alice", "Game", None).unwrap();
+    /// let bob = db.insert_character("Bob", "Game", None).unwrap();
+    /// let arrows = db.insert_object("Arrow", "ammo", None).unwrap();
+    /// db.add_object_to_character("Game", "Alice", arrows, 20).unwrap();
+    ///
+    /// db.transfer_object(&alice, &bob, arrows, 5).unwrap();
+    /// assert_eq!(db.get_character_objects("Game", "Alice").unwrap()[0].quantity, 15);
+    /// assert_eq!(db.get_character_objects("Game", "Bob").unwrap()[0].quantity, 5);
+    /// ```
+    pub fn transfer_object(
+        &self,
+        from_uuid: &str,
+        to_uuid: &str,
+        object_id: i64,
+        quantity: i32,
+    ) -> DbResult<()> {
+        if quantity <= 0 {
+            return Err(DbError::invalid(format!(
+                "transfer quantity must be positive, got {}",
+                quantity
+            )));
+        }
+
+        self.transaction(|db| {
+            let (from, to) = db.trading_pair(from_uuid, to_uuid)?;
+
+            let owned = db.stack_quantity(&from.game, &from.name, object_id)?;
+            if owned < quantity {
+                return Err(DbError::invalid(format!(
+                    "{} holds {} of object {} but {} were requested",
+                    from.name, owned, object_id, quantity
+                )));
+            }
+
+            if owned == quantity {
+                db.remove_object_from_character(&from.game, &from.name, object_id)?;
+            } else {
+                db.update_object_quantity(&from.game, &from.name, object_id, owned - quantity)?;
+            }
+            db.add_object_to_character(&to.game, &to.name, object_id, quantity)?;
+            Ok(())
+        })
+    }
+
+    /// Looks up both sides of a transfer and checks they can trade.
+    fn trading_pair(
+        &self,
+        from_uuid: &str,
+        to_uuid: &str,
+    ) -> DbResult<(CharacterRecord, CharacterRecord)> {
+        if from_uuid == to_uuid {
+            return Err(DbError::invalid("a character cannot trade with itself"));
+        }
+
+        let from = self
+            .get_character_by_uuid(from_uuid)?
+            .ok_or_else(|| DbError::not_found(format!("character {}", from_uuid)))?;
+        let to = self
+            .get_character_by_uuid(to_uuid)?
+            .ok_or_else(|| DbError::not_found(format!("character {}", to_uuid)))?;
+
+        if from.game != to.game {
+            return Err(DbError::invalid(format!(
+                "{} ({}) and {} ({}) are in different games",
+                from.name, from.game, to.name, to.game
+            )));
+        }
+
+        Ok((from, to))
+    }
+
+    /// How many of an object a character holds (0 if none).
+    fn stack_quantity(&self, game: &str, character_name: &str, object_id: i64) -> DbResult<i32> {
+        let quantity: Option<Option<i32>> = self
+            .conn
+            .query_row(
+                "SELECT quantity FROM character_objects
+                 WHERE game = ?1 AND character_name = ?2 AND object_id = ?3",
+                (game, character_name, object_id),
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(quantity.map_or(0, |quantity| quantity.unwrap_or(1)))
+    }
+
+    // ==================== TRADE OFFER METHODS ====================
+
+    /// Records an offer from one character to another.
+    ///
+    /// The proposer must currently hold everything they offer; ownership is
+    /// checked again when the offer is accepted.
+    ///
+    /// # Arguments
+    ///
+    /// * `from_uuid` - UUID of the proposing character
+    /// * `to_uuid` - UUID of the character receiving the offer
+    /// * `offered` - Objects the proposer gives
+    /// * `requested` - Objects the proposer wants in return
+    /// * `expires_at` - Optional deadline (Unix seconds)
+    ///
+    /// # Returns
+    ///
+    /// Returns the new offer.
+    pub fn propose_trade(
+        &self,
+        from_uuid: &str,
+        to_uuid: &str,
+        offered: &[TradeItem],
+        requested: &[TradeItem],
+        expires_at: Option<i64>,
+    ) -> DbResult<TradeOffer> {
+        if offered.is_empty() && requested.is_empty() {
+            return Err(DbError::invalid("a trade must include at least one item"));
+        }
+        if let Some(item) = offered
+            .iter()
+            .chain(requested)
+            .find(|item| item.quantity <= 0)
+        {
+            return Err(DbError::invalid(format!(
+                "trade quantity for object {} must be positive",
+                item.object_id
+            )));
+        }
+
+        let (from, _) = self.trading_pair(from_uuid, to_uuid)?;
+        for item in offered {
+            let owned = self.stack_quantity(&from.game, &from.name, item.object_id)?;
+            if owned < item.quantity {
+                return Err(DbError::invalid(format!(
+                    "{} holds {} of object {} but offers {}",
+                    from.name, owned, item.object_id, item.quantity
+                )));
+            }
+        }
+
+        let offered_json = serde_json::to_string(offered).expect("trade items serialize");
+        let requested_json = serde_json::to_string(requested).expect("trade items serialize");
+        let id = self.conn.query_row(
+            "INSERT INTO trade_offers (game, from_uuid, to_uuid, offered, requested, created_at, expires_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             RETURNING id",
+            (
+                &from.game,
+                from_uuid,
+                to_uuid,
+                &offered_json,
+                &requested_json,
+                super::database::unix_now(),
+                expires_at,
+            ),
+            |row| row.get(0),
+        )?;
+
+        self.get_trade(id)?
+            .ok_or_else(|| DbError::not_found(format!("trade offer {}", id)))
+    }
+
+    /// Retrieves a trade offer by ID.
+    pub fn get_trade(&self, trade_id: i64) -> DbResult<Option<TradeOffer>> {
+        Ok(self
+            .conn
+            .query_row(
+                &format!("SELECT {} FROM trade_offers WHERE id = ?1", TRADE_COLUMNS),
+                [trade_id],
+                TradeOffer::from_row,
+            )
+            .optional()?)
+    }
+
+    /// Lists the offers a character made or received, newest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `character_uuid` - UUID of either party
+    /// * `status` - Only return offers with this status, if given
+    pub fn list_trades(
+        &self,
+        character_uuid: &str,
+        status: Option<TradeStatus>,
+    ) -> DbResult<Vec<TradeOffer>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM trade_offers
+             WHERE (from_uuid = ?1 OR to_uuid = ?1) AND (?2 IS NULL OR status = ?2)
+             ORDER BY created_at DESC, id DESC",
+            TRADE_COLUMNS
+        ))?;
+
+        let rows = stmt.query_map(
+            (character_uuid, status.map(TradeStatus::as_str)),
+            TradeOffer::from_row,
+        )?;
+
+        let mut trades = Vec::new();
+        for row in rows {
+            trades.push(row?);
+        }
+
+        Ok(trades)
+    }
+
+    /// Accepts a pending offer, exchanging every item in one transaction.
+    ///
+    /// If either side no longer holds what they promised, nothing moves and
+    /// the offer stays pending.
+    ///
+    /// # Arguments
+    ///
+    /// * `trade_id` - The offer to accept
+    /// * `now` - Current time (Unix seconds), checked against the deadline
+    ///
+    /// # Returns
+    ///
+    /// Returns the accepted offer, or `DbError::InvalidOperation` if it is no
+    /// longer pending or has passed its deadline (in which case it is marked
+    /// expired).
+    pub fn accept_trade(&self, trade_id: i64, now: i64) -> DbResult<TradeOffer> {
+        self.expire_trades(now)?;
+
+        self.transaction(|db| {
+            let trade = db.pending_trade(trade_id)?;
+
+            for item in &trade.offered {
+                db.transfer_object(
+                    &trade.from_uuid,
+                    &trade.to_uuid,
+                    item.object_id,
+                    item.quantity,
+                )?;
+            }
+            for item in &trade.requested {
+                db.transfer_object(
+                    &trade.to_uuid,
+                    &trade.from_uuid,
+                    item.object_id,
+                    item.quantity,
+                )?;
+            }
+
+            db.resolve_trade(trade_id, TradeStatus::Accepted, now)
+        })
+    }
+
+    /// Rejects a pending offer. No items move.
+    pub fn reject_trade(&self, trade_id: i64, now: i64) -> DbResult<TradeOffer> {
+        self.expire_trades(now)?;
+
+        self.transaction(|db| {
+            db.pending_trade(trade_id)?;
+            db.resolve_trade(trade_id, TradeStatus::Rejected, now)
+        })
+    }
+
+    /// Marks every pending offer whose deadline is at or before `now` as expired.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of offers expired.
+    pub fn expire_trades(&self, now: i64) -> DbResult<usize> {
+        Ok(self.conn.execute(
+            "UPDATE trade_offers SET status = 'expired', resolved_at = ?1
+             WHERE status = 'pending' AND expires_at IS NOT NULL AND expires_at <= ?1",
+            [now],
+        )?)
+    }
+
+    /// Loads an offer and checks it can still be answered.
+    fn pending_trade(&self, trade_id: i64) -> DbResult<TradeOffer> {
+        let trade = self
+            .get_trade(trade_id)?
+            .ok_or_else(|| DbError::not_found(format!("trade offer {}", trade_id)))?;
+
+        if trade.status != TradeStatus::Pending {
+            return Err(DbError::invalid(format!(
+                "trade offer {} is already {}",
+                trade_id, trade.status
+            )));
+        }
+
+        Ok(trade)
+    }
+
+    /// Sets a final status on an offer and returns the updated row.
+    fn resolve_trade(&self, trade_id: i64, status: TradeStatus, now: i64) -> DbResult<TradeOffer> {
+        self.conn.execute(
+            "UPDATE trade_offers SET status = ?1, resolved_at = ?2 WHERE id = ?3",
+            (status.as_str(), now, trade_id),
+        )?;
+
+        self.get_trade(trade_id)?
+            .ok_or_else(|| DbError::not_found(format!("trade offer {}", trade_id)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Market {
+        db: Database,
+        alice: String,
+        bob: String,
+        gold: i64,
+        sword: i64,
+    }
+
+    fn setup_market() -> Market {
+        let db = Database::new(":memory:").unwrap();
+        let alice = db.insert_character("Alice", "Game", None).unwrap();
+        let bob = db.insert_character("Bob", "Game", None).unwrap();
+        let gold = db.insert_object("Gold Coin", "currency", None).unwrap();
+        let sword = db.insert_object("Sword", "weapon", None).unwrap();
+        db.add_object_to_character("Game", "Alice", gold, 100)
+            .unwrap();
+        db.add_object_to_character("Game", "Bob", sword, 1).unwrap();
+        Market {
+            db,
+            alice,
+            bob,
+            gold,
+            sword,
+        }
+    }
+
+    fn held(db: &Database, name: &str, object_id: i64) -> i32 {
+        db.stack_quantity("Game", name, object_id).unwrap()
+    }
+
+    #[test]
+    fn test_transfer_partial_stack() {
+        let m = setup_market();
+        m.db.transfer_object(&m.alice, &m.bob, m.gold, 30).unwrap();
+
+        assert_eq!(held(&m.db, "Alice", m.gold), 70);
+        assert_eq!(held(&m.db, "Bob", m.gold), 30);
+    }
+
+    #[test]
+    fn test_transfer_whole_stack_removes_row() {
+        let m = setup_market();
+        m.db.transfer_object(&m.bob, &m.alice, m.sword, 1).unwrap();
+
+        assert!(m
+            .db
+            .get_character_objects("Game", "Bob")
+            .unwrap()
+            .is_empty());
+        assert_eq!(held(&m.db, "Alice", m.sword), 1);
+    }
+
+    #[test]
+    fn test_transfer_more_than_owned_changes_nothing() {
+        let m = setup_market();
+        let err =
+            m.db.transfer_object(&m.alice, &m.bob, m.gold, 101)
+                .unwrap_err();
+
+        assert!(matches!(err, DbError::InvalidOperation(_)));
+        assert_eq!(held(&m.db, "Alice", m.gold), 100);
+        assert_eq!(held(&m.db, "Bob", m.gold), 0);
+    }
+
+    #[test]
+    fn test_transfer_rejects_bad_parties() {
+        let m = setup_market();
+        let stranger = m.db.insert_character("Carol", "Other Game", None).unwrap();
+        m.db.add_object_to_character("Other Game", "Carol", m.gold, 5)
+            .unwrap();
+
+        assert!(matches!(
+            m.db.transfer_object(&stranger, &m.alice, m.gold, 1),
+            Err(DbError::InvalidOperation(_))
+        ));
+        assert!(matches!(
+            m.db.transfer_object(&m.alice, &m.alice, m.gold, 1),
+            Err(DbError::InvalidOperation(_))
+        ));
+        assert!(matches!(
+            m.db.transfer_object(&m.alice, "missing", m.gold, 1),
+            Err(DbError::NotFound(_))
+        ));
+        assert!(matches!(
+            m.db.transfer_object(&m.alice, &m.bob, m.gold, 0),
+            Err(DbError::InvalidOperation(_))
+        ));
+    }
+
+    #[test]
+    fn test_accepted_trade_swaps_items() {
+        let m = setup_market();
+        let offer =
+            m.db.propose_trade(
+                &m.alice,
+                &m.bob,
+                &[TradeItem {
+                    object_id: m.gold,
+                    quantity: 40,
+                }],
+                &[TradeItem {
+                    object_id: m.sword,
+                    quantity: 1,
+                }],
+                None,
+            )
+            .unwrap();
+        assert_eq!(offer.status, TradeStatus::Pending);
+
+        let accepted = m.db.accept_trade(offer.id, offer.created_at).unwrap();
+        assert_eq!(accepted.status, TradeStatus::Accepted);
+        assert_eq!(held(&m.db, "Alice", m.gold), 60);
+        assert_eq!(held(&m.db, "Alice", m.sword), 1);
+        assert_eq!(held(&m.db, "Bob", m.gold), 40);
+        assert_eq!(held(&m.db, "Bob", m.sword), 0);
+
+        // A settled offer can't be answered again
+        assert!(matches!(
+            m.db.accept_trade(offer.id, offer.created_at),
+            Err(DbError::InvalidOperation(_))
+        ));
+    }
+
+    #[test]
+    fn test_failed_acceptance_rolls_back_every_leg() {
+        let m = setup_market();
+        let offer =
+            m.db.propose_trade(
+                &m.alice,
+                &m.bob,
+                &[TradeItem {
+                    object_id: m.gold,
+                    quantity: 40,
+                }],
+                &[TradeItem {
+                    object_id: m.sword,
+                    quantity: 1,
+                }],
+                None,
+            )
+            .unwrap();
+
+        // Bob sells the sword elsewhere before answering
+        m.db.remove_object_from_character("Game", "Bob", m.sword)
+            .unwrap();
+
+        let err = m.db.accept_trade(offer.id, offer.created_at).unwrap_err();
+        assert!(matches!(err, DbError::InvalidOperation(_)));
+        assert_eq!(held(&m.db, "Alice", m.gold), 100);
+        assert_eq!(held(&m.db, "Bob", m.gold), 0);
+        assert_eq!(
+            m.db.get_trade(offer.id).unwrap().unwrap().status,
+            TradeStatus::Pending
+        );
+    }
+
+    #[test]
+    fn test_reject_and_expire() {
+        let m = setup_market();
+        let gold = [TradeItem {
+            object_id: m.gold,
+            quantity: 1,
+        }];
+        let rejected =
+            m.db.propose_trade(&m.alice, &m.bob, &gold, &[], None)
+                .unwrap();
+        let expiring =
+            m.db.propose_trade(&m.alice, &m.bob, &gold, &[], Some(1_000))
+                .unwrap();
+
+        let rejected = m.db.reject_trade(rejected.id, 500).unwrap();
+        assert_eq!(rejected.status, TradeStatus::Rejected);
+        assert_eq!(held(&m.db, "Alice", m.gold), 100);
+
+        assert!(matches!(
+            m.db.accept_trade(expiring.id, 1_000),
+            Err(DbError::InvalidOperation(_))
+        ));
+        let expired = m.db.get_trade(expiring.id).unwrap().unwrap();
+        assert_eq!(expired.status, TradeStatus::Expired);
+        assert_eq!(expired.resolved_at, Some(1_000));
+    }
+
+    #[test]
+    fn test_propose_requires_offered_items() {
+        let m = setup_market();
+        let err =
+            m.db.propose_trade(
+                &m.bob,
+                &m.alice,
+                &[TradeItem {
+                    object_id: m.gold,
+                    quantity: 1,
+                }],
+                &[],
+                None,
+            )
+            .unwrap_err();
+
+        assert!(matches!(err, DbError::InvalidOperation(_)));
+    }
+
+    #[test]
+    fn test_list_trades_filters_by_status() {
+        let m = setup_market();
+        let gold = [TradeItem {
+            object_id: m.gold,
+            quantity: 1,
+        }];
+        let first =
+            m.db.propose_trade(&m.alice, &m.bob, &gold, &[], None)
+                .unwrap();
+        m.db.propose_trade(&m.alice, &m.bob, &gold, &[], None)
+            .unwrap();
+        m.db.reject_trade(first.id, first.created_at).unwrap();
+
+        assert_eq!(m.db.list_trades(&m.bob, None).unwrap().len(), 2);
+        let pending =
+            m.db.list_trades(&m.alice, Some(TradeStatus::Pending))
+                .unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_ne!(pending[0].id, first.id);
+    }
+}
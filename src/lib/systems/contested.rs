@@ -0,0 +1,244 @@
+//! Contested and resisted roll resolution.
+//!
+//! A contested roll pits two characters' pools against each other (e.g. the
+//! sneak's `stealth` against the guard's `awareness`) and compares net
+//! successes. A resisted roll is one-sided: the defender doesn't roll, but a
+//! static trait such as `physical` soaks successes off the attacker's result.
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use super::dice::{DicePool, RollResult};
+use crate::entities::character::{Character, Stat};
+
+/// Who wins a contested roll when both sides score the same net successes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TieRule {
+    /// The status quo holds; the acting side has to beat the defender (default)
+    #[default]
+    DefenderWins,
+    /// The acting side wins ties
+    AttackerWins,
+    /// Ties are reported as a draw and left to the GM
+    Draw,
+}
+
+/// The side that came out ahead in a contest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContestWinner {
+    Attacker,
+    Defender,
+    Draw,
+}
+
+/// Result of a contested roll, including both sides' full breakdowns.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContestedOutcome {
+    /// The acting character's roll
+    pub attacker: RollResult,
+    /// The opposing character's roll
+    pub defender: RollResult,
+    /// Which side won after applying botches and the tie rule
+    pub winner: ContestWinner,
+    /// Attacker net successes minus defender net successes
+    pub margin: i32,
+}
+
+/// Result of a roll made against a static resistance trait.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResistedOutcome {
+    /// The attacker's roll before resistance is applied
+    pub roll: RollResult,
+    /// The trait that resisted the roll
+    pub resisted_by: Stat,
+    /// Successes removed by the defender's trait
+    pub resistance: u32,
+    /// Successes left over after resistance
+    pub net_successes: u32,
+}
+
+impl ResistedOutcome {
+    /// Returns true if any success got through the resistance.
+    pub fn is_success(&self) -> bool {
+        self.net_successes > 0
+    }
+}
+
+/// Rolls both pools and decides the contest.
+///
+/// # Examples
+///
+/// ```
+/// use rand::SeedableRng;
+/// use rand::rngs::StdRng;
+/// use ttdigirpg::entities::character::{Character, Stat};
+/// use ttdigirpg::systems::contested::{contested_roll, TieRule};
+/// use ttdigirpg::systems::dice::DicePool;
+///
+/// let thief = Character::new("Thief".to_string());
+/// let guard = Character::new("Guard".to_string());
+/// let mut rng = StdRng::seed_from_u64(1);
+///
+/// let outcome = contested_roll(
+///     (&thief, &DicePool::new(&[Stat::Physical, Stat::Stealth])),
+///     (&guard, &DicePool::new(&[Stat::Mental, Stat::Awareness])),
+///     TieRule::DefenderWins,
+///     &mut rng,
+/// );
+/// assert_eq!(outcome.attacker.faces.len(), 2);
+/// ```
+pub fn contested_roll<R: Rng + ?Sized>(
+    attacker: (&Character, &DicePool),
+    defender: (&Character, &DicePool),
+    tie_rule: TieRule,
+    rng: &mut R,
+) -> ContestedOutcome {
+    let attack = attacker.1.roll(attacker.0, rng);
+    let defense = defender.1.roll(defender.0, rng);
+    resolve_contest(attack, defense, tie_rule)
+}
+
+/// Decides a contest from two finished rolls.
+///
+/// A botching side always loses to a side that didn't botch; otherwise the
+/// higher net successes win and equal results fall to the tie rule.
+pub fn resolve_contest(
+    attacker: RollResult,
+    defender: RollResult,
+    tie_rule: TieRule,
+) -> ContestedOutcome {
+    let margin = attacker.net_successes as i32 - defender.net_successes as i32;
+
+    let winner = match (attacker.botch, defender.botch) {
+        (true, false) => ContestWinner::Defender,
+        (false, true) => ContestWinner::Attacker,
+        _ if margin > 0 => ContestWinner::Attacker,
+        _ if margin < 0 => ContestWinner::Defender,
+        _ => match tie_rule {
+            TieRule::DefenderWins => ContestWinner::Defender,
+            TieRule::AttackerWins => ContestWinner::Attacker,
+            TieRule::Draw => ContestWinner::Draw,
+        },
+    };
+
+    ContestedOutcome {
+        attacker,
+        defender,
+        winner,
+        margin,
+    }
+}
+
+/// Rolls the attacker's pool and subtracts the defender's rating in `resisted_by`.
+pub fn resisted_roll<R: Rng + ?Sized>(
+    attacker: (&Character, &DicePool),
+    defender: &Character,
+    resisted_by: Stat,
+    rng: &mut R,
+) -> ResistedOutcome {
+    let roll = attacker.1.roll(attacker.0, rng);
+    resolve_resistance(roll, resisted_by, defender.stat(resisted_by))
+}
+
+/// Applies a static resistance rating to a finished roll.
+pub fn resolve_resistance(roll: RollResult, resisted_by: Stat, resistance: u32) -> ResistedOutcome {
+    let net_successes = roll.net_successes.saturating_sub(resistance);
+    ResistedOutcome {
+        roll,
+        resisted_by,
+        resistance,
+        net_successes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn roll(faces: &[u32]) -> RollResult {
+        RollResult::from_faces(faces.to_vec(), 6)
+    }
+
+    #[test]
+    fn test_higher_net_successes_wins() {
+        let outcome = resolve_contest(roll(&[8, 9, 2]), roll(&[7, 3]), TieRule::DefenderWins);
+
+        assert_eq!(outcome.winner, ContestWinner::Attacker);
+        assert_eq!(outcome.margin, 1);
+    }
+
+    #[test]
+    fn test_tie_rules() {
+        let tie = |rule| resolve_contest(roll(&[8, 2]), roll(&[6, 3]), rule).winner;
+
+        assert_eq!(tie(TieRule::DefenderWins), ContestWinner::Defender);
+        assert_eq!(tie(TieRule::AttackerWins), ContestWinner::Attacker);
+        assert_eq!(tie(TieRule::Draw), ContestWinner::Draw);
+    }
+
+    #[test]
+    fn test_botch_loses_even_on_equal_net() {
+        // Both sides net zero, but only the attacker botched
+        let outcome = resolve_contest(roll(&[1, 3]), roll(&[2, 3]), TieRule::AttackerWins);
+
+        assert_eq!(outcome.winner, ContestWinner::Defender);
+        assert!(outcome.attacker.botch);
+    }
+
+    #[test]
+    fn test_resistance_reduces_successes() {
+        let outcome = resolve_resistance(roll(&[9, 8, 7, 2]), Stat::Physical, 2);
+
+        assert_eq!(outcome.net_successes, 1);
+        assert!(outcome.is_success());
+
+        let soaked = resolve_resistance(roll(&[9, 2]), Stat::Physical, 3);
+        assert_eq!(soaked.net_successes, 0);
+        assert!(!soaked.is_success());
+    }
+
+    #[test]
+    fn test_contested_roll_keeps_both_breakdowns() {
+        let mut thief = Character::new("Thief".to_string());
+        thief.stealth = 4;
+        let mut guard = Character::new("Guard".to_string());
+        guard.awareness = 2;
+
+        let mut rng = StdRng::seed_from_u64(99);
+        let outcome = contested_roll(
+            (&thief, &DicePool::new(&[Stat::Physical, Stat::Stealth])),
+            (&guard, &DicePool::new(&[Stat::Mental, Stat::Awareness])),
+            TieRule::Draw,
+            &mut rng,
+        );
+
+        assert_eq!(outcome.attacker.faces.len(), 5);
+        assert_eq!(outcome.defender.faces.len(), 3);
+        assert_eq!(
+            outcome.margin,
+            outcome.attacker.net_successes as i32 - outcome.defender.net_successes as i32
+        );
+    }
+
+    #[test]
+    fn test_resisted_roll_uses_defender_stat() {
+        let attacker = Character::new("Brute".to_string());
+        let mut defender = Character::new("Tank".to_string());
+        defender.physical = 5;
+
+        let mut rng = StdRng::seed_from_u64(3);
+        let outcome = resisted_roll(
+            (&attacker, &DicePool::new(&[Stat::Physical, Stat::Brawl])),
+            &defender,
+            Stat::Physical,
+            &mut rng,
+        );
+
+        assert_eq!(outcome.resistance, 5);
+        assert_eq!(outcome.net_successes, 0, "Two dice can never beat five resistance");
+    }
+}
@@ -0,0 +1,514 @@
+//! Crafting from recipes.
+//!
+//! Recipes live in JSON data files (see `data/recipes/`), each file holding a
+//! list of recipes. A recipe names the objects it consumes and produces, the
+//! dice pool rolled to make it and how many in-game minutes it takes:
+//!
+//! ```json
+//! {
+//!   "id": "smoke_bomb",
+//!   "name": "Smoke Bomb",
+//!   "pool": { "stats": ["mental", "science"], "difficulty": 7 },
+//!   "minutes": 30,
+//!   "inputs": [{ "object": "Saltpeter", "quantity": 2 }, { "object": "Sugar" }],
+//!   "output": { "object": "Smoke Bomb" }
+//! }
+//! ```
+//!
+//! Ingredients are item instances the character carries (including inside
+//! containers). A successful roll consumes them and creates the output with a
+//! `quality` property equal to the net successes (capped at `MAX_QUALITY`). A
+//! failed roll wastes only time; a botch ruins the ingredients. Every
+//! inventory change happens in one transaction.
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+use super::dice::{DicePool, RollResult, DIE_SIDES, MAX_POOL_MODIFIER};
+use crate::entities::character::Character;
+use crate::entities::database::Database;
+use crate::entities::error::DbError;
+
+/// Directory the game loads recipes from.
+pub const DEFAULT_RECIPE_DIR: &str = "data/recipes";
+
+/// Highest quality a crafted item can have.
+pub const MAX_QUALITY: u32 = 5;
+
+/// An object and how many of it a recipe uses or makes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecipeItem {
+    /// Name of the object template
+    pub object: String,
+    /// How many (default 1)
+    #[serde(default = "one")]
+    pub quantity: u32,
+}
+
+fn one() -> u32 {
+    1
+}
+
+/// A way of turning ingredients into a new item.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Recipe {
+    /// Unique key used to look the recipe up
+    pub id: String,
+    /// Display name
+    pub name: String,
+    /// The roll made to craft it
+    pub pool: DicePool,
+    /// In-game minutes one attempt takes
+    #[serde(default)]
+    pub minutes: u32,
+    /// Instances consumed
+    pub inputs: Vec<RecipeItem>,
+    /// What is produced
+    pub output: RecipeItem,
+}
+
+/// Every known recipe, keyed by ID.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RecipeBook {
+    recipes: BTreeMap<String, Recipe>,
+}
+
+impl RecipeBook {
+    /// Loads every `.json` file in a directory, in file name order.
+    ///
+    /// # Returns
+    ///
+    /// Returns `CraftingError::Data`, naming the file, if a file can't be read
+    /// or parsed, if a recipe's pool is out of range, or if two recipes share
+    /// an ID.
+    pub fn load_dir(dir: impl AsRef<Path>) -> Result<Self, CraftingError> {
+        let dir = dir.as_ref();
+        let read_error =
+            |err: std::io::Error| CraftingError::Data(format!("{}: {}", dir.display(), err));
+
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(dir).map_err(read_error)? {
+            let path = entry.map_err(read_error)?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                files.push(path);
+            }
+        }
+        files.sort();
+
+        let mut book = RecipeBook::default();
+        for path in files {
+            let text = std::fs::read_to_string(&path)
+                .map_err(|err| CraftingError::Data(format!("{}: {}", path.display(), err)))?;
+            book.add_json(&text)
+                .map_err(|err| CraftingError::Data(format!("{}: {}", path.display(), err)))?;
+        }
+        Ok(book)
+    }
+
+    /// Adds the recipes in a JSON array.
+    pub fn add_json(&mut self, json: &str) -> Result<(), CraftingError> {
+        let recipes: Vec<Recipe> =
+            serde_json::from_str(json).map_err(|err| CraftingError::Data(err.to_string()))?;
+        for recipe in recipes {
+            self.add(recipe)?;
+        }
+        Ok(())
+    }
+
+    /// Adds one recipe, refusing a second recipe with the same ID or a pool
+    /// a roll request couldn't ask for.
+    pub fn add(&mut self, recipe: Recipe) -> Result<(), CraftingError> {
+        if self.recipes.contains_key(&recipe.id) {
+            return Err(CraftingError::Data(format!(
+                "duplicate recipe id {}",
+                recipe.id
+            )));
+        }
+        if !(2..=DIE_SIDES).contains(&recipe.pool.difficulty) {
+            return Err(CraftingError::Data(format!(
+                "recipe {}: pool difficulty must be between 2 and {}",
+                recipe.id, DIE_SIDES
+            )));
+        }
+        if !(-MAX_POOL_MODIFIER..=MAX_POOL_MODIFIER).contains(&recipe.pool.modifier) {
+            return Err(CraftingError::Data(format!(
+                "recipe {}: pool modifier must be between -{1} and {1}",
+                recipe.id, MAX_POOL_MODIFIER
+            )));
+        }
+        self.recipes.insert(recipe.id.clone(), recipe);
+        Ok(())
+    }
+
+    /// Looks a recipe up by ID.
+    pub fn get(&self, id: &str) -> Option<&Recipe> {
+        self.recipes.get(id)
+    }
+
+    /// All recipes, ordered by ID.
+    pub fn iter(&self) -> impl Iterator<Item = &Recipe> {
+        self.recipes.values()
+    }
+
+    /// Number of recipes.
+    pub fn len(&self) -> usize {
+        self.recipes.len()
+    }
+
+    /// Whether the book has no recipes.
+    pub fn is_empty(&self) -> bool {
+        self.recipes.is_empty()
+    }
+}
+
+/// What happened when a character tried a recipe.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CraftOutcome {
+    /// The crafting roll
+    pub roll: RollResult,
+    /// In-game minutes spent
+    pub minutes: u32,
+    /// IDs of the ingredient instances used up
+    pub consumed: Vec<i64>,
+    /// IDs of the new instances
+    pub produced: Vec<i64>,
+    /// Quality of the new instances, if any were made
+    pub quality: Option<u32>,
+}
+
+/// Reasons a crafting attempt could not be made.
+#[derive(Debug)]
+pub enum CraftingError {
+    /// A recipe file is missing or malformed
+    Data(String),
+    /// A recipe refers to an object template that doesn't exist
+    UnknownObject(String),
+    /// The character lacks ingredients (one description per missing input)
+    MissingIngredients(Vec<String>),
+    /// The database refused a change
+    Db(DbError),
+}
+
+impl fmt::Display for CraftingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CraftingError::Data(msg) => write!(f, "Invalid recipe data: {}", msg),
+            CraftingError::UnknownObject(name) => write!(f, "Unknown object: {}", name),
+            CraftingError::MissingIngredients(missing) => {
+                write!(f, "Missing ingredients: {}", missing.join(", "))
+            }
+            CraftingError::Db(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for CraftingError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CraftingError::Db(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<DbError> for CraftingError {
+    fn from(err: DbError) -> Self {
+        CraftingError::Db(err)
+    }
+}
+
+/// Has a character attempt a recipe using what they carry.
+///
+/// # Arguments
+///
+/// * `db` - The database holding the character's inventory
+/// * `game` - The game the character belongs to
+/// * `character` - The crafter; `character.name` selects their inventory
+/// * `recipe` - What to make
+/// * `rng` - Source of randomness for the roll
+///
+/// # Returns
+///
+/// Returns the outcome, or `CraftingError::MissingIngredients` (without
+/// rolling) if the character doesn't carry everything the recipe needs.
+pub fn craft<R: Rng + ?Sized>(
+    db: &Database,
+    game: &str,
+    character: &Character,
+    recipe: &Recipe,
+    rng: &mut R,
+) -> Result<CraftOutcome, CraftingError> {
+    db.transaction(|db| {
+        let ingredients = gather_ingredients(db, game, &character.name, recipe)?;
+        let output = db
+            .find_objects_by_name(&recipe.output.object)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| CraftingError::UnknownObject(recipe.output.object.clone()))?;
+
+        let roll = recipe.pool.roll(character, rng);
+        let mut outcome = CraftOutcome {
+            roll,
+            minutes: recipe.minutes,
+            consumed: Vec::new(),
+            produced: Vec::new(),
+            quality: None,
+        };
+        if !outcome.roll.is_success() && !outcome.roll.botch {
+            return Ok(outcome);
+        }
+
+        for id in ingredients {
+            db.delete_item_instance(id)?;
+            outcome.consumed.push(id);
+        }
+        if outcome.roll.botch {
+            return Ok(outcome);
+        }
+
+        let quality = outcome.roll.net_successes.clamp(1, MAX_QUALITY);
+        for _ in 0..recipe.output.quantity {
+            let id = db.create_item_instance(output.id, game, Some(&character.name))?;
+            db.set_instance_property(id, "quality", Some(json!(quality)))?;
+            outcome.produced.push(id);
+        }
+        outcome.quality = Some(quality);
+        Ok(outcome)
+    })
+}
+
+/// Picks carried instances satisfying every input, or lists what's missing.
+fn gather_ingredients(
+    db: &Database,
+    game: &str,
+    character_name: &str,
+    recipe: &Recipe,
+) -> Result<Vec<i64>, CraftingError> {
+    let carried = db.get_carried_instances(game, character_name)?;
+    let mut chosen = Vec::new();
+    let mut missing = Vec::new();
+
+    for input in &recipe.inputs {
+        let templates: HashSet<i64> = db
+            .find_objects_by_name(&input.object)?
+            .into_iter()
+            .map(|object| object.id)
+            .collect();
+        if templates.is_empty() {
+            return Err(CraftingError::UnknownObject(input.object.clone()));
+        }
+
+        let matches: Vec<i64> = carried
+            .iter()
+            .filter(|item| templates.contains(&item.object_id) && !chosen.contains(&item.id))
+            .map(|item| item.id)
+            .take(input.quantity as usize)
+            .collect();
+        if matches.len() < input.quantity as usize {
+            missing.push(format!(
+                "{} {} (have {})",
+                input.quantity,
+                input.object,
+                matches.len()
+            ));
+        }
+        chosen.extend(matches);
+    }
+
+    if missing.is_empty() {
+        Ok(chosen)
+    } else {
+        Err(CraftingError::MissingIngredients(missing))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn smoke_bomb() -> Recipe {
+        let mut book = RecipeBook::default();
+        book.add_json(
+            r#"[{
+                "id": "smoke_bomb",
+                "name": "Smoke Bomb",
+                "pool": {"stats": ["mental", "science"], "difficulty": 7},
+                "minutes": 30,
+                "inputs": [{"object": "Saltpeter", "quantity": 2}, {"object": "Sugar"}],
+                "output": {"object": "Smoke Bomb", "quantity": 2}
+            }]"#,
+        )
+        .unwrap();
+        book.get("smoke_bomb").unwrap().clone()
+    }
+
+    fn setup_workshop() -> (Database, Character) {
+        let db = Database::new(":memory:").unwrap();
+        db.insert_character("Chemist", "Game", None).unwrap();
+        let saltpeter = db.insert_object("Saltpeter", "material", None).unwrap();
+        let sugar = db.insert_object("Sugar", "material", None).unwrap();
+        db.insert_object("Smoke Bomb", "gadget", None).unwrap();
+        for object in [saltpeter, saltpeter, sugar] {
+            db.create_item_instance(object, "Game", Some("Chemist"))
+                .unwrap();
+        }
+
+        let mut chemist = Character::new("Chemist".to_string());
+        chemist.mental = 5;
+        chemist.science = 5;
+        (db, chemist)
+    }
+
+    /// Crafts with successive seeds until the roll matches `wanted`.
+    fn craft_until(
+        wanted: impl Fn(&RollResult) -> bool,
+    ) -> (Database, Result<CraftOutcome, CraftingError>) {
+        for seed in 0..1_000 {
+            let (db, chemist) = setup_workshop();
+            let roll = smoke_bomb()
+                .pool
+                .roll(&chemist, &mut StdRng::seed_from_u64(seed));
+            if wanted(&roll) {
+                let outcome = craft(
+                    &db,
+                    "Game",
+                    &chemist,
+                    &smoke_bomb(),
+                    &mut StdRng::seed_from_u64(seed),
+                );
+                return (db, outcome);
+            }
+        }
+        panic!("no seed produced the wanted roll");
+    }
+
+    #[test]
+    fn test_success_consumes_inputs_and_sets_quality() {
+        let (db, outcome) = craft_until(|roll| roll.net_successes >= 2);
+        let outcome = outcome.unwrap();
+
+        assert_eq!(outcome.minutes, 30);
+        assert_eq!(outcome.consumed.len(), 3);
+        assert_eq!(outcome.produced.len(), 2);
+        let quality = outcome.roll.net_successes.min(MAX_QUALITY);
+        assert_eq!(outcome.quality, Some(quality));
+
+        let inventory = db.get_character_instances("Game", "Chemist").unwrap();
+        assert_eq!(inventory.len(), 2);
+        assert!(inventory.iter().all(|item| item.name == "Smoke Bomb"));
+        assert_eq!(inventory[0].properties["quality"], quality);
+    }
+
+    #[test]
+    fn test_failure_keeps_ingredients() {
+        let (db, outcome) = craft_until(|roll| !roll.is_success() && !roll.botch);
+        let outcome = outcome.unwrap();
+
+        assert!(outcome.consumed.is_empty() && outcome.produced.is_empty());
+        assert_eq!(
+            db.get_character_instances("Game", "Chemist").unwrap().len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_missing_ingredients_change_nothing() {
+        let (db, chemist) = setup_workshop();
+        let sugar = db.get_character_instances("Game", "Chemist").unwrap()[2].id;
+        db.delete_item_instance(sugar).unwrap();
+
+        let err = craft(
+            &db,
+            "Game",
+            &chemist,
+            &smoke_bomb(),
+            &mut StdRng::seed_from_u64(1),
+        )
+        .unwrap_err();
+
+        match err {
+            CraftingError::MissingIngredients(missing) => {
+                assert_eq!(missing, vec!["1 Sugar (have 0)".to_string()])
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+        assert_eq!(
+            db.get_character_instances("Game", "Chemist").unwrap().len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_unknown_output_rolls_back() {
+        let (db, chemist) = setup_workshop();
+        let mut recipe = smoke_bomb();
+        recipe.output.object = "Philosopher's Stone".to_string();
+
+        let err = craft(
+            &db,
+            "Game",
+            &chemist,
+            &recipe,
+            &mut StdRng::seed_from_u64(1),
+        )
+        .unwrap_err();
+        assert!(matches!(err, CraftingError::UnknownObject(_)));
+        assert_eq!(
+            db.get_character_instances("Game", "Chemist").unwrap().len(),
+            3
+        );
+    }
+
+    #[test]
+    fn test_duplicate_recipe_ids_are_rejected() {
+        let mut book = RecipeBook::default();
+        book.add(smoke_bomb()).unwrap();
+        assert!(matches!(
+            book.add(smoke_bomb()),
+            Err(CraftingError::Data(_))
+        ));
+    }
+
+    #[test]
+    fn test_out_of_range_pools_fail_the_load_naming_the_file() {
+        let mut book = RecipeBook::default();
+        let mut recipe = smoke_bomb();
+        recipe.pool.difficulty = 11;
+        assert!(matches!(book.add(recipe), Err(CraftingError::Data(_))));
+        let mut recipe = smoke_bomb();
+        recipe.pool.modifier = i32::MAX;
+        assert!(matches!(book.add(recipe), Err(CraftingError::Data(_))));
+        assert!(book.is_empty());
+
+        let dir = std::env::temp_dir().join(format!("ttdigirpg-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(
+            dir.join("broken.json"),
+            r#"[{"id": "dud", "name": "Dud", "pool": {"stats": [], "difficulty": 0},
+                 "minutes": 1, "inputs": [], "output": {"object": "Dud"}}]"#,
+        )
+        .unwrap();
+        let err = RecipeBook::load_dir(&dir).unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let message = err.to_string();
+        assert!(message.contains("broken.json"), "{}", message);
+        assert!(message.contains("difficulty"), "{}", message);
+    }
+
+    #[test]
+    fn test_shipped_recipe_files_load() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(DEFAULT_RECIPE_DIR);
+        let book = RecipeBook::load_dir(dir).unwrap();
+
+        assert!(!book.is_empty());
+        assert!(book.get("smoke_bomb").is_some());
+        assert!(book.get("improvised_spear").is_some());
+    }
+}
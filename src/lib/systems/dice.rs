@@ -0,0 +1,228 @@
+//! Dice pool mechanics.
+//!
+//! Rolls follow the World of Darkness pattern: a pool of d10s is built from an
+//! attribute plus an ability, every die at or above the difficulty is a success,
+//! and every 1 cancels a success. A roll with ones but no successes at all is a
+//! botch.
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::entities::character::{Character, Stat};
+
+/// Number of sides on every die in a pool.
+pub const DIE_SIDES: u32 = 10;
+
+/// Difficulty used when a pool doesn't specify one.
+pub const DEFAULT_DIFFICULTY: u32 = 6;
+
+/// Most dice a roll request may throw once its stats and modifiers are added up.
+pub const MAX_POOL_DICE: u32 = 30;
+
+/// Largest bonus or penalty, in dice, a roll request may ask for.
+pub const MAX_POOL_MODIFIER: i32 = 20;
+
+/// Most stats a roll request may add together.
+pub const MAX_POOL_STATS: usize = 4;
+
+/// Seeds handed out by the server are below this, so clients reading the JSON
+/// as a double keep them exact.
+pub const MAX_SEED: u64 = 1 << 53;
+
+/// Describes which dice a character throws for a single action.
+///
+/// A pool is the sum of its stats plus a flat modifier (which may be negative,
+/// e.g. for wound or encumbrance penalties). The pool never drops below zero dice.
+///
+/// # Examples
+///
+/// ```
+/// use ttdigirpg::entities::character::{Character, Stat};
+/// use ttdigirpg::systems::dice::DicePool;
+///
+/// let mut character = Character::new("Shade".to_string());
+/// character.physical = 3;
+/// character.stealth = 2;
+///
+/// let pool = DicePool::new(&[Stat::Physical, Stat::Stealth]).with_modifier(-1);
+/// assert_eq!(pool.size(&character), 4);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DicePool {
+    /// Stats whose ratings are added together to form the pool
+    pub stats: Vec<Stat>,
+    /// Flat bonus or penalty in dice
+    #[serde(default)]
+    pub modifier: i32,
+    /// Minimum face that counts as a success
+    #[serde(default = "default_difficulty")]
+    pub difficulty: u32,
+}
+
+fn default_difficulty() -> u32 {
+    DEFAULT_DIFFICULTY
+}
+
+impl DicePool {
+    /// Creates a pool from the given stats at the default difficulty.
+    pub fn new(stats: &[Stat]) -> Self {
+        DicePool {
+            stats: stats.to_vec(),
+            modifier: 0,
+            difficulty: DEFAULT_DIFFICULTY,
+        }
+    }
+
+    /// Adds a bonus (or, if negative, a penalty) to the pool.
+    pub fn with_modifier(mut self, modifier: i32) -> Self {
+        self.modifier = self.modifier.saturating_add(modifier);
+        self
+    }
+
+    /// Sets the difficulty, clamped to the faces of a d10.
+    pub fn with_difficulty(mut self, difficulty: u32) -> Self {
+        self.difficulty = difficulty.clamp(2, DIE_SIDES);
+        self
+    }
+
+    /// Number of dice this pool gives the character, never fewer than zero.
+    pub fn size(&self, character: &Character) -> u32 {
+        let base: i64 = self
+            .stats
+            .iter()
+            .map(|stat| i64::from(character.stat(*stat)))
+            .sum();
+        u32::try_from((base + i64::from(self.modifier)).max(0)).unwrap_or(u32::MAX)
+    }
+
+    /// Rolls this pool for the given character.
+    pub fn roll<R: Rng + ?Sized>(&self, character: &Character, rng: &mut R) -> RollResult {
+        roll_dice(self.size(character), self.difficulty, rng)
+    }
+}
+
+/// The full breakdown of a single pool roll.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct RollResult {
+    /// Every die face, in the order rolled
+    pub faces: Vec<u32>,
+    /// Difficulty the roll was made against
+    pub difficulty: u32,
+    /// Dice at or above the difficulty
+    pub successes: u32,
+    /// Dice showing a 1
+    pub ones: u32,
+    /// Successes left after ones cancel them (never negative)
+    pub net_successes: u32,
+    /// True if ones came up and no die succeeded
+    pub botch: bool,
+}
+
+impl RollResult {
+    /// Scores a set of already-rolled faces against a difficulty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ttdigirpg::systems::dice::RollResult;
+    ///
+    /// let result = RollResult::from_faces(vec![1, 6, 9, 3], 6);
+    /// assert_eq!(result.successes, 2);
+    /// assert_eq!(result.net_successes, 1);
+    /// assert!(!result.botch);
+    /// ```
+    pub fn from_faces(faces: Vec<u32>, difficulty: u32) -> Self {
+        let successes = faces.iter().filter(|&&face| face >= difficulty).count() as u32;
+        let ones = faces.iter().filter(|&&face| face == 1).count() as u32;
+
+        RollResult {
+            faces,
+            difficulty,
+            successes,
+            ones,
+            net_successes: successes.saturating_sub(ones),
+            botch: successes == 0 && ones > 0,
+        }
+    }
+
+    /// Returns true if at least one success survived cancellation.
+    pub fn is_success(&self) -> bool {
+        self.net_successes > 0
+    }
+}
+
+/// Rolls `count` d10s against `difficulty`.
+pub fn roll_dice<R: Rng + ?Sized>(count: u32, difficulty: u32, rng: &mut R) -> RollResult {
+    let faces = (0..count).map(|_| rng.gen_range(1..=DIE_SIDES)).collect();
+    RollResult::from_faces(faces, difficulty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_from_faces_counts_successes_and_ones() {
+        let result = RollResult::from_faces(vec![10, 7, 6, 5, 1], 6);
+
+        assert_eq!(result.successes, 3);
+        assert_eq!(result.ones, 1);
+        assert_eq!(result.net_successes, 2);
+        assert!(!result.botch);
+        assert!(result.is_success());
+    }
+
+    #[test]
+    fn test_ones_cancel_but_never_go_negative() {
+        let result = RollResult::from_faces(vec![8, 1, 1, 1], 6);
+
+        assert_eq!(result.net_successes, 0);
+        assert!(!result.botch, "A roll with any success is a failure, not a botch");
+    }
+
+    #[test]
+    fn test_botch_requires_ones_and_no_successes() {
+        assert!(RollResult::from_faces(vec![1, 3, 4], 6).botch);
+        assert!(!RollResult::from_faces(vec![2, 3, 4], 6).botch);
+    }
+
+    #[test]
+    fn test_pool_size_includes_modifier_and_floors_at_zero() {
+        let mut character = Character::new("Test".to_string());
+        character.physical = 3;
+        character.athletics = 2;
+
+        let pool = DicePool::new(&[Stat::Physical, Stat::Athletics]);
+        assert_eq!(pool.size(&character), 5);
+        assert_eq!(pool.clone().with_modifier(-2).size(&character), 3);
+        assert_eq!(pool.clone().with_modifier(-10).size(&character), 0);
+        assert_eq!(pool.clone().with_modifier(40).size(&character), 45);
+        assert_eq!(pool.with_modifier(i32::MIN).with_modifier(-1).modifier, i32::MIN);
+    }
+
+    #[test]
+    fn test_roll_uses_pool_size_and_valid_faces() {
+        let mut character = Character::new("Test".to_string());
+        character.mental = 4;
+        character.science = 3;
+
+        let pool = DicePool::new(&[Stat::Mental, Stat::Science]).with_difficulty(7);
+        let mut rng = StdRng::seed_from_u64(42);
+        let result = pool.roll(&character, &mut rng);
+
+        assert_eq!(result.faces.len(), 7);
+        assert_eq!(result.difficulty, 7);
+        assert!(result.faces.iter().all(|face| (1..=DIE_SIDES).contains(face)));
+    }
+
+    #[test]
+    fn test_same_seed_gives_same_roll() {
+        let first = roll_dice(10, 6, &mut StdRng::seed_from_u64(7));
+        let second = roll_dice(10, 6, &mut StdRng::seed_from_u64(7));
+
+        assert_eq!(first, second);
+    }
+}
@@ -0,0 +1,307 @@
+//! Carrying capacity and encumbrance.
+//!
+//! Objects declare `"weight"` (kg per unit) and `"bulk"` (how awkward one unit
+//! is to carry) in their JSON properties. A character's capacity for both
+//! grows with `physical`. How far the carried load exceeds that capacity sets
+//! an encumbrance level, and each level takes dice away from physical pools.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::dice::DicePool;
+use crate::entities::character::{Character, Stat};
+use crate::entities::items::ItemInstance;
+use crate::entities::records::InventoryEntry;
+
+/// Kilograms anyone can carry regardless of build.
+pub const BASE_WEIGHT_CAPACITY: f64 = 10.0;
+/// Extra kilograms of capacity per dot of `physical`.
+pub const WEIGHT_CAPACITY_PER_DOT: f64 = 15.0;
+/// Bulk anyone can carry regardless of build.
+pub const BASE_BULK_CAPACITY: f64 = 4.0;
+/// Extra bulk capacity per dot of `physical`.
+pub const BULK_CAPACITY_PER_DOT: f64 = 2.0;
+
+/// Stats whose pools suffer encumbrance penalties.
+pub const ENCUMBERED_STATS: [Stat; 5] = [
+    Stat::Physical,
+    Stat::Athletics,
+    Stat::Brawl,
+    Stat::Combat,
+    Stat::Stealth,
+];
+
+/// Total weight and bulk of everything a character carries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct InventoryLoad {
+    /// Total weight in kilograms
+    pub weight: f64,
+    /// Total bulk
+    pub bulk: f64,
+}
+
+impl InventoryLoad {
+    /// Sums the load of stacked inventory rows and individual item instances.
+    ///
+    /// Stacks count `quantity` times; items without a `weight` or `bulk`
+    /// property count as weightless for that measure. Pass every carried
+    /// instance (see `Database::get_carried_instances`) so the contents of
+    /// containers count too.
+    pub fn from_inventory(entries: &[InventoryEntry], instances: &[ItemInstance]) -> Self {
+        let mut load = InventoryLoad::default();
+
+        for entry in entries {
+            let properties = entry
+                .properties
+                .as_deref()
+                .and_then(|json| serde_json::from_str::<Value>(json).ok())
+                .unwrap_or(Value::Null);
+            let quantity = f64::from(entry.quantity.max(0));
+            load.weight += number(&properties, "weight") * quantity;
+            load.bulk += number(&properties, "bulk") * quantity;
+        }
+
+        for instance in instances {
+            load.weight += instance.weight();
+            load.bulk += number(&instance.properties, "bulk");
+        }
+
+        load
+    }
+}
+
+/// Reads a non-negative numeric property, defaulting to zero.
+pub(crate) fn number(properties: &Value, key: &str) -> f64 {
+    properties
+        .get(key)
+        .and_then(Value::as_f64)
+        .unwrap_or(0.0)
+        .max(0.0)
+}
+
+/// How much a character's load slows them down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncumbranceLevel {
+    /// Load within capacity
+    Unencumbered,
+    /// Up to one and a half times capacity
+    Burdened,
+    /// Up to twice capacity
+    Heavy,
+    /// More than twice capacity
+    Overloaded,
+}
+
+impl EncumbranceLevel {
+    /// Classifies a load as a fraction of capacity (1.0 = exactly at capacity).
+    pub fn from_ratio(ratio: f64) -> Self {
+        if ratio <= 1.0 {
+            EncumbranceLevel::Unencumbered
+        } else if ratio <= 1.5 {
+            EncumbranceLevel::Burdened
+        } else if ratio <= 2.0 {
+            EncumbranceLevel::Heavy
+        } else {
+            EncumbranceLevel::Overloaded
+        }
+    }
+
+    /// Dice removed from affected pools at this level.
+    pub fn penalty(self) -> i32 {
+        match self {
+            EncumbranceLevel::Unencumbered => 0,
+            EncumbranceLevel::Burdened => -1,
+            EncumbranceLevel::Heavy => -2,
+            EncumbranceLevel::Overloaded => -3,
+        }
+    }
+}
+
+/// A character's capacity compared with what they carry.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Encumbrance {
+    /// What the character carries
+    pub load: InventoryLoad,
+    /// Weight the character can carry without penalty
+    pub weight_capacity: f64,
+    /// Bulk the character can carry without penalty
+    pub bulk_capacity: f64,
+    /// The worse of the weight and bulk levels
+    pub level: EncumbranceLevel,
+}
+
+impl Encumbrance {
+    /// Works out a character's encumbrance for the given load.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ttdigirpg::entities::character::Character;
+    /// use ttdigirpg::systems::encumbrance::{Encumbrance, EncumbranceLevel, InventoryLoad};
+    ///
+    /// let mut character = Character::new("Porter".to_string());
+    /// character.physical = 2; // carries 40 kg comfortably
+    ///
+    /// let load = InventoryLoad { weight: 55.0, bulk: 0.0 };
+    /// let encumbrance = Encumbrance::for_character(&character, load);
+    /// assert_eq!(encumbrance.level, EncumbranceLevel::Burdened);
+    /// ```
+    pub fn for_character(character: &Character, load: InventoryLoad) -> Self {
+        let weight_capacity = weight_capacity(character.physical);
+        let bulk_capacity = bulk_capacity(character.physical);
+        let level = EncumbranceLevel::from_ratio(load.weight / weight_capacity)
+            .max(EncumbranceLevel::from_ratio(load.bulk / bulk_capacity));
+
+        Encumbrance {
+            load,
+            weight_capacity,
+            bulk_capacity,
+            level,
+        }
+    }
+
+    /// Applies this encumbrance's penalty to a pool that uses a physical stat.
+    ///
+    /// Pools built only from social or mental stats are returned unchanged.
+    pub fn apply(&self, pool: DicePool) -> DicePool {
+        if pool
+            .stats
+            .iter()
+            .any(|stat| ENCUMBERED_STATS.contains(stat))
+        {
+            pool.with_modifier(self.level.penalty())
+        } else {
+            pool
+        }
+    }
+}
+
+/// Kilograms a character with this `physical` rating carries without penalty.
+pub fn weight_capacity(physical: u32) -> f64 {
+    BASE_WEIGHT_CAPACITY + WEIGHT_CAPACITY_PER_DOT * f64::from(physical)
+}
+
+/// Bulk a character with this `physical` rating carries without penalty.
+pub fn bulk_capacity(physical: u32) -> f64 {
+    BASE_BULK_CAPACITY + BULK_CAPACITY_PER_DOT * f64::from(physical)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry(quantity: i32, properties: &str) -> InventoryEntry {
+        InventoryEntry {
+            object_id: 1,
+            name: "Thing".to_string(),
+            obj_type: "item".to_string(),
+            quantity,
+            properties: Some(properties.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_capacity_scales_with_physical() {
+        assert_eq!(weight_capacity(1), 25.0);
+        assert_eq!(weight_capacity(5), 85.0);
+        assert_eq!(bulk_capacity(3), 10.0);
+    }
+
+    #[test]
+    fn test_load_multiplies_stacks_and_skips_missing_properties() {
+        let entries = vec![
+            entry(20, r#"{"weight": 0.05, "bulk": 0.1}"#),
+            entry(1, r#"{"weight": 3.5}"#),
+            entry(4, r#"{"damage": 10}"#),
+        ];
+
+        let load = InventoryLoad::from_inventory(&entries, &[]);
+        assert!((load.weight - 4.5).abs() < 1e-9);
+        assert!((load.bulk - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_load_includes_instances() {
+        let instance = ItemInstance {
+            id: 1,
+            object_id: 1,
+            game: "Game".to_string(),
+            character_name: Some("Alice".to_string()),
+            container_id: None,
+            location: None,
+            name: "Plate Armour".to_string(),
+            obj_type: "armor".to_string(),
+            durability: None,
+            charges: None,
+            notes: None,
+            properties: json!({"weight": 20.0, "bulk": 3}),
+            modified: vec![],
+        };
+
+        let load = InventoryLoad::from_inventory(&[], &[instance]);
+        assert_eq!(
+            load,
+            InventoryLoad {
+                weight: 20.0,
+                bulk: 3.0
+            }
+        );
+    }
+
+    #[test]
+    fn test_levels_follow_the_worse_measure() {
+        let character = Character::new("Test".to_string()); // 25 kg, 6 bulk
+
+        let light = Encumbrance::for_character(
+            &character,
+            InventoryLoad {
+                weight: 20.0,
+                bulk: 2.0,
+            },
+        );
+        assert_eq!(light.level, EncumbranceLevel::Unencumbered);
+
+        let bulky = Encumbrance::for_character(
+            &character,
+            InventoryLoad {
+                weight: 1.0,
+                bulk: 11.0,
+            },
+        );
+        assert_eq!(bulky.level, EncumbranceLevel::Heavy);
+
+        let crushed = Encumbrance::for_character(
+            &character,
+            InventoryLoad {
+                weight: 80.0,
+                bulk: 0.0,
+            },
+        );
+        assert_eq!(crushed.level, EncumbranceLevel::Overloaded);
+    }
+
+    #[test]
+    fn test_penalty_only_hits_physical_pools() {
+        let mut character = Character::new("Test".to_string());
+        character.physical = 3;
+        character.stealth = 3;
+        character.mental = 3;
+        character.investigation = 3;
+        let encumbrance = Encumbrance::for_character(
+            &character,
+            InventoryLoad {
+                weight: 100.0,
+                bulk: 0.0,
+            },
+        );
+        assert_eq!(encumbrance.level, EncumbranceLevel::Heavy);
+
+        let sneak = encumbrance.apply(DicePool::new(&[Stat::Physical, Stat::Stealth]));
+        let search = encumbrance.apply(DicePool::new(&[Stat::Mental, Stat::Investigation]));
+
+        assert_eq!(sneak.size(&character), 4);
+        assert_eq!(search.size(&character), 6);
+    }
+}
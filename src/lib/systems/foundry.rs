@@ -0,0 +1,660 @@
+//! FoundryVTT actor import and export.
+//!
+//! Converts a character sheet plus its inventory stacks to and from a Foundry
+//! actor document with embedded items. Where each stat lives inside the
+//! actor's `system` data depends on the Foundry game system in use, so the
+//! mapping comes from a `FoundryTemplate` (see `data/foundry/*.json`).
+//!
+//! Importing keeps the whole original document in the character's `data`
+//! under `"foundry"`. Exporting starts from that document and only overwrites
+//! the fields the template maps, so flags, images, token settings and any
+//! other fields this crate doesn't understand survive a round trip.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use crate::entities::character::{Character, FieldError, Stat};
+use crate::entities::database::Database;
+use crate::entities::error::DbError;
+use crate::entities::records::{merge_sheet_data, CharacterRecord, InventoryEntry};
+
+/// Directory the server and CLI load extra templates from.
+pub const DEFAULT_TEMPLATE_DIR: &str = "data/foundry";
+
+/// Template used when a request doesn't name one.
+pub const DEFAULT_TEMPLATE: &str = "worldofdarkness";
+
+/// Key in a character's `data` JSON holding its last imported actor document.
+pub const DOCUMENT_KEY: &str = "foundry";
+
+/// Flag scope under the actor's `flags` where exports record the character ID.
+pub const FLAG_SCOPE: &str = "ttdigirpg";
+
+const BUILTIN_TEMPLATE: &str = include_str!("../../../data/foundry/worldofdarkness.json");
+
+/// Where a Foundry game system keeps the data this crate maps.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FoundryTemplate {
+    /// Key used to select the template (usually the Foundry system ID)
+    pub id: String,
+    /// Actor `type` to export and accept on import
+    pub actor_type: String,
+    /// Dotted path of each stat's value inside the actor's `system` data;
+    /// unmapped stats are neither exported nor imported
+    pub stats: HashMap<Stat, String>,
+    /// Dotted path of an item's quantity inside the item's `system` data
+    #[serde(default = "default_quantity_path")]
+    pub item_quantity: String,
+}
+
+fn default_quantity_path() -> String {
+    "quantity".to_string()
+}
+
+/// An item read from an actor document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FoundryItem {
+    /// Object name
+    pub name: String,
+    /// Object type
+    pub obj_type: String,
+    /// Stack size
+    pub quantity: i32,
+    /// The item's `system` data without the quantity, used as object properties
+    pub properties: Value,
+}
+
+/// An actor document converted to this crate's types.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FoundryActor {
+    /// The sheet, with mapped stats taken from the document
+    pub sheet: Character,
+    /// The embedded items
+    pub items: Vec<FoundryItem>,
+    /// The document as given, kept for the next export
+    pub document: Value,
+}
+
+/// Reasons a Foundry import or export failed.
+#[derive(Debug)]
+pub enum FoundryError {
+    /// A template file is missing or malformed
+    Data(String),
+    /// No template has the requested ID
+    UnknownTemplate(String),
+    /// The actor document is not valid for the template (one entry per bad field)
+    Invalid(Vec<FieldError>),
+    /// The database refused a change
+    Db(DbError),
+}
+
+impl fmt::Display for FoundryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FoundryError::Data(msg) => write!(f, "Invalid Foundry template: {}", msg),
+            FoundryError::UnknownTemplate(id) => write!(f, "Unknown Foundry template: {}", id),
+            FoundryError::Invalid(fields) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|err| format!("{} {}", err.field, err.message))
+                    .collect();
+                write!(f, "Invalid Foundry actor: {}", fields.join("; "))
+            }
+            FoundryError::Db(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for FoundryError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FoundryError::Db(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<DbError> for FoundryError {
+    fn from(err: DbError) -> Self {
+        FoundryError::Db(err)
+    }
+}
+
+impl FoundryTemplate {
+    /// Builds an actor document for a character.
+    ///
+    /// # Arguments
+    ///
+    /// * `sheet` - The character's sheet
+    /// * `inventory` - The character's inventory stacks
+    /// * `previous` - The last imported document, whose other fields are kept
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ttdigirpg::entities::character::Character;
+    /// use ttdigirpg::systems::foundry::FoundryTemplates;
+    ///
+    /// let templates = FoundryTemplates::builtin();
+    /// let template = templates.get("worldofdarkness").unwrap();
+    ///
+    /// let mut sheet = Character::new("Shade".to_string());
+    /// sheet.stealth = 4;
+    /// let actor = template.export_actor(&sheet, &[], None);
+    ///
+    /// assert_eq!(actor["name"], "Shade");
+    /// assert_eq!(actor["system"]["abilities"]["skills"]["stealth"]["value"], 4);
+    /// ```
+    pub fn export_actor(
+        &self,
+        sheet: &Character,
+        inventory: &[InventoryEntry],
+        previous: Option<&Value>,
+    ) -> Value {
+        let mut actor = match previous {
+            Some(Value::Object(previous)) => Value::Object(previous.clone()),
+            _ => json!({ "type": self.actor_type }),
+        };
+        actor["name"] = json!(sheet.name);
+
+        let system = object_entry(&mut actor, "system");
+        for (stat, path) in &self.stats {
+            set_path(system, path, json!(sheet.stat(*stat)));
+        }
+
+        // Reuse each stack's previous item document so its _id, image and
+        // flags are kept; new stacks get a fresh document
+        let mut previous_items: Vec<Value> = actor
+            .get("items")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+        let items = inventory
+            .iter()
+            .map(|entry| {
+                let reused = previous_items.iter().position(|item| {
+                    item["name"] == entry.name.as_str() && item["type"] == entry.obj_type.as_str()
+                });
+                let mut item = match reused {
+                    Some(index) => previous_items.remove(index),
+                    None => json!({
+                        "name": entry.name,
+                        "type": entry.obj_type,
+                        "system": entry
+                            .properties
+                            .as_deref()
+                            .and_then(|json| serde_json::from_str::<Value>(json).ok())
+                            .filter(Value::is_object)
+                            .unwrap_or_else(|| json!({})),
+                    }),
+                };
+                set_path(
+                    object_entry(&mut item, "system"),
+                    &self.item_quantity,
+                    json!(entry.quantity),
+                );
+                item
+            })
+            .collect();
+        actor["items"] = Value::Array(items);
+        actor
+    }
+
+    /// Reads an actor document, starting from `base` for unmapped stats.
+    ///
+    /// Returns `FoundryError::Invalid` listing every field that is missing or
+    /// has the wrong type, and any stat the sheet validation rejects.
+    pub fn import_actor(
+        &self,
+        document: &Value,
+        base: &Character,
+    ) -> Result<FoundryActor, FoundryError> {
+        let mut errors = Vec::new();
+        let Some(actor) = document.as_object() else {
+            return Err(FoundryError::Invalid(vec![FieldError::new(
+                "body",
+                "must be a JSON object",
+            )]));
+        };
+
+        let mut sheet = base.clone();
+        match actor.get("name").and_then(Value::as_str) {
+            Some(name) => sheet.name = name.to_string(),
+            None => errors.push(FieldError::new("name", "is required")),
+        }
+        if let Some(actor_type) = actor.get("type") {
+            if actor_type != self.actor_type.as_str() {
+                errors.push(FieldError::new(
+                    "type",
+                    format!("must be {:?} for the {} template", self.actor_type, self.id),
+                ));
+            }
+        }
+
+        let system = actor.get("system").cloned().unwrap_or(Value::Null);
+        let mut stats: Vec<_> = self.stats.iter().collect();
+        stats.sort_by_key(|(stat, _)| Stat::ALL.iter().position(|s| s == *stat));
+        for (stat, path) in stats {
+            let Some(value) = get_path(&system, path) else {
+                continue;
+            };
+            match value.as_u64().and_then(|v| u32::try_from(v).ok()) {
+                Some(rating) => *sheet.stat_mut(*stat) = rating,
+                None => errors.push(FieldError::new(
+                    format!("system.{}", path),
+                    "must be a non-negative integer",
+                )),
+            }
+        }
+        if let Err(sheet_errors) = sheet.validate() {
+            for err in sheet_errors {
+                // A missing name is already reported above
+                if !errors.iter().any(|known| known.field == err.field) {
+                    errors.push(err);
+                }
+            }
+        }
+
+        let mut items = Vec::new();
+        match actor.get("items") {
+            None | Some(Value::Null) => {}
+            Some(Value::Array(documents)) => {
+                for (index, item) in documents.iter().enumerate() {
+                    match self.import_item(item) {
+                        Ok(item) => items.push(item),
+                        Err(message) => {
+                            errors.push(FieldError::new(format!("items[{}]", index), message))
+                        }
+                    }
+                }
+            }
+            Some(_) => errors.push(FieldError::new("items", "must be an array")),
+        }
+
+        if !errors.is_empty() {
+            return Err(FoundryError::Invalid(errors));
+        }
+        Ok(FoundryActor {
+            sheet,
+            items,
+            document: document.clone(),
+        })
+    }
+
+    fn import_item(&self, item: &Value) -> Result<FoundryItem, String> {
+        let name = item
+            .get("name")
+            .and_then(Value::as_str)
+            .filter(|name| !name.trim().is_empty())
+            .ok_or("name is required")?;
+        let obj_type = item.get("type").and_then(Value::as_str).unwrap_or("item");
+
+        let mut properties = match item.get("system") {
+            Some(Value::Object(system)) => Value::Object(system.clone()),
+            None | Some(Value::Null) => json!({}),
+            Some(_) => return Err("system must be an object".to_string()),
+        };
+        let quantity = match remove_path(&mut properties, &self.item_quantity) {
+            None => 1,
+            Some(value) => value
+                .as_i64()
+                .and_then(|q| i32::try_from(q).ok())
+                .filter(|&q| q >= 1)
+                .ok_or_else(|| {
+                    format!("system.{} must be a positive integer", self.item_quantity)
+                })?,
+        };
+
+        Ok(FoundryItem {
+            name: name.to_string(),
+            obj_type: obj_type.to_string(),
+            quantity,
+            properties,
+        })
+    }
+}
+
+/// The templates available for import and export, by ID.
+#[derive(Debug, Clone, Default)]
+pub struct FoundryTemplates {
+    templates: HashMap<String, FoundryTemplate>,
+}
+
+impl FoundryTemplates {
+    /// Only the built-in World of Darkness template.
+    pub fn builtin() -> Self {
+        let mut templates = FoundryTemplates::default();
+        templates
+            .add_json(BUILTIN_TEMPLATE)
+            .expect("built-in Foundry template is valid");
+        templates
+    }
+
+    /// The built-in template plus every `*.json` template in a directory.
+    ///
+    /// A file whose `id` matches the built-in template replaces it.
+    pub fn load_dir(dir: impl AsRef<Path>) -> Result<Self, FoundryError> {
+        let dir = dir.as_ref();
+        let read_error =
+            |err: std::io::Error| FoundryError::Data(format!("{}: {}", dir.display(), err));
+
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(dir).map_err(read_error)? {
+            let path = entry.map_err(read_error)?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                files.push(path);
+            }
+        }
+        files.sort();
+
+        let mut templates = FoundryTemplates::builtin();
+        for path in files {
+            let text = std::fs::read_to_string(&path)
+                .map_err(|err| FoundryError::Data(format!("{}: {}", path.display(), err)))?;
+            templates
+                .add_json(&text)
+                .map_err(|err| FoundryError::Data(format!("{}: {}", path.display(), err)))?;
+        }
+        Ok(templates)
+    }
+
+    /// Templates from `DEFAULT_TEMPLATE_DIR` if it exists, otherwise the built-in one.
+    pub fn load_default() -> Result<Self, FoundryError> {
+        if Path::new(DEFAULT_TEMPLATE_DIR).is_dir() {
+            FoundryTemplates::load_dir(DEFAULT_TEMPLATE_DIR)
+        } else {
+            Ok(FoundryTemplates::builtin())
+        }
+    }
+
+    /// Adds (or replaces) the template in a JSON document.
+    pub fn add_json(&mut self, json: &str) -> Result<(), FoundryError> {
+        let template: FoundryTemplate =
+            serde_json::from_str(json).map_err(|err| FoundryError::Data(err.to_string()))?;
+        self.templates.insert(template.id.clone(), template);
+        Ok(())
+    }
+
+    /// Looks a template up by ID.
+    pub fn get(&self, id: &str) -> Result<&FoundryTemplate, FoundryError> {
+        self.templates
+            .get(id)
+            .ok_or_else(|| FoundryError::UnknownTemplate(id.to_string()))
+    }
+}
+
+/// Exports a stored character and its inventory stacks as an actor document.
+///
+/// The document records the character's UUID under `flags.ttdigirpg.id`.
+pub fn export_character(
+    db: &Database,
+    template: &FoundryTemplate,
+    record: &CharacterRecord,
+) -> Result<Value, FoundryError> {
+    let inventory = db.get_character_objects(&record.game, &record.name)?;
+    let previous = stored_document(record);
+
+    let mut actor = template.export_actor(&record.sheet(), &inventory, previous.as_ref());
+    let flags = object_entry(&mut actor, "flags");
+    set_path(flags, &format!("{}.id", FLAG_SCOPE), json!(record.uuid));
+    Ok(actor)
+}
+
+/// Imports an actor document as a character of a game.
+///
+/// With `existing`, that character's sheet, name and inventory stacks are
+/// replaced by the document's; otherwise a new character is created. Items
+/// are matched to object definitions by name and type, creating definitions
+/// that don't exist yet. Everything happens in one transaction.
+///
+/// # Returns
+///
+/// Returns the stored character record.
+pub fn import_character(
+    db: &Database,
+    template: &FoundryTemplate,
+    game: &str,
+    document: &Value,
+    existing: Option<&CharacterRecord>,
+) -> Result<CharacterRecord, FoundryError> {
+    let base = existing.map(CharacterRecord::sheet).unwrap_or_default();
+    let actor = template.import_actor(document, &base)?;
+
+    db.transaction(|db| {
+        let mut data = merge_sheet_data(
+            existing.and_then(|record| record.data.as_deref()),
+            &actor.sheet,
+        );
+        if let Ok(Value::Object(mut map)) = serde_json::from_str::<Value>(&data) {
+            map.insert(DOCUMENT_KEY.to_string(), actor.document.clone());
+            data = Value::Object(map).to_string();
+        }
+
+        let uuid = match existing {
+            Some(record) => {
+                if record.name != actor.sheet.name {
+                    db.rename_character(&record.uuid, &actor.sheet.name)?;
+                }
+                db.update_character_by_uuid(&record.uuid, &data)?;
+                for entry in db.get_character_objects(game, &actor.sheet.name)? {
+                    db.remove_object_from_character(game, &actor.sheet.name, entry.object_id)?;
+                }
+                record.uuid.clone()
+            }
+            None => db.insert_character(&actor.sheet.name, game, Some(&data))?,
+        };
+
+        for item in &actor.items {
+            let object_id = match db
+                .find_objects_by_name(&item.name)?
+                .into_iter()
+                .find(|object| object.obj_type == item.obj_type)
+            {
+                Some(object) => object.id,
+                None => {
+                    let properties = match &item.properties {
+                        Value::Object(map) if map.is_empty() => None,
+                        properties => Some(properties.to_string()),
+                    };
+                    db.insert_object(&item.name, &item.obj_type, properties.as_deref())?
+                }
+            };
+            db.add_object_to_character(game, &actor.sheet.name, object_id, item.quantity)?;
+        }
+
+        db.get_character_by_uuid(&uuid)?
+            .ok_or_else(|| DbError::not_found(format!("character {}", uuid)).into())
+    })
+}
+
+/// The actor document saved by the last import, if any.
+fn stored_document(record: &CharacterRecord) -> Option<Value> {
+    let data: Value = serde_json::from_str(record.data.as_deref()?).ok()?;
+    data.get(DOCUMENT_KEY)
+        .filter(|doc| doc.is_object())
+        .cloned()
+}
+
+/// The object at `key`, replacing whatever non-object value was there.
+fn object_entry<'a>(value: &'a mut Value, key: &str) -> &'a mut Value {
+    if !value.is_object() {
+        *value = Value::Object(Map::new());
+    }
+    let entry = value
+        .as_object_mut()
+        .expect("just made an object")
+        .entry(key)
+        .or_insert_with(|| Value::Object(Map::new()));
+    if !entry.is_object() {
+        *entry = Value::Object(Map::new());
+    }
+    entry
+}
+
+fn get_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(value, |value, key| value.get(key))
+}
+
+fn set_path(value: &mut Value, path: &str, new_value: Value) {
+    let (parents, last) = match path.rsplit_once('.') {
+        Some((parents, last)) => (Some(parents), last),
+        None => (None, path),
+    };
+    let mut target = value;
+    for key in parents.into_iter().flat_map(|parents| parents.split('.')) {
+        target = object_entry(target, key);
+    }
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    target[last] = new_value;
+}
+
+fn remove_path(value: &mut Value, path: &str) -> Option<Value> {
+    let (parents, last) = match path.rsplit_once('.') {
+        Some((parents, last)) => (Some(parents), last),
+        None => (None, path),
+    };
+    let mut target = value;
+    for key in parents.into_iter().flat_map(|parents| parents.split('.')) {
+        target = target.get_mut(key)?;
+    }
+    target.as_object_mut()?.remove(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn actor_document() -> Value {
+        json!({
+            "_id": "a1B2c3D4e5F6g7H8",
+            "name": "Mara",
+            "type": "character",
+            "img": "icons/mara.webp",
+            "system": {
+                "attributes": { "physical": { "value": 3, "label": "Physical" } },
+                "abilities": { "skills": { "stealth": { "value": 4 } } },
+                "biography": "Grew up on the docks."
+            },
+            "items": [
+                {
+                    "_id": "i1B2c3D4e5F6g7H8",
+                    "name": "Lockpicks",
+                    "type": "gear",
+                    "img": "icons/picks.webp",
+                    "system": { "quantity": 2, "weight": 0.1 }
+                },
+                { "name": "Flare", "type": "gear" }
+            ],
+            "flags": { "core": { "sheetClass": "wod.Sheet" } }
+        })
+    }
+
+    #[test]
+    fn test_import_reads_mapped_stats_and_items() {
+        let templates = FoundryTemplates::builtin();
+        let template = templates.get(DEFAULT_TEMPLATE).unwrap();
+
+        let actor = template
+            .import_actor(&actor_document(), &Character::default())
+            .unwrap();
+        assert_eq!(actor.sheet.name, "Mara");
+        assert_eq!(actor.sheet.physical, 3);
+        assert_eq!(actor.sheet.stealth, 4);
+        assert_eq!(actor.sheet.social, 1, "missing stats keep the base value");
+
+        assert_eq!(actor.items.len(), 2);
+        assert_eq!(actor.items[0].quantity, 2);
+        assert_eq!(actor.items[0].properties, json!({"weight": 0.1}));
+        assert_eq!(actor.items[1].quantity, 1);
+    }
+
+    #[test]
+    fn test_import_reports_every_bad_field() {
+        let templates = FoundryTemplates::builtin();
+        let template = templates.get(DEFAULT_TEMPLATE).unwrap();
+        let document = json!({
+            "type": "npc",
+            "system": { "attributes": { "mental": { "value": "high" } } },
+            "items": [{ "name": "Rope", "system": { "quantity": 0 } }]
+        });
+
+        let Err(FoundryError::Invalid(errors)) =
+            template.import_actor(&document, &Character::default())
+        else {
+            panic!("expected validation errors");
+        };
+        let fields: Vec<&str> = errors.iter().map(|err| err.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec!["name", "type", "system.attributes.mental.value", "items[0]"]
+        );
+    }
+
+    #[test]
+    fn test_round_trip_preserves_unknown_fields() {
+        let db = Database::new(":memory:").unwrap();
+        let templates = FoundryTemplates::builtin();
+        let template = templates.get(DEFAULT_TEMPLATE).unwrap();
+
+        let record = import_character(&db, template, "Game", &actor_document(), None).unwrap();
+        let inventory = db.get_character_objects("Game", "Mara").unwrap();
+        assert_eq!(inventory.len(), 2);
+
+        let exported = export_character(&db, template, &record).unwrap();
+        assert_eq!(exported["_id"], "a1B2c3D4e5F6g7H8");
+        assert_eq!(exported["img"], "icons/mara.webp");
+        assert_eq!(exported["system"]["biography"], "Grew up on the docks.");
+        assert_eq!(
+            exported["system"]["attributes"]["physical"],
+            json!({"value": 3, "label": "Physical"})
+        );
+        assert_eq!(exported["flags"]["core"]["sheetClass"], "wod.Sheet");
+        assert_eq!(exported["flags"][FLAG_SCOPE]["id"], record.uuid);
+
+        let picks = &exported["items"][0];
+        assert_eq!(picks["_id"], "i1B2c3D4e5F6g7H8");
+        assert_eq!(picks["img"], "icons/picks.webp");
+        assert_eq!(picks["system"]["quantity"], 2);
+
+        // Re-importing the export changes nothing but what was edited
+        let mut edited = exported.clone();
+        edited["system"]["abilities"]["skills"]["stealth"]["value"] = json!(5);
+        edited["items"][0]["system"]["quantity"] = json!(1);
+        let updated = import_character(&db, template, "Game", &edited, Some(&record)).unwrap();
+        assert_eq!(updated.uuid, record.uuid);
+        assert_eq!(updated.sheet().stealth, 5);
+
+        let again = export_character(&db, template, &updated).unwrap();
+        assert_eq!(again["items"][0]["system"]["quantity"], 1);
+        assert_eq!(again["items"].as_array().unwrap().len(), 2);
+        assert_eq!(again["img"], "icons/mara.webp");
+    }
+
+    #[test]
+    fn test_export_without_previous_document() {
+        let db = Database::new(":memory:").unwrap();
+        db.insert_character("Bob", "Game", None).unwrap();
+        let rope = db
+            .insert_object("Rope", "gear", Some(r#"{"weight": 3}"#))
+            .unwrap();
+        db.add_object_to_character("Game", "Bob", rope, 2).unwrap();
+
+        let templates = FoundryTemplates::builtin();
+        let record = db.get_character("Bob", "Game").unwrap().unwrap();
+        let actor =
+            export_character(&db, templates.get(DEFAULT_TEMPLATE).unwrap(), &record).unwrap();
+
+        assert_eq!(actor["type"], "character");
+        assert_eq!(actor["system"]["attributes"]["physical"]["value"], 1);
+        assert_eq!(
+            actor["items"],
+            json!([{"name": "Rope", "type": "gear", "system": {"weight": 3, "quantity": 2}}])
+        );
+    }
+}
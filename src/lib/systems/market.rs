@@ -0,0 +1,485 @@
+//! Market and price simulation.
+//!
+//! Each good traded at a location has a stock (supply), a level of interest
+//! (demand) and a price. Every world tick the location produces and consumes
+//! some of the good, demand drifts back towards its usual level, and the price
+//! moves part of the way towards `base_price * sqrt(demand / supply)`. Player
+//! purchases drain stock and spike demand; events such as a blockade or a
+//! festival change production and consumption at a location while they last.
+//!
+//! The market keeps its own state. `Market::update_values` writes each good's
+//! average price back to its `EconomicEntity` so the rest of the game sees
+//! prices change.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::entities::database::Database;
+use crate::entities::economy::EntityId;
+use crate::entities::error::DbResult;
+
+/// Ticks of consumption a location keeps in stock at equilibrium.
+pub const RESERVE_TICKS: f64 = 10.0;
+/// Fraction of the gap to normal demand closed each tick.
+pub const DEMAND_RECOVERY: f64 = 0.25;
+/// Fraction of the gap to the target price closed each tick.
+pub const PRICE_SMOOTHING: f64 = 0.5;
+/// Prices never fall below this fraction of the base price.
+pub const MIN_PRICE_FACTOR: f64 = 0.25;
+/// Prices never rise above this multiple of the base price.
+pub const MAX_PRICE_FACTOR: f64 = 4.0;
+
+/// Production multiplier at a blockaded location.
+pub const BLOCKADE_PRODUCTION: f64 = 0.2;
+/// Consumption and demand multiplier during a festival.
+pub const FESTIVAL_DEMAND: f64 = 1.5;
+
+/// One good as traded at one location.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MarketGood {
+    /// The economic entity being traded
+    pub entity: EntityId,
+    /// Where it is traded
+    pub location: String,
+    /// Price when supply and demand are balanced
+    pub base_price: i32,
+    /// Units the location produces per tick
+    pub production: f64,
+    /// Units the location consumes per tick
+    pub consumption: f64,
+    /// Units in stock
+    pub supply: f64,
+    /// Current interest in the good, in units
+    pub demand: f64,
+    /// Current price per unit
+    pub price: i32,
+}
+
+impl MarketGood {
+    /// Creates a good at equilibrium: stocked for `RESERVE_TICKS` of
+    /// consumption and priced at `base_price`.
+    ///
+    /// # Returns
+    ///
+    /// Returns `MarketError::InvalidGood` if the base price is negative or
+    /// production or consumption is negative or not a finite number.
+    pub fn new(
+        entity: EntityId,
+        location: impl Into<String>,
+        base_price: i32,
+        production: f64,
+        consumption: f64,
+    ) -> Result<Self, MarketError> {
+        if base_price < 0 {
+            return Err(MarketError::InvalidGood(format!(
+                "base price {} is negative",
+                base_price
+            )));
+        }
+        for (name, rate) in [("production", production), ("consumption", consumption)] {
+            if !rate.is_finite() || rate < 0.0 {
+                return Err(MarketError::InvalidGood(format!(
+                    "{} must be a non-negative number, not {}",
+                    name, rate
+                )));
+            }
+        }
+
+        Ok(MarketGood {
+            entity,
+            location: location.into(),
+            base_price,
+            production,
+            consumption,
+            supply: consumption * RESERVE_TICKS,
+            demand: consumption * RESERVE_TICKS,
+            price: base_price,
+        })
+    }
+
+    /// The price supply and demand are currently pulling towards.
+    pub fn target_price(&self) -> i32 {
+        let base = f64::from(self.base_price);
+        let ratio = self.demand.max(0.0) / self.supply.max(1.0);
+        // min/max rather than clamp: a deserialized good may still have a
+        // negative base price, which would swap the bounds
+        let (low, high) = (base * MIN_PRICE_FACTOR, base * MAX_PRICE_FACTOR);
+        let price = (base * ratio.sqrt()).max(low.min(high)).min(low.max(high));
+        (price.round() as i32).max(1)
+    }
+
+    /// Advances this good by one tick under the given event multipliers.
+    fn tick(&mut self, production_factor: f64, demand_factor: f64) {
+        self.supply += self.production * production_factor;
+        self.supply = (self.supply - self.consumption * demand_factor).max(0.0);
+
+        let normal_demand = self.consumption * RESERVE_TICKS * demand_factor;
+        self.demand += (normal_demand - self.demand) * DEMAND_RECOVERY;
+
+        let gap = f64::from(self.target_price() - self.price);
+        self.price = (f64::from(self.price) + gap * PRICE_SMOOTHING).round() as i32;
+        self.price = self.price.max(1);
+    }
+}
+
+/// Kinds of event that disturb a location's market.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarketEventKind {
+    /// Goods can't get in: production drops to `BLOCKADE_PRODUCTION`
+    Blockade,
+    /// Crowds arrive: consumption and demand rise by `FESTIVAL_DEMAND`
+    Festival,
+}
+
+impl MarketEventKind {
+    /// `(production, demand)` multipliers while the event lasts.
+    pub fn factors(self) -> (f64, f64) {
+        match self {
+            MarketEventKind::Blockade => (BLOCKADE_PRODUCTION, 1.0),
+            MarketEventKind::Festival => (1.0, FESTIVAL_DEMAND),
+        }
+    }
+}
+
+/// An event running at a location.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MarketEvent {
+    /// What is happening
+    pub kind: MarketEventKind,
+    /// Where it is happening
+    pub location: String,
+    /// Ticks left before it ends
+    pub remaining_ticks: u32,
+}
+
+/// The result of a completed purchase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Purchase {
+    /// Price paid per unit
+    pub unit_price: i32,
+    /// Total price paid
+    pub total: i64,
+}
+
+/// Reasons a purchase can fail.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MarketError {
+    /// The good isn't traded at that location
+    UnknownGood(EntityId, String),
+    /// A good was described with impossible numbers
+    InvalidGood(String),
+    /// Not enough stock
+    OutOfStock {
+        /// Units requested
+        requested: u32,
+        /// Whole units in stock
+        available: u32,
+    },
+}
+
+impl fmt::Display for MarketError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarketError::UnknownGood(entity, location) => {
+                write!(f, "{} is not traded at {}", entity, location)
+            }
+            MarketError::InvalidGood(reason) => write!(f, "Invalid market good: {}", reason),
+            MarketError::OutOfStock {
+                requested,
+                available,
+            } => write!(
+                f,
+                "Only {} in stock but {} were requested",
+                available, requested
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MarketError {}
+
+/// Every traded good and running event in the world.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Market {
+    /// Goods keyed by location, then entity
+    goods: BTreeMap<String, BTreeMap<EntityId, MarketGood>>,
+    /// Events still running
+    pub events: Vec<MarketEvent>,
+    /// Ticks simulated so far
+    pub ticks: u64,
+}
+
+impl Market {
+    /// Creates an empty market.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a good, replacing any existing one for the same entity and location.
+    pub fn add_good(&mut self, good: MarketGood) {
+        self.goods
+            .entry(good.location.clone())
+            .or_default()
+            .insert(good.entity, good);
+    }
+
+    /// Looks up a good at a location.
+    pub fn good(&self, entity: EntityId, location: &str) -> Option<&MarketGood> {
+        self.goods.get(location)?.get(&entity)
+    }
+
+    /// All goods, ordered by location.
+    pub fn goods(&self) -> impl Iterator<Item = &MarketGood> {
+        self.goods.values().flat_map(BTreeMap::values)
+    }
+
+    /// Starts an event at a location for `ticks` ticks.
+    pub fn start_event(&mut self, kind: MarketEventKind, location: impl Into<String>, ticks: u32) {
+        self.events.push(MarketEvent {
+            kind,
+            location: location.into(),
+            remaining_ticks: ticks,
+        });
+    }
+
+    /// Advances every good by one tick, then counts down running events.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use ttdigirpg::entities::economy::EntityId;
+    /// use ttdigirpg::systems::market::{Market, MarketEventKind, MarketGood};
+    ///
+    /// let grain = EntityId::new();
+    /// let mut market = Market::new();
+    /// market.add_good(MarketGood::new(grain, "Harbour", 10, 5.0, 5.0).unwrap());
+    ///
+    /// market.start_event(MarketEventKind::Blockade, "Harbour", 5);
+    /// for _ in 0..5 {
+    ///     market.tick();
+    /// }
+    /// assert!(market.good(grain, "Harbour").unwrap().price > 10);
+    /// ```
+    pub fn tick(&mut self) {
+        for (location, goods) in self.goods.iter_mut() {
+            let (production, demand) = self
+                .events
+                .iter()
+                .filter(|event| &event.location == location)
+                .map(|event| event.kind.factors())
+                .fold((1.0, 1.0), |(p, d), (ep, ed)| (p * ep, d * ed));
+
+            for good in goods.values_mut() {
+                good.tick(production, demand);
+            }
+        }
+
+        for event in &mut self.events {
+            event.remaining_ticks = event.remaining_ticks.saturating_sub(1);
+        }
+        self.events.retain(|event| event.remaining_ticks > 0);
+        self.ticks += 1;
+    }
+
+    /// Buys `quantity` units at the current price.
+    ///
+    /// The stock drops immediately and demand rises by the amount bought, so
+    /// heavy buying pushes the price up over the following ticks.
+    pub fn purchase(
+        &mut self,
+        entity: EntityId,
+        location: &str,
+        quantity: u32,
+    ) -> Result<Purchase, MarketError> {
+        let good = self
+            .goods
+            .get_mut(location)
+            .and_then(|goods| goods.get_mut(&entity))
+            .ok_or_else(|| MarketError::UnknownGood(entity, location.to_string()))?;
+
+        let available = good.supply.floor() as u32;
+        if quantity > available {
+            return Err(MarketError::OutOfStock {
+                requested: quantity,
+                available,
+            });
+        }
+
+        good.supply -= f64::from(quantity);
+        good.demand += f64::from(quantity);
+        Ok(Purchase {
+            unit_price: good.price,
+            total: i64::from(good.price) * i64::from(quantity),
+        })
+    }
+
+    /// Average current price of an entity across the locations that trade it.
+    pub fn reference_price(&self, entity: EntityId) -> Option<i32> {
+        let prices: Vec<f64> = self
+            .goods()
+            .filter(|good| good.entity == entity)
+            .map(|good| f64::from(good.price))
+            .collect();
+        if prices.is_empty() {
+            return None;
+        }
+        Some((prices.iter().sum::<f64>() / prices.len() as f64).round() as i32)
+    }
+
+    /// Writes each traded entity's reference price to its stored `value`.
+    ///
+    /// # Returns
+    ///
+    /// Returns how many entities were updated, or `DbError::NotFound` if a
+    /// traded entity isn't stored (nothing is written in that case).
+    pub fn update_values(&self, db: &Database) -> DbResult<usize> {
+        let mut prices = BTreeMap::new();
+        for good in self.goods() {
+            if let Some(price) = self.reference_price(good.entity) {
+                prices.insert(good.entity, price);
+            }
+        }
+
+        db.transaction(|db| {
+            for (&entity, &price) in &prices {
+                db.set_economic_entity_value(entity, price)?;
+            }
+            Ok(prices.len())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::economy::EconomicEntity;
+
+    fn harbour_market() -> (Market, EntityId) {
+        let grain = EntityId::new();
+        let mut market = Market::new();
+        market.add_good(MarketGood::new(grain, "Harbour", 10, 5.0, 5.0).unwrap());
+        (market, grain)
+    }
+
+    fn run(market: &mut Market, ticks: u32) {
+        for _ in 0..ticks {
+            market.tick();
+        }
+    }
+
+    #[test]
+    fn test_equilibrium_is_stable() {
+        let (mut market, grain) = harbour_market();
+        run(&mut market, 20);
+
+        let good = market.good(grain, "Harbour").unwrap();
+        assert_eq!(good.price, 10);
+        assert_eq!(good.supply, 50.0);
+        assert_eq!(market.ticks, 20);
+    }
+
+    #[test]
+    fn test_impossible_goods_are_rejected_and_never_panic() {
+        let grain = EntityId::new();
+        for (base_price, production, consumption) in [
+            (-10, 5.0, 5.0),
+            (10, f64::NAN, 5.0),
+            (10, 5.0, -1.0),
+            (10, 5.0, f64::INFINITY),
+        ] {
+            assert!(matches!(
+                MarketGood::new(grain, "Harbour", base_price, production, consumption),
+                Err(MarketError::InvalidGood(_))
+            ));
+        }
+
+        // A good that skipped validation (e.g. deserialized) still ticks
+        let mut good = MarketGood::new(grain, "Harbour", 10, 5.0, 5.0).unwrap();
+        good.base_price = -10;
+        good.demand = f64::NAN;
+        let mut market = Market::new();
+        market.add_good(good);
+        run(&mut market, 3);
+        assert!(market.good(grain, "Harbour").unwrap().price >= 1);
+    }
+
+    #[test]
+    fn test_blockade_raises_prices_then_ends() {
+        let (mut market, grain) = harbour_market();
+        market.start_event(MarketEventKind::Blockade, "Harbour", 5);
+        run(&mut market, 5);
+
+        assert!(market.events.is_empty());
+        let blockaded = market.good(grain, "Harbour").unwrap().price;
+        assert!(blockaded > 10, "price was {}", blockaded);
+    }
+
+    #[test]
+    fn test_festival_only_affects_its_location() {
+        let (mut market, grain) = harbour_market();
+        market.add_good(MarketGood::new(grain, "Capital", 10, 5.0, 5.0).unwrap());
+        market.start_event(MarketEventKind::Festival, "Capital", 10);
+        run(&mut market, 6);
+
+        assert_eq!(market.good(grain, "Harbour").unwrap().price, 10);
+        assert!(market.good(grain, "Capital").unwrap().price > 10);
+        assert!(market.reference_price(grain).unwrap() > 10);
+    }
+
+    #[test]
+    fn test_purchases_drain_stock_and_push_price_up() {
+        let (mut market, grain) = harbour_market();
+        let bought = market.purchase(grain, "Harbour", 30).unwrap();
+        assert_eq!(
+            bought,
+            Purchase {
+                unit_price: 10,
+                total: 300
+            }
+        );
+        assert_eq!(market.good(grain, "Harbour").unwrap().supply, 20.0);
+
+        market.tick();
+        assert!(market.good(grain, "Harbour").unwrap().price > 10);
+
+        assert_eq!(
+            market.purchase(grain, "Harbour", 1_000),
+            Err(MarketError::OutOfStock {
+                requested: 1_000,
+                available: 20
+            })
+        );
+        assert!(matches!(
+            market.purchase(grain, "Nowhere", 1),
+            Err(MarketError::UnknownGood(..))
+        ));
+    }
+
+    #[test]
+    fn test_prices_stay_within_bounds() {
+        let (mut market, grain) = harbour_market();
+        market.start_event(MarketEventKind::Blockade, "Harbour", 1_000);
+        run(&mut market, 200);
+
+        assert_eq!(market.good(grain, "Harbour").unwrap().price, 40);
+    }
+
+    #[test]
+    fn test_update_values_writes_entity_value() {
+        let db = Database::new(":memory:").unwrap();
+        let grain = EconomicEntity::new("Grain", 10);
+        db.insert_economic_entity(&grain).unwrap();
+
+        let mut market = Market::new();
+        market.add_good(MarketGood::new(grain.id, "Harbour", 10, 5.0, 5.0).unwrap());
+        market.start_event(MarketEventKind::Blockade, "Harbour", 10);
+        run(&mut market, 10);
+
+        assert_eq!(market.update_values(&db).unwrap(), 1);
+        let stored = db.get_economic_entity(grain.id).unwrap().unwrap();
+        assert_eq!(Some(stored.value), market.reference_price(grain.id));
+        assert!(stored.value > 10);
+    }
+}
@@ -1,7 +1,19 @@
 //! Systems module - contains game system functions (dice rolling, combat, etc.)
 //!
+//! Implemented so far:
+//! - Dice pool rolling (`dice`)
+//! - Contested and resisted rolls (`contested`)
+//! - Carrying capacity and encumbrance penalties (`encumbrance`)
+//! - Market supply, demand and price simulation (`market`)
+//! - Crafting items from data-file recipes (`crafting`)
+//! - FoundryVTT actor import and export (`foundry`)
+//!
 //! This is a placeholder for future game systems like:
-//! - Dice rolling mechanics
 //! - Combat calculations
-//! - Economy systems
 //! - World simulation
+pub mod contested;
+pub mod crafting;
+pub mod dice;
+pub mod encumbrance;
+pub mod foundry;
+pub mod market;
@@ -11,7 +11,10 @@ use ttdigirpg::demo::demo;
 /// Usage:
 ///   cargo run           - Runs the API server (default)
 ///   cargo run -- --demo - Runs the character creation demo
-///   cargo run -- --server - Explicitly runs the API server
+///   cargo run -- --server [flags] - Runs the API server with config flags (see `api::config`)
+///   cargo run -- --foundry-export <game> <character> [template] - Prints a Foundry actor
+///   cargo run -- --foundry-import <game> <file> [template] - Imports a Foundry actor
+///   cargo run -- --issue-token <game> <role> [character] [label] - Prints a new API token
 fn main() {
     let args: Vec<String> = env::args().collect();
 
@@ -29,27 +32,138 @@ fn main() {
         }
         "--server" => {
             println!("Starting API server mode...\n");
-            // Run the server by spawning the api_server binary logic
-            run_server();
+            run_server(args.get(2..).unwrap_or(&[]));
+        }
+        "--foundry-export" | "--foundry-import" => {
+            if let Err(e) = run_foundry(mode, &args[2..]) {
+                eprintln!("Foundry error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        "--issue-token" => {
+            if let Err(e) = run_issue_token(&args[2..]) {
+                eprintln!("Token error: {}", e);
+                std::process::exit(1);
+            }
         }
         _ => {
             eprintln!("Unknown argument: {}", mode);
-            eprintln!("Usage:");
-            eprintln!("  cargo run           - Run API server (default)");
-            eprintln!("  cargo run -- --demo - Run character demo");
-            eprintln!("  cargo run -- --server - Run API server explicitly");
+            print_usage();
             std::process::exit(1);
         }
     }
 }
 
-/// Runs the API server for testing
+/// Runs the API server configured from the config file, environment and `flags`
 #[tokio::main]
-async fn run_server() {
+async fn run_server(flags: &[String]) {
+    use ttdigirpg::api::config::ServerConfig;
     use ttdigirpg::api::server::run_api_server;
 
-    if let Err(e) = run_api_server().await {
+    let config = match ServerConfig::load(flags) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            print_usage();
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = run_api_server(&config).await {
         eprintln!("Server error: {}", e);
         std::process::exit(1);
     }
 }
+
+fn print_usage() {
+    eprintln!("Usage:");
+    eprintln!("  cargo run           - Run API server (default)");
+    eprintln!("  cargo run -- --demo - Run character demo");
+    eprintln!("  cargo run -- --server [--config <file>] [--bind <ip>] [--port <n>] [--origin <url>]... [--methods <list>] [--db <path>] [--no-auth]");
+    eprintln!("                       - Run API server with explicit settings (also read from ttdigirpg.toml and TTDIGIRPG_* variables)");
+    eprintln!("  cargo run -- --foundry-export <game> <character> [template] - Print a character as a Foundry actor");
+    eprintln!("  cargo run -- --foundry-import <game> <file> [template] - Import a Foundry actor, updating the character with its name if there is one");
+    eprintln!("  cargo run -- --issue-token <game> <gm|player|observer> [character] [label] - Print a new API token; players need their character's name");
+}
+
+/// Converts between stored characters and Foundry actor JSON files.
+fn run_foundry(mode: &str, args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    use ttdigirpg::api::config::ServerConfig;
+    use ttdigirpg::entities::database::Database;
+    use ttdigirpg::systems::foundry::{self, FoundryTemplates, DEFAULT_TEMPLATE};
+
+    let (game, target) = match args {
+        [game, target] | [game, target, _] => (game, target),
+        _ => {
+            print_usage();
+            std::process::exit(1);
+        }
+    };
+    let templates = FoundryTemplates::load_default()?;
+    let template = templates.get(args.get(2).map_or(DEFAULT_TEMPLATE, String::as_str))?;
+    // Use the same database the server would
+    let db_path = ServerConfig::load(&[])?.db_path;
+    if let Some(parent) = std::path::Path::new(&db_path).parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let db = Database::new(&db_path)?;
+
+    if mode == "--foundry-export" {
+        let record = db
+            .get_character(target, game)?
+            .ok_or_else(|| format!("no character named {} in {}", target, game))?;
+        let actor = foundry::export_character(&db, template, &record)?;
+        println!("{}", serde_json::to_string_pretty(&actor)?);
+    } else {
+        let document: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(target)?)?;
+        let existing = match document.get("name").and_then(|name| name.as_str()) {
+            Some(name) => db.get_character(name, game)?,
+            None => None,
+        };
+        let record =
+            foundry::import_character(&db, template, game, &document, existing.as_ref())?;
+        let action = if existing.is_some() { "Updated" } else { "Imported" };
+        println!("{} {} ({}) in {}", action, record.name, record.uuid, record.game);
+    }
+    Ok(())
+}
+
+/// Issues an API token from the command line, e.g. the first GM token of a game.
+fn run_issue_token(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    use ttdigirpg::api::config::ServerConfig;
+    use ttdigirpg::entities::database::Database;
+    use ttdigirpg::entities::tokens::Role;
+
+    let (game, role) = match args {
+        [game, role, ..] if args.len() <= 4 => (game, role.parse::<Role>()?),
+        _ => {
+            print_usage();
+            std::process::exit(1);
+        }
+    };
+    // Players name their character; other roles go straight to the label
+    let (character, label) = match role {
+        Role::Player => (args.get(2), args.get(3)),
+        _ if args.len() > 3 => return Err("only player tokens take a character".into()),
+        _ => (None, args.get(2)),
+    };
+
+    let db_path = ServerConfig::load(&[])?.db_path;
+    if let Some(parent) = std::path::Path::new(&db_path).parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let db = Database::new(&db_path)?;
+
+    let character_uuid = match character {
+        Some(name) => Some(
+            db.get_character(name, game)?
+                .ok_or_else(|| format!("no character named {} in {}", name, game))?
+                .uuid,
+        ),
+        None => None,
+    };
+    let (token, secret) =
+        db.issue_token(game, role, character_uuid.as_deref(), label.map(String::as_str))?;
+    eprintln!("Issued {} token {} for {}", token.role, token.id, token.game);
+    println!("{}", secret);
+    Ok(())
+}